@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lists::{rc_dll, slot_list, unsafe_dll, unsafe_queue};
+
+// Big enough that allocation and pointer chasing dominate, small enough to keep runs short.
+const N: i32 = 10_000;
+
+// Fill the list from the back and drain it from the front, i.e. use it as a queue.
+fn push_pop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("push_pop");
+
+    group.bench_function("unsafe_dll", |b| {
+        b.iter(|| {
+            let mut list = unsafe_dll::LinkedList::new();
+            for i in 0..N {
+                list.push_back(black_box(i));
+            }
+            while let Some(i) = list.pop_front() {
+                black_box(i);
+            }
+        })
+    });
+
+    group.bench_function("unsafe_queue", |b| {
+        b.iter(|| {
+            let mut list = unsafe_queue::List::new();
+            for i in 0..N {
+                list.push(black_box(i));
+            }
+            while let Some(i) = list.pop() {
+                black_box(i);
+            }
+        })
+    });
+
+    group.bench_function("rc_dll", |b| {
+        b.iter(|| {
+            let mut list = rc_dll::LinkedList::new();
+            for i in 0..N {
+                list.push_back(black_box(i));
+            }
+            while let Some(i) = list.pop_front() {
+                black_box(i);
+            }
+        })
+    });
+
+    group.bench_function("slot_list", |b| {
+        b.iter(|| {
+            let mut list = slot_list::LinkedList::new();
+            for i in 0..N {
+                list.push_back(black_box(i));
+            }
+            while let Some(i) = list.pop_front() {
+                black_box(i);
+            }
+        })
+    });
+
+    group.bench_function("vec_deque", |b| {
+        b.iter(|| {
+            let mut list = VecDeque::new();
+            for i in 0..N {
+                list.push_back(black_box(i));
+            }
+            while let Some(i) = list.pop_front() {
+                black_box(i);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+// Sum a prebuilt list, so only the walk itself is measured.
+fn iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+
+    let list: unsafe_dll::LinkedList<i32> = (0..N).collect();
+    group.bench_function("unsafe_dll", |b| {
+        b.iter(|| black_box(&list).iter().sum::<i32>())
+    });
+
+    let list: unsafe_queue::List<i32> = (0..N).collect();
+    group.bench_function("unsafe_queue", |b| {
+        b.iter(|| black_box(&list).iter().sum::<i32>())
+    });
+
+    // Clones every element, since it can't hand out plain references.
+    let list: rc_dll::LinkedList<i32> = (0..N).collect();
+    group.bench_function("rc_dll", |b| {
+        b.iter(|| black_box(&list).iter().sum::<i32>())
+    });
+
+    let list: slot_list::LinkedList<i32> = (0..N).collect();
+    group.bench_function("slot_list", |b| {
+        b.iter(|| black_box(&list).iter().sum::<i32>())
+    });
+
+    let list: VecDeque<i32> = (0..N).collect();
+    group.bench_function("vec_deque", |b| {
+        b.iter(|| black_box(&list).iter().sum::<i32>())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, push_pop, iterate);
+criterion_main!(benches);
@@ -1,7 +1,7 @@
-use std::{
-    cell::{Ref, RefCell, RefMut},
-    rc::Rc,
-};
+use alloc::rc::Rc;
+use core::cell::{Ref, RefCell, RefMut};
+
+use crate::list_ops::ListOps;
 
 type Link<T> = Option<Rc<RefCell<Node<T>>>>;
 
@@ -23,6 +23,7 @@ impl<T> Node<T> {
 pub struct List<T> {
     head: Link<T>,
     tail: Link<T>,
+    len: usize,
 }
 
 impl<T> Drop for List<T> {
@@ -36,9 +37,18 @@ impl<T> List<T> {
         Self {
             head: None,
             tail: None,
+            len: 0,
         }
     }
 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn into_iter(self) -> IntoIter<T> {
         IntoIter(self)
     }
@@ -57,6 +67,7 @@ impl<T> List<T> {
                 self.tail = Some(new_head)
             }
         }
+        self.len += 1;
     }
 
     pub fn push_back(&mut self, elem: T) {
@@ -73,6 +84,7 @@ impl<T> List<T> {
                 self.tail = Some(new_tail)
             }
         }
+        self.len += 1;
     }
 
     pub fn pop_front(&mut self) -> Option<T> {
@@ -93,6 +105,7 @@ impl<T> List<T> {
                     self.tail.take();
                 }
             }
+            self.len -= 1;
             Rc::try_unwrap(old_head).ok().unwrap().into_inner().elem
         })
     }
@@ -108,6 +121,7 @@ impl<T> List<T> {
                     self.head.take();
                 }
             }
+            self.len -= 1;
             Rc::try_unwrap(old_tail).ok().unwrap().into_inner().elem
         })
     }
@@ -136,6 +150,34 @@ impl<T> List<T> {
     }
 }
 
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ListOps<T> for List<T> {
+    fn push_front(&mut self, elem: T) {
+        self.push_front(elem)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn for_each<F: FnMut(&T)>(&self, mut f: F) {
+        let mut cur = self.head.clone();
+        while let Some(node) = cur {
+            f(&node.borrow().elem);
+            cur = node.borrow().next.clone();
+        }
+    }
+}
+
 pub struct IntoIter<T>(List<T>);
 
 impl<T> DoubleEndedIterator for IntoIter<T> {
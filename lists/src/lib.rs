@@ -1,5 +1,15 @@
+// The collections only need an allocator, so the crate builds without std. Tests still get std
+// for the test harness, Vec and format!.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
 pub mod doubly_linked_list;
 pub mod foo;
+pub mod list_ops;
+pub mod mpsc_queue;
 pub mod persistent_stack;
+pub mod rc_dll;
+pub mod slot_list;
 pub mod unsafe_dll;
 pub mod unsafe_queue;
@@ -0,0 +1,110 @@
+use alloc::vec::Vec;
+
+// The operations every mutable list in the crate can do, so tests and benchmarks can be written
+// once and run against all of them.
+//
+// Elements are visited with a closure instead of an iterator: the RefCell based lists can't hand
+// out references that outlive the borrow of their node.
+pub trait ListOps<T> {
+    fn push_front(&mut self, elem: T);
+
+    fn pop_front(&mut self) -> Option<T>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Visits the elements front to back.
+    fn for_each<F: FnMut(&T)>(&self, f: F);
+
+    fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut elems = Vec::with_capacity(self.len());
+        self.for_each(|elem| elems.push(elem.clone()));
+        elems
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+
+    use super::ListOps;
+    use crate::{doubly_linked_list, rc_dll, slot_list, unsafe_dll, unsafe_queue};
+
+    // Tiny LCG, so the operation sequence is random-looking but the same on every run.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            self.0 >> 33
+        }
+    }
+
+    // Runs the same random pushes and pops on the list and on a VecDeque and checks that
+    // they always agree.
+    fn matches_vec_deque<L: ListOps<i32> + Default>() {
+        let mut list = L::default();
+        let mut model = VecDeque::new();
+        let mut rng = Rng(42);
+
+        assert!(list.is_empty());
+        assert_eq!(list.pop_front(), None);
+
+        for i in 0..1000 {
+            // Lean towards pushing so the list actually grows
+            if rng.next().is_multiple_of(3) {
+                assert_eq!(list.pop_front(), model.pop_front());
+            } else {
+                list.push_front(i);
+                model.push_front(i);
+            }
+
+            assert_eq!(list.len(), model.len());
+            assert_eq!(list.is_empty(), model.is_empty());
+        }
+
+        assert_eq!(list.to_vec(), Vec::from(model.clone()));
+
+        // Drain it completely and make sure it still works afterwards
+        while let Some(elem) = list.pop_front() {
+            assert_eq!(Some(elem), model.pop_front());
+        }
+        assert!(list.is_empty());
+        list.push_front(7);
+        assert_eq!(list.to_vec(), vec![7]);
+    }
+
+    #[test]
+    fn unsafe_dll() {
+        matches_vec_deque::<unsafe_dll::LinkedList<i32>>();
+    }
+
+    #[test]
+    fn unsafe_queue() {
+        matches_vec_deque::<unsafe_queue::List<i32>>();
+    }
+
+    #[test]
+    fn rc_dll() {
+        matches_vec_deque::<rc_dll::LinkedList<i32>>();
+    }
+
+    #[test]
+    fn slot_list() {
+        matches_vec_deque::<slot_list::LinkedList<i32>>();
+    }
+
+    #[test]
+    fn doubly_linked_list() {
+        matches_vec_deque::<doubly_linked_list::List<i32>>();
+    }
+}
@@ -0,0 +1,237 @@
+// Lock-free multi-producer single-consumer queue
+//
+// Dmitry Vyukov's intrusive MPSC queue. Producers only ever touch `head`, with a single atomic
+// swap, so pushing never blocks or retries. The consumer owns `tail` and is the only one that
+// frees nodes, which is what lets us skip hazard pointers/epochs that a Michael–Scott queue needs.
+// The lessons:
+// - There is always at least one node in the queue, the "stub". Popping moves the value out of
+//   the node after the stub and turns that node into the new stub.
+// - Between a producer's swap and its store to `next`, the chain is briefly broken. The consumer
+//   can see that as an empty queue even though a push is in flight, so `pop` returning None
+//   means "nothing ready right now", not "nobody is pushing".
+// - Splitting the queue into a cloneable Producer and a single Consumer makes "single consumer"
+//   a type-level guarantee instead of a comment on an unsafe fn.
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    cell::UnsafeCell,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+struct Node<T> {
+    next: AtomicPtr<Node<T>>,
+    // None only for the stub.
+    value: Option<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: Option<T>) -> *mut Self {
+        Box::into_raw(Box::new(Node {
+            next: AtomicPtr::new(ptr::null_mut()),
+            value,
+        }))
+    }
+}
+
+struct Queue<T> {
+    // Most recently pushed node. Producers swap themselves in here.
+    head: AtomicPtr<Node<T>>,
+    // The stub. Only the consumer reads or writes this.
+    tail: UnsafeCell<*mut Node<T>>,
+}
+
+// The raw pointers opt us out of both. Values move from the producers' threads to the
+// consumer's, so T only has to be Send, like std's mpsc channels.
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        // Nobody else is left, so walk from the stub and free everything.
+        let mut cur = *self.tail.get_mut();
+        while !cur.is_null() {
+            unsafe {
+                let node = Box::from_raw(cur);
+                cur = node.next.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+pub struct Producer<T> {
+    queue: Arc<Queue<T>>,
+}
+
+pub struct Consumer<T> {
+    queue: Arc<Queue<T>>,
+}
+
+pub fn channel<T>() -> (Producer<T>, Consumer<T>) {
+    let stub = Node::new(None);
+    let queue = Arc::new(Queue {
+        head: AtomicPtr::new(stub),
+        tail: UnsafeCell::new(stub),
+    });
+
+    (
+        Producer {
+            queue: queue.clone(),
+        },
+        Consumer { queue },
+    )
+}
+
+impl<T> Producer<T> {
+    pub fn push(&self, value: T) {
+        let node = Node::new(Some(value));
+
+        // AcqRel: Release publishes our node to the next producer, Acquire makes sure the
+        // previous head is fully initialised before we write to it.
+        let prev = self.queue.head.swap(node, Ordering::AcqRel);
+
+        // The previous head can't have been freed: the consumer never frees the node that
+        // `head` points at, and until this store it can't get past `prev`.
+        unsafe { (*prev).next.store(node, Ordering::Release) };
+    }
+}
+
+impl<T> Clone for Producer<T> {
+    fn clone(&self) -> Self {
+        Producer {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<T> Consumer<T> {
+    // Takes the oldest value, or None if there is nothing ready yet.
+    pub fn pop(&mut self) -> Option<T> {
+        unsafe {
+            // &mut self makes us the only one touching the tail.
+            let tail = *self.queue.tail.get();
+            let next = (*tail).next.load(Ordering::Acquire);
+
+            if next.is_null() {
+                return None;
+            }
+
+            // `next` becomes the stub, so its value moves out and the old stub is freed.
+            *self.queue.tail.get() = next;
+            let value = (*next).value.take();
+            drop(Box::from_raw(tail));
+
+            value
+        }
+    }
+
+    // True once every Producer is gone, at which point nothing new can show up.
+    pub fn is_disconnected(&self) -> bool {
+        Arc::strong_count(&self.queue) == 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread};
+
+    use super::channel;
+
+    #[test]
+    fn basics() {
+        let (producer, mut consumer) = channel();
+
+        // Try to break an empty queue
+        assert_eq!(consumer.pop(), None);
+
+        producer.push(1);
+        producer.push(2);
+        assert_eq!(consumer.pop(), Some(1));
+
+        producer.push(3);
+        assert_eq!(consumer.pop(), Some(2));
+        assert_eq!(consumer.pop(), Some(3));
+
+        // Check exhaustion
+        assert_eq!(consumer.pop(), None);
+        producer.push(4);
+        assert_eq!(consumer.pop(), Some(4));
+        assert_eq!(consumer.pop(), None);
+
+        assert!(!consumer.is_disconnected());
+        drop(producer);
+        assert!(consumer.is_disconnected());
+    }
+
+    #[test]
+    fn drop_frees_unpopped_values() {
+        let value = Arc::new(());
+
+        let (producer, mut consumer) = channel();
+        for _ in 0..10 {
+            producer.push(value.clone());
+        }
+        consumer.pop();
+        assert_eq!(Arc::strong_count(&value), 10);
+
+        drop(producer);
+        drop(consumer);
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn stress() {
+        // Miri is slow and mostly cares about the interleavings, not the volume.
+        const PRODUCERS: usize = if cfg!(miri) { 3 } else { 8 };
+        const PER_PRODUCER: usize = if cfg!(miri) { 50 } else { 10_000 };
+
+        let (producer, mut consumer) = channel();
+
+        let handles: Vec<_> = (0..PRODUCERS)
+            .map(|id| {
+                let producer = producer.clone();
+                thread::spawn(move || {
+                    for seq in 0..PER_PRODUCER {
+                        producer.push((id, seq));
+                    }
+                })
+            })
+            .collect();
+        drop(producer);
+
+        // Each producer's values must arrive in the order it pushed them.
+        let mut next_seq = [0; PRODUCERS];
+        let mut received = 0;
+        while received < PRODUCERS * PER_PRODUCER {
+            match consumer.pop() {
+                Some((id, seq)) => {
+                    assert_eq!(seq, next_seq[id]);
+                    next_seq[id] += 1;
+                    received += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(consumer.pop(), None);
+        assert!(consumer.is_disconnected());
+        assert_eq!(next_seq, [PER_PRODUCER; PRODUCERS]);
+    }
+
+    mod auto_traits {
+        use std::{cell::Cell, rc::Rc};
+
+        use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+        use super::super::{Consumer, Producer};
+
+        assert_impl_all!(Producer<i32>: Send, Sync, Clone);
+        assert_impl_all!(Consumer<i32>: Send);
+        assert_impl_all!(Producer<Cell<i32>>: Send, Sync);
+        assert_not_impl_any!(Consumer<i32>: Clone);
+        assert_not_impl_any!(Producer<Rc<i32>>: Send, Sync);
+        assert_not_impl_any!(Consumer<Rc<i32>>: Send, Sync);
+    }
+}
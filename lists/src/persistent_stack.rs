@@ -1,10 +1,15 @@
-use std::rc::Rc;
+use alloc::rc::Rc;
+
+// A persistent list: nothing is ever mutated, "changing" the list gives back a new one
+// that shares its tail with the old one.
 
 type Link<T> = Option<Rc<Node<T>>>;
 
 struct Node<T> {
     elem: T,
     next: Link<T>,
+    // Length of the list starting at this node. Nodes never change, so this can't go stale.
+    len: usize,
 }
 
 pub struct Iter<'a, T> {
@@ -31,10 +36,24 @@ impl<T> List<T> {
             head: Some(Rc::new(Node {
                 elem,
                 next: self.head.clone(),
+                len: self.len() + 1,
             })),
         }
     }
 
+    // Same as prepend, named like the mutable lists in the crate.
+    pub fn push_front(&self, elem: T) -> List<T> {
+        self.prepend(elem)
+    }
+
+    pub fn len(&self) -> usize {
+        self.head.as_ref().map_or(0, |node| node.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
     pub fn tail(&self) -> List<T> {
         List {
             head: self.head.as_ref().and_then(|node| node.next.clone()),
@@ -46,6 +65,21 @@ impl<T> List<T> {
     }
 }
 
+// Cloning only bumps the count on the head node, both lists share every node.
+impl<T> Clone for List<T> {
+    fn clone(&self) -> Self {
+        List {
+            head: self.head.clone(),
+        }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
 
@@ -77,6 +111,8 @@ impl<T> Drop for List<T> {
 
 #[cfg(test)]
 mod test {
+    use std::rc::Rc;
+
     use super::List;
 
     #[test]
@@ -110,4 +146,64 @@ mod test {
         assert_eq!(iter.next(), Some(&2));
         assert_eq!(iter.next(), Some(&1));
     }
+
+    #[test]
+    fn len() {
+        let empty = List::new();
+        assert_eq!(empty.len(), 0);
+        assert!(empty.is_empty());
+
+        let list = empty.push_front(1).push_front(2).push_front(3);
+        assert_eq!(list.len(), 3);
+        assert!(!list.is_empty());
+        assert_eq!(list.tail().len(), 2);
+        assert_eq!(list.tail().tail().tail().len(), 0);
+        assert_eq!(list.tail().tail().tail().tail().len(), 0);
+
+        // The old list is untouched
+        assert_eq!(empty.len(), 0);
+    }
+
+    #[test]
+    fn structural_sharing() {
+        let tail = List::new().push_front(1).push_front(2);
+        let a = tail.push_front(3);
+        let b = tail.push_front(4);
+
+        // Both lists see their own head and the same tail
+        assert_eq!(a.iter().copied().collect::<Vec<_>>(), vec![3, 2, 1]);
+        assert_eq!(b.iter().copied().collect::<Vec<_>>(), vec![4, 2, 1]);
+        assert_eq!(tail.iter().copied().collect::<Vec<_>>(), vec![2, 1]);
+
+        let shared = tail.head.as_ref().unwrap();
+        assert!(Rc::ptr_eq(a.tail().head.as_ref().unwrap(), shared));
+        assert!(Rc::ptr_eq(b.tail().head.as_ref().unwrap(), shared));
+        // Held by `tail` itself and by the heads of `a` and `b`
+        assert_eq!(Rc::strong_count(shared), 3);
+
+        let cloned = a.clone();
+        assert!(Rc::ptr_eq(
+            cloned.head.as_ref().unwrap(),
+            a.head.as_ref().unwrap()
+        ));
+
+        // Dropping one list leaves the shared nodes alive for the others
+        drop(a);
+        drop(cloned);
+        assert_eq!(Rc::strong_count(tail.head.as_ref().unwrap()), 2);
+        assert_eq!(b.iter().copied().collect::<Vec<_>>(), vec![4, 2, 1]);
+    }
+
+    #[test]
+    fn long_list_drop() {
+        // Miri is slow, and checks the drop loop just as well on a shorter list.
+        const LEN: usize = if cfg!(miri) { 1_000 } else { 100_000 };
+
+        let mut list = List::new();
+        for i in 0..LEN {
+            list = list.push_front(i);
+        }
+        assert_eq!(list.len(), LEN);
+        drop(list);
+    }
 }
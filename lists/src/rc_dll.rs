@@ -0,0 +1,530 @@
+// Safe doubly-linked
+//
+// The same deque as unsafe_dll, but without a single line of unsafe.
+// The lessons:
+// - `next` links own the node after them, `prev` links are Weak. If both directions were Rc
+//   every pair of neighbours would be a reference cycle and nothing would ever be freed.
+// - The list keeps a strong `back` pointer, otherwise `back()` would have to upgrade a Weak
+//   into a local Rc and there would be nothing to hand out a Ref from.
+// - Every element lives behind its own RefCell, so accessors hand out Ref/RefMut guards instead
+//   of plain references.
+// - A borrowing iterator can't be written at all. Getting to the next node means borrowing the
+//   current one, and that borrow ends before the iterator can return. `iter` clones the elements
+//   instead, and mutation goes through `for_each_mut`.
+use alloc::rc::{Rc, Weak};
+use core::{
+    cell::{Ref, RefCell, RefMut},
+    fmt::Debug,
+    iter::FusedIterator,
+    marker::PhantomData,
+};
+
+use crate::list_ops::ListOps;
+
+type Link<T> = Option<Rc<RefCell<Node<T>>>>;
+
+type WeakLink<T> = Option<Weak<RefCell<Node<T>>>>;
+
+struct Node<T> {
+    elem: T,
+    next: Link<T>,
+    prev: WeakLink<T>,
+}
+
+impl<T> Node<T> {
+    fn new(elem: T) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Node {
+            elem,
+            next: None,
+            prev: None,
+        }))
+    }
+}
+
+pub struct LinkedList<T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+}
+
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        // Dropping `front` would free the nodes recursively through their `next` links,
+        // which overflows the stack on long lists. Pop them one at a time instead.
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T> LinkedList<T> {
+    pub fn new() -> Self {
+        Self {
+            front: None,
+            back: None,
+            len: 0,
+        }
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        let new_front = Node::new(elem);
+
+        match self.front.take() {
+            Some(old_front) => {
+                old_front.borrow_mut().prev = Some(Rc::downgrade(&new_front));
+                new_front.borrow_mut().next = Some(old_front);
+            }
+            None => self.back = Some(new_front.clone()),
+        }
+        self.front = Some(new_front);
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.front.take().map(|old_front| {
+            match old_front.borrow_mut().next.take() {
+                Some(new_front) => {
+                    new_front.borrow_mut().prev = None;
+                    self.front = Some(new_front);
+                }
+                None => {
+                    self.back.take();
+                }
+            }
+            self.len -= 1;
+            // Both the list and the neighbours have let go, so this is the last Rc.
+            Rc::try_unwrap(old_front).ok().unwrap().into_inner().elem
+        })
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        let new_back = Node::new(elem);
+
+        match self.back.take() {
+            Some(old_back) => {
+                new_back.borrow_mut().prev = Some(Rc::downgrade(&old_back));
+                old_back.borrow_mut().next = Some(new_back.clone());
+            }
+            None => self.front = Some(new_back.clone()),
+        }
+        self.back = Some(new_back);
+        self.len += 1;
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.back.take().map(|old_back| {
+            match old_back
+                .borrow_mut()
+                .prev
+                .take()
+                .and_then(|prev| prev.upgrade())
+            {
+                Some(new_back) => {
+                    new_back.borrow_mut().next.take();
+                    self.back = Some(new_back);
+                }
+                None => {
+                    self.front.take();
+                }
+            }
+            self.len -= 1;
+            Rc::try_unwrap(old_back).ok().unwrap().into_inner().elem
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn front(&self) -> Option<Ref<'_, T>> {
+        self.front
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.elem))
+    }
+
+    pub fn front_mut(&mut self) -> Option<RefMut<'_, T>> {
+        self.front
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.elem))
+    }
+
+    pub fn back(&self) -> Option<Ref<'_, T>> {
+        self.back
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.elem))
+    }
+
+    pub fn back_mut(&mut self) -> Option<RefMut<'_, T>> {
+        self.back
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.elem))
+    }
+
+    pub fn iter(&self) -> Iter<'_, T>
+    where
+        T: Clone,
+    {
+        Iter {
+            front: self.front.clone(),
+            back: self.back.clone(),
+            len: self.len,
+            _list: PhantomData,
+        }
+    }
+
+    // Stands in for iter_mut, front to back.
+    pub fn for_each_mut<F: FnMut(&mut T)>(&mut self, mut f: F) {
+        for node in self.nodes() {
+            f(&mut node.borrow_mut().elem);
+        }
+    }
+
+    fn nodes(&self) -> Nodes<T> {
+        Nodes {
+            next: self.front.clone(),
+        }
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Clone for LinkedList<T> {
+    fn clone(&self) -> Self {
+        self.iter().collect()
+    }
+}
+
+impl<T> Extend<T> for LinkedList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push_back(item);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for LinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Debug> Debug for LinkedList<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut list = f.debug_list();
+        for node in self.nodes() {
+            list.entry(&node.borrow().elem);
+        }
+        list.finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for LinkedList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .nodes()
+                .zip(other.nodes())
+                .all(|(a, b)| a.borrow().elem == b.borrow().elem)
+    }
+}
+
+impl<T: Eq> Eq for LinkedList<T> {}
+
+impl<T> ListOps<T> for LinkedList<T> {
+    fn push_front(&mut self, elem: T) {
+        self.push_front(elem)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn for_each<F: FnMut(&T)>(&self, mut f: F) {
+        for node in self.nodes() {
+            f(&node.borrow().elem);
+        }
+    }
+}
+
+// Walks the nodes front to back, holding an Rc to the next one.
+struct Nodes<T> {
+    next: Link<T>,
+}
+
+impl<T> Iterator for Nodes<T> {
+    type Item = Rc<RefCell<Node<T>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.take().inspect(|node| {
+            self.next = node.borrow().next.clone();
+        })
+    }
+}
+
+pub struct Iter<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    // The Rcs keep the nodes alive on their own, but the list must not change under us.
+    _list: PhantomData<&'a LinkedList<T>>,
+}
+
+// Having a Drop impl keeps the list borrowed until the iterator is gone. Without it the borrow
+// could end early while the Rcs are still held, and popping those nodes would then panic.
+impl<T> Drop for Iter<'_, T> {
+    fn drop(&mut self) {}
+}
+
+impl<'a, T: Clone> Iterator for Iter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.front.take().map(|node| {
+            let node = node.borrow();
+            self.len -= 1;
+            // The ends meet here, let go of the other one too
+            if self.len == 0 {
+                self.back = None;
+            } else {
+                self.front = node.next.clone();
+            }
+            node.elem.clone()
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T: Clone> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.back.take().map(|node| {
+            let node = node.borrow();
+            self.len -= 1;
+            if self.len == 0 {
+                self.front = None;
+            } else {
+                self.back = node.prev.as_ref().and_then(|prev| prev.upgrade());
+            }
+            node.elem.clone()
+        })
+    }
+}
+
+impl<'a, T: Clone> ExactSizeIterator for Iter<'a, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a, T: Clone> FusedIterator for Iter<'a, T> {}
+
+pub struct IntoIter<T> {
+    list: LinkedList<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {
+    fn len(&self) -> usize {
+        self.list.len
+    }
+}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::LinkedList;
+    use crate::unsafe_dll;
+
+    #[test]
+    fn test_basic_front() {
+        let mut list = LinkedList::new();
+
+        // Try to break an empty list
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+
+        // Try to break a one item list
+        list.push_front(10);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.pop_back(), Some(10));
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+
+        // Mess around
+        list.push_front(10);
+        list.push_front(20);
+        list.push_back(30);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_front(), Some(20));
+        assert_eq!(list.pop_back(), Some(30));
+        list.push_back(40);
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.pop_front(), Some(40));
+        assert_eq!(list.pop_front(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut list = LinkedList::new();
+        assert!(list.front().is_none());
+        assert!(list.back_mut().is_none());
+
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        assert_eq!(*list.front().unwrap(), 1);
+        assert_eq!(*list.back().unwrap(), 3);
+
+        *list.front_mut().unwrap() = 10;
+        *list.back_mut().unwrap() *= 10;
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.pop_back(), Some(30));
+        assert_eq!(*list.back().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut list: LinkedList<i32> = (1..=5).collect();
+
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(list.iter().rev().collect::<Vec<_>>(), vec![5, 4, 3, 2, 1]);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(5));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+        drop(iter);
+
+        list.for_each_mut(|elem| *elem *= 10);
+        assert_eq!(
+            list.into_iter().rev().collect::<Vec<_>>(),
+            vec![50, 40, 30, 20, 10]
+        );
+    }
+
+    #[test]
+    fn test_traits() {
+        let list: LinkedList<i32> = (1..=3).collect();
+        let cloned = list.clone();
+
+        assert_eq!(list, cloned);
+        assert_ne!(list, LinkedList::default());
+        assert_eq!(format!("{:?}", list), "[1, 2, 3]");
+        assert_eq!(format!("{:?}", LinkedList::<i32>::new()), "[]");
+    }
+
+    #[test]
+    fn test_no_leaks() {
+        let elem = Rc::new(0);
+
+        let mut list = LinkedList::new();
+        for _ in 0..10 {
+            list.push_back(elem.clone());
+            list.push_front(elem.clone());
+        }
+        list.pop_back();
+        list.pop_front();
+        assert_eq!(Rc::strong_count(&elem), 19);
+
+        // Weak back-links mean neighbours don't keep each other alive
+        drop(list);
+        assert_eq!(Rc::strong_count(&elem), 1);
+    }
+
+    #[test]
+    fn test_long_list_drop() {
+        // Miri is slow, and checks the drop loop just as well on a shorter list.
+        const LEN: i32 = if cfg!(miri) { 1_000 } else { 100_000 };
+
+        let list: LinkedList<i32> = (0..LEN).collect();
+        drop(list);
+    }
+
+    #[test]
+    fn test_same_as_unsafe_dll() {
+        let mut safe = LinkedList::new();
+        let mut unsafe_list = unsafe_dll::LinkedList::new();
+
+        for i in 0..20 {
+            match i % 5 {
+                0 | 1 => {
+                    safe.push_back(i);
+                    unsafe_list.push_back(i);
+                }
+                2 => {
+                    safe.push_front(i);
+                    unsafe_list.push_front(i);
+                }
+                3 => assert_eq!(safe.pop_front(), unsafe_list.pop_front()),
+                _ => assert_eq!(safe.pop_back(), unsafe_list.pop_back()),
+            }
+            assert_eq!(safe.len(), unsafe_list.len());
+            assert_eq!(safe.front().as_deref(), unsafe_list.front());
+            assert_eq!(safe.back().as_deref(), unsafe_list.back());
+        }
+
+        assert_eq!(
+            safe.iter().collect::<Vec<_>>(),
+            unsafe_list.iter().copied().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,603 @@
+// Arena doubly-linked
+//
+// All nodes live in one Vec and link to each other by index, so there is no unsafe and no
+// RefCell. Removed nodes leave a free slot behind which is chained into a free list and reused
+// by the next insert.
+// The lessons:
+// - Indices are just numbers, so the borrow checker can't tell a stale one from a live one.
+//   Reusing slots means a stale index silently points at some other element.
+// - Nodes sit next to each other in memory, which is much kinder to the cache than a Box per node.
+// - The Vec never shrinks. A list that was once big keeps its slots around for reuse.
+use alloc::vec::Vec;
+use core::{fmt::Debug, iter::FusedIterator};
+
+use crate::list_ops::ListOps;
+
+type Link = Option<usize>;
+
+struct Node<T> {
+    elem: T,
+    prev: Link,
+    next: Link,
+}
+
+enum Slot<T> {
+    Occupied(Node<T>),
+    Free { next_free: Link },
+}
+
+pub struct LinkedList<T> {
+    slots: Vec<Slot<T>>,
+    front: Link,
+    back: Link,
+    // Head of the chain of free slots.
+    free: Link,
+    len: usize,
+}
+
+impl<T> LinkedList<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            front: None,
+            back: None,
+            free: None,
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            ..Self::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Number of slots, occupied or not.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        self.link_between(None, self.front, elem);
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        self.link_between(self.back, None, elem);
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.front.map(|index| self.unlink(index))
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.back.map(|index| self.unlink(index))
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.front.map(|index| &self.node(index).elem)
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.front.map(|index| &mut self.node_mut(index).elem)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.back.map(|index| &self.node(index).elem)
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.back.map(|index| &mut self.node_mut(index).elem)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            list: self,
+            front: self.front,
+            back: self.back,
+            len: self.len,
+        }
+    }
+
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: None,
+            list: self,
+            index: None,
+        }
+    }
+
+    fn node(&self, index: usize) -> &Node<T> {
+        match &self.slots[index] {
+            Slot::Occupied(node) => node,
+            Slot::Free { .. } => unreachable!("link to a free slot"),
+        }
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut Node<T> {
+        match &mut self.slots[index] {
+            Slot::Occupied(node) => node,
+            Slot::Free { .. } => unreachable!("link to a free slot"),
+        }
+    }
+
+    // Puts a new node between `prev` and `next`, which must be neighbours (or the ends).
+    fn link_between(&mut self, prev: Link, next: Link, elem: T) -> usize {
+        let node = Slot::Occupied(Node { elem, prev, next });
+
+        let index = match self.free {
+            Some(index) => {
+                let Slot::Free { next_free } = core::mem::replace(&mut self.slots[index], node)
+                else {
+                    unreachable!("free list points at an occupied slot")
+                };
+                self.free = next_free;
+                index
+            }
+            None => {
+                self.slots.push(node);
+                self.slots.len() - 1
+            }
+        };
+
+        match prev {
+            Some(prev) => self.node_mut(prev).next = Some(index),
+            None => self.front = Some(index),
+        }
+        match next {
+            Some(next) => self.node_mut(next).prev = Some(index),
+            None => self.back = Some(index),
+        }
+
+        self.len += 1;
+        index
+    }
+
+    // Takes the node out of the list and hands its slot to the free list.
+    fn unlink(&mut self, index: usize) -> T {
+        let free = Slot::Free {
+            next_free: self.free,
+        };
+        let Slot::Occupied(node) = core::mem::replace(&mut self.slots[index], free) else {
+            unreachable!("unlinking a free slot")
+        };
+        self.free = Some(index);
+
+        match node.prev {
+            Some(prev) => self.node_mut(prev).next = node.next,
+            None => self.front = node.next,
+        }
+        match node.next {
+            Some(next) => self.node_mut(next).prev = node.prev,
+            None => self.back = node.prev,
+        }
+
+        self.len -= 1;
+        node.elem
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Extend<T> for LinkedList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push_back(item);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for LinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Debug> Debug for LinkedList<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T> ListOps<T> for LinkedList<T> {
+    fn push_front(&mut self, elem: T) {
+        self.push_front(elem)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn for_each<F: FnMut(&T)>(&self, f: F) {
+        self.iter().for_each(f)
+    }
+}
+
+pub struct Iter<'a, T> {
+    list: &'a LinkedList<T>,
+    front: Link,
+    back: Link,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.front.map(|index| {
+            let node = self.list.node(index);
+            self.front = node.next;
+            self.len -= 1;
+            &node.elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.back.map(|index| {
+            let node = self.list.node(index);
+            self.back = node.prev;
+            self.len -= 1;
+            &node.elem
+        })
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a LinkedList<T> {
+    type Item = &'a T;
+
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct IntoIter<T> {
+    list: LinkedList<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {
+    fn len(&self) -> usize {
+        self.list.len
+    }
+}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+// Same cursor as unsafe_dll: it sits on an element or on the "ghost" between the back and the
+// front of the list.
+pub struct CursorMut<'a, T> {
+    cur: Link,
+    list: &'a mut LinkedList<T>,
+    // None when pointing at the ghost.
+    index: Option<usize>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn move_next(&mut self) {
+        match self.cur {
+            Some(cur) => {
+                self.cur = self.list.node(cur).next;
+                self.index = self.index.filter(|_| self.cur.is_some()).map(|i| i + 1);
+            }
+            None => {
+                self.cur = self.list.front;
+                self.index = self.cur.map(|_| 0);
+            }
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        match self.cur {
+            Some(cur) => {
+                self.cur = self.list.node(cur).prev;
+                self.index = self.index.filter(|_| self.cur.is_some()).map(|i| i - 1);
+            }
+            None => {
+                self.cur = self.list.back;
+                self.index = self.cur.map(|_| self.list.len - 1);
+            }
+        }
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.cur.map(|cur| &mut self.list.node_mut(cur).elem)
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        let next = match self.cur {
+            Some(cur) => self.list.node(cur).next,
+            // The ghost's next is the front of the list
+            None => self.list.front,
+        };
+        next.map(|next| &mut self.list.node_mut(next).elem)
+    }
+
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        let prev = match self.cur {
+            Some(cur) => self.list.node(cur).prev,
+            // The ghost's prev is the back of the list
+            None => self.list.back,
+        };
+        prev.map(|prev| &mut self.list.node_mut(prev).elem)
+    }
+
+    // Inserts before the current element. At the ghost, that is the back of the list.
+    // The cursor keeps pointing at the same element.
+    pub fn insert_before(&mut self, elem: T) {
+        match self.cur {
+            Some(cur) => {
+                let prev = self.list.node(cur).prev;
+                self.list.link_between(prev, Some(cur), elem);
+                *self.index.as_mut().unwrap() += 1;
+            }
+            None => self.list.push_back(elem),
+        }
+    }
+
+    // Inserts after the current element. At the ghost, that is the front of the list.
+    // The cursor keeps pointing at the same element.
+    pub fn insert_after(&mut self, elem: T) {
+        match self.cur {
+            Some(cur) => {
+                let next = self.list.node(cur).next;
+                self.list.link_between(Some(cur), next, elem);
+            }
+            None => self.list.push_front(elem),
+        }
+    }
+
+    // Unlinks the current element and moves the cursor to the one after it
+    // (or the ghost, if it was the back).
+    pub fn remove_current(&mut self) -> Option<T> {
+        let cur = self.cur?;
+        let next = self.list.node(cur).next;
+
+        self.cur = next;
+        if next.is_none() {
+            self.index = None;
+        }
+
+        Some(self.list.unlink(cur))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LinkedList;
+
+    fn collect(list: &LinkedList<i32>) -> Vec<i32> {
+        let forward: Vec<i32> = list.iter().copied().collect();
+        let mut backward: Vec<i32> = list.iter().rev().copied().collect();
+        backward.reverse();
+        assert_eq!(forward, backward);
+        assert_eq!(forward.len(), list.len());
+        forward
+    }
+
+    #[test]
+    fn test_basic() {
+        let mut list = LinkedList::new();
+
+        // Try to break an empty list
+        assert!(list.is_empty());
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.pop_back(), None);
+
+        // Try to break a one item list
+        list.push_back(10);
+        assert_eq!(list.front(), Some(&10));
+        assert_eq!(list.back(), Some(&10));
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.pop_back(), None);
+
+        // Mess around
+        list.push_front(10);
+        list.push_front(20);
+        list.push_back(30);
+        assert_eq!(collect(&list), vec![20, 10, 30]);
+        assert_eq!(list.pop_back(), Some(30));
+        assert_eq!(list.pop_front(), Some(20));
+        list.push_back(40);
+        *list.front_mut().unwrap() += 1;
+        *list.back_mut().unwrap() += 1;
+        assert_eq!(collect(&list), vec![11, 41]);
+        assert_eq!(list.pop_front(), Some(11));
+        assert_eq!(list.pop_front(), Some(41));
+        assert_eq!(list.pop_front(), None);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_reuses_free_slots() {
+        let mut list: LinkedList<i32> = (0..4).collect();
+        assert_eq!(list.capacity(), 4);
+
+        list.pop_front();
+        list.pop_back();
+        list.push_front(10);
+        list.push_back(20);
+        assert_eq!(list.capacity(), 4);
+        assert_eq!(collect(&list), vec![10, 1, 2, 20]);
+
+        while list.pop_front().is_some() {}
+        list.extend(0..4);
+        assert_eq!(list.capacity(), 4);
+        assert_eq!(collect(&list), vec![0, 1, 2, 3]);
+
+        list.push_back(4);
+        assert_eq!(list.capacity(), 5);
+    }
+
+    #[test]
+    fn test_iter() {
+        let list: LinkedList<i32> = (1..=5).collect();
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&5));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), Some(&4));
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(format!("{:?}", list), "[1, 2, 3, 4, 5]");
+        assert_eq!(
+            list.into_iter().rev().collect::<Vec<_>>(),
+            vec![5, 4, 3, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_cursor_move_peek() {
+        let mut list: LinkedList<i32> = (1..=6).collect();
+        let mut cursor = list.cursor_mut();
+
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&mut 1));
+        assert_eq!(cursor.peek_prev(), Some(&mut 6));
+        assert_eq!(cursor.index(), None);
+
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 2));
+        assert_eq!(cursor.peek_next(), Some(&mut 3));
+        assert_eq!(cursor.peek_prev(), Some(&mut 1));
+        assert_eq!(cursor.index(), Some(1));
+
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 6));
+        assert_eq!(cursor.index(), Some(5));
+
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+
+        let mut empty = LinkedList::<i32>::new();
+        let mut cursor = empty.cursor_mut();
+        cursor.move_next();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+    }
+
+    #[test]
+    fn test_cursor_mut_insert_remove() {
+        let mut list: LinkedList<i32> = (1..=6).collect();
+        let mut cursor = list.cursor_mut();
+
+        cursor.move_next();
+        cursor.move_next();
+        cursor.insert_before(15);
+        cursor.insert_after(25);
+        assert_eq!(cursor.current(), Some(&mut 2));
+        assert_eq!(cursor.index(), Some(2));
+
+        assert_eq!(cursor.remove_current(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 25));
+        assert_eq!(cursor.index(), Some(2));
+        *cursor.current().unwrap() = 20;
+
+        // Insert at both ends through the ghost
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        cursor.insert_after(0);
+        cursor.insert_before(7);
+        assert_eq!(collect(&list), vec![0, 1, 15, 20, 3, 4, 5, 6, 7]);
+
+        // Remove the back and land on the ghost
+        let mut cursor = list.cursor_mut();
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(7));
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(collect(&list), vec![0, 1, 15, 20, 3, 4, 5, 6]);
+
+        // Removed slots get reused by the next inserts
+        assert_eq!(list.capacity(), 9);
+        list.push_front(-1);
+        assert_eq!(list.capacity(), 9);
+        assert_eq!(collect(&list), vec![-1, 0, 1, 15, 20, 3, 4, 5, 6]);
+    }
+}
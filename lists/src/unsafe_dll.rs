@@ -1,5 +1,15 @@
 // Unsafe doubly-linked
-use std::{marker::PhantomData, ptr::NonNull};
+use alloc::boxed::Box;
+use core::{
+    cmp::Ordering,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    marker::PhantomData,
+    ptr::NonNull,
+};
+
+use crate::list_ops::ListOps;
 
 pub struct LinkedList<T> {
     front: Link<T>,
@@ -74,10 +84,218 @@ impl<T> LinkedList<T> {
         }
     }
 
+    pub fn push_back(&mut self, elem: T) {
+        unsafe {
+            let new_node = Node {
+                front: None,
+                back: None,
+                elem,
+            };
+            let new_back = NonNull::new_unchecked(Box::into_raw(Box::new(new_node)));
+
+            if let Some(old_back) = self.back {
+                // Non-empty list. Mirror of push_front: the old back gets a `back` (next) node.
+                (*old_back.as_ptr()).back = Some(new_back);
+                (*new_back.as_ptr()).front = Some(old_back);
+            } else {
+                // Empty list! The new element is also the front
+                self.front = Some(new_back);
+            }
+            self.back = Some(new_back);
+            self.len += 1;
+        }
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        unsafe {
+            self.back.map(|node| {
+                let boxed_node = Box::from_raw(node.as_ptr());
+                let data = boxed_node.elem;
+
+                // `front` is `prev`. makes the back of the list the previous node.
+                self.back = boxed_node.front;
+                if let Some(new_back) = self.back {
+                    (*new_back.as_ptr()).back = None;
+                } else {
+                    // The list is empty now, so front has to be cleared as well.
+                    self.front = None;
+                }
+                self.len -= 1;
+                data
+            })
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // The front variant of `Extend`. Every element is pushed onto the front,
+    // so they end up in the reverse order of the iterator.
+    pub fn extend_front<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push_front(item);
+        }
+    }
+
+    // Splits the list in two at the given index. Returns everything from `at` and onwards,
+    // and keeps [0, at) in this list.
+    pub fn split_off(&mut self, at: usize) -> LinkedList<T> {
+        assert!(at <= self.len, "Cannot split off at a nonexistent index");
+
+        if at == 0 {
+            return core::mem::take(self);
+        }
+
+        // Walk to the last element that stays in this list, from whichever end is closer.
+        let len = self.len;
+        let mut cursor = self.cursor_mut();
+        if at - 1 < len / 2 {
+            for _ in 0..at {
+                cursor.move_next();
+            }
+        } else {
+            for _ in 0..len - at + 1 {
+                cursor.move_prev();
+            }
+        }
+
+        cursor.split_after()
+    }
+
+    // Moves all elements of `other` onto the back of this list in O(1), leaving `other` empty.
+    pub fn append(&mut self, other: &mut LinkedList<T>) {
+        let Some(back) = self.back else {
+            // We are empty, so just take over the other list's nodes
+            core::mem::swap(self, other);
+            return;
+        };
+
+        if let Some(other_front) = other.front.take() {
+            unsafe {
+                (*back.as_ptr()).back = Some(other_front);
+                (*other_front.as_ptr()).front = Some(back);
+            }
+            self.back = other.back.take();
+            self.len += other.len;
+            other.len = 0;
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        unsafe { self.node_at(index).map(|node| &(*node.as_ptr()).elem) }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        unsafe { self.node_at(index).map(|node| &mut (*node.as_ptr()).elem) }
+    }
+
+    // Inserts so that `elem` ends up at `index`, shifting everything after it back.
+    // Panics if `index > len`, like Vec::insert.
+    pub fn insert(&mut self, index: usize, elem: T) {
+        assert!(index <= self.len, "Cannot insert at a nonexistent index");
+
+        if index == self.len {
+            self.push_back(elem);
+            return;
+        }
+
+        let node = self.node_at(index).unwrap();
+        unsafe {
+            match (*node.as_ptr()).front {
+                None => self.push_front(elem),
+                Some(prev) => {
+                    let new_node = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
+                        front: Some(prev),
+                        back: Some(node),
+                        elem,
+                    })));
+
+                    (*prev.as_ptr()).back = Some(new_node);
+                    (*node.as_ptr()).front = Some(new_node);
+                    self.len += 1;
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        let node = self.node_at(index)?;
+        unsafe { Some(self.unlink(node)) }
+    }
+
+    // Walks to the node at `index` from whichever end is closer. O(min(index, len - index)).
+    fn node_at(&self, index: usize) -> Link<T> {
+        if index >= self.len {
+            return None;
+        }
+
+        unsafe {
+            if index < self.len / 2 {
+                let mut node = self.front;
+                for _ in 0..index {
+                    node = (*node?.as_ptr()).back;
+                }
+                node
+            } else {
+                let mut node = self.back;
+                for _ in 0..self.len - index - 1 {
+                    node = (*node?.as_ptr()).front;
+                }
+                node
+            }
+        }
+    }
+
+    // Keeps only the elements the predicate returns true for, in order.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.extract_if(|elem| !f(elem)).for_each(drop);
+    }
+
+    // Lazily removes the elements the predicate returns true for, yielding them in order.
+    // Elements that were not visited yet when the iterator is dropped stay in the list.
+    pub fn extract_if<F: FnMut(&mut T) -> bool>(&mut self, pred: F) -> ExtractIf<'_, T, F> {
+        ExtractIf {
+            next: self.front,
+            list: self,
+            pred,
+        }
+    }
+
+    // Removes the node from the list and frees it, fixing up its neighbours' links.
+    // Safety: `node` has to be a node of this list.
+    unsafe fn unlink(&mut self, node: NonNull<Node<T>>) -> T {
+        let boxed_node = Box::from_raw(node.as_ptr());
+        let prev = boxed_node.front;
+        let next = boxed_node.back;
+
+        match prev {
+            Some(prev) => (*prev.as_ptr()).back = next,
+            None => self.front = next,
+        }
+        match next {
+            Some(next) => (*next.as_ptr()).front = prev,
+            None => self.back = prev,
+        }
+        self.len -= 1;
+
+        boxed_node.elem
+    }
+
+    // The cursor starts at the "ghost" element, which sits between the back and the front
+    // of the list. Moving next from the ghost goes to the front, moving prev goes to the back.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            cur: None,
+            list: self,
+            index: None,
+        }
+    }
+
     pub fn front(&self) -> Option<&T> {
         unsafe { self.front.map(|node| &(*node.as_ptr()).elem) }
         // Can also write it like this with the `?` operator
@@ -87,6 +305,14 @@ impl<T> LinkedList<T> {
     pub fn front_mut(&mut self) -> Option<&mut T> {
         unsafe { self.front.map(|node| &mut (*node.as_ptr()).elem) }
     }
+
+    pub fn back(&self) -> Option<&T> {
+        unsafe { self.back.map(|node| &(*node.as_ptr()).elem) }
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.back.map(|node| &mut (*node.as_ptr()).elem) }
+    }
     pub fn iter(&self) -> Iter<T> {
         Iter {
             front: self.front,
@@ -95,6 +321,125 @@ impl<T> LinkedList<T> {
             _boo: PhantomData,
         }
     }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            front: self.front,
+            back: self.back,
+            len: self.len,
+            _boo: PhantomData,
+        }
+    }
+}
+
+// NonNull opts us out of Send and Sync. Owning the nodes makes the list exactly as
+// thread-safe as a Box<T>, so we opt back in with the same bounds as std's LinkedList.
+unsafe impl<T: Send> Send for LinkedList<T> {}
+unsafe impl<T: Sync> Sync for LinkedList<T> {}
+
+// Iter is a &LinkedList, so sending it means sharing the elements.
+unsafe impl<T: Sync> Send for Iter<'_, T> {}
+unsafe impl<T: Sync> Sync for Iter<'_, T> {}
+
+// IterMut and CursorMut are a &mut LinkedList.
+unsafe impl<T: Send> Send for IterMut<'_, T> {}
+unsafe impl<T: Sync> Sync for IterMut<'_, T> {}
+
+unsafe impl<T: Send> Send for CursorMut<'_, T> {}
+unsafe impl<T: Sync> Sync for CursorMut<'_, T> {}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Clone for LinkedList<T> {
+    fn clone(&self) -> Self {
+        let mut new_list = Self::new();
+        for item in self {
+            new_list.push_back(item.clone());
+        }
+        new_list
+    }
+}
+
+impl<T> Extend<T> for LinkedList<T> {
+    // Pushes onto the back, so the elements keep the iterator's order.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push_back(item);
+        }
+    }
+}
+
+impl<'a, T: Copy> Extend<&'a T> for LinkedList<T> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<T> FromIterator<T> for LinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = Self::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Debug> Debug for LinkedList<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for LinkedList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+}
+
+impl<T: Eq> Eq for LinkedList<T> {}
+
+impl<T: PartialOrd> PartialOrd for LinkedList<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other)
+    }
+}
+
+impl<T: Ord> Ord for LinkedList<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other)
+    }
+}
+
+impl<T: Hash> Hash for LinkedList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Hash the length first, like slices do, so that lists of lists don't collide
+        // just because their elements line up the same when flattened.
+        self.len().hash(state);
+        for item in self {
+            item.hash(state);
+        }
+    }
+}
+
+impl<T> ListOps<T> for LinkedList<T> {
+    fn push_front(&mut self, elem: T) {
+        self.push_front(elem)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        self.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn for_each<F: FnMut(&T)>(&self, f: F) {
+        self.iter().for_each(f)
+    }
 }
 
 // This will not work because *mut T is invariant over T
@@ -119,6 +464,12 @@ impl<'a, T> Iterator for Iter<'a, T> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
+        // `len` and not the links decides when we are done, since iterating from both ends
+        // means front and back will pass each other in the middle.
+        if self.len == 0 {
+            return None;
+        }
+
         self.front.map(|node| unsafe {
             self.len -= 1;
             // New front would be current front's next node
@@ -132,6 +483,29 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.back.map(|node| unsafe {
+            self.len -= 1;
+            // New back would be current back's previous node
+            self.back = (*node.as_ptr()).front;
+            &(*node.as_ptr()).elem
+        })
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
 impl<'a, T> IntoIterator for &'a LinkedList<T> {
     type Item = &'a T;
 
@@ -142,47 +516,1130 @@ impl<'a, T> IntoIterator for &'a LinkedList<T> {
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::LinkedList;
+pub struct IterMut<'a, T> {
+    front: Link<T>,
+    back: Link<T>,
+    len: usize,
+    // `&'a mut T` makes IterMut invariant over T and ties it to the mutable borrow of the list,
+    // so only one IterMut (and no Iter) can exist at a time.
+    _boo: PhantomData<&'a mut T>,
+}
 
-    #[test]
-    fn test_basic_front() {
-        let mut list = LinkedList::new();
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
 
-        // Try to break an empty list
-        assert_eq!(list.len(), 0);
-        assert_eq!(list.pop_front(), None);
-        assert_eq!(list.len(), 0);
+    fn next(&mut self) -> Option<Self::Item> {
+        // Every node is handed out at most once, so the &mut's never alias.
+        // We only go through the raw pointer, and never create a reference to the whole Node.
+        if self.len == 0 {
+            return None;
+        }
 
-        // Try to break a one item list
-        list.push_front(10);
-        assert_eq!(list.len(), 1);
-        assert_eq!(list.pop_front(), Some(10));
-        assert_eq!(list.len(), 0);
-        assert_eq!(list.pop_front(), None);
-        assert_eq!(list.len(), 0);
+        self.front.map(|node| unsafe {
+            self.len -= 1;
+            self.front = (*node.as_ptr()).back;
+            &mut (*node.as_ptr()).elem
+        })
+    }
 
-        // Mess around
-        list.push_front(10);
-        assert_eq!(list.len(), 1);
-        list.push_front(20);
-        assert_eq!(list.len(), 2);
-        list.push_front(30);
-        assert_eq!(list.len(), 3);
-        assert_eq!(list.pop_front(), Some(30));
-        assert_eq!(list.len(), 2);
-        list.push_front(40);
-        assert_eq!(list.len(), 3);
-        assert_eq!(list.pop_front(), Some(40));
-        assert_eq!(list.len(), 2);
-        assert_eq!(list.pop_front(), Some(20));
-        assert_eq!(list.len(), 1);
-        assert_eq!(list.pop_front(), Some(10));
-        assert_eq!(list.len(), 0);
-        assert_eq!(list.pop_front(), None);
-        assert_eq!(list.len(), 0);
-        assert_eq!(list.pop_front(), None);
-        assert_eq!(list.len(), 0);
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        // Checking `len` makes sure front and back never hand out the same node.
+        if self.len == 0 {
+            return None;
+        }
+
+        self.back.map(|node| unsafe {
+            self.len -= 1;
+            self.back = (*node.as_ptr()).front;
+            &mut (*node.as_ptr()).elem
+        })
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<'a, T> FusedIterator for IterMut<'a, T> {}
+
+impl<'a, T> IntoIterator for &'a mut LinkedList<T> {
+    type Item = &'a mut T;
+
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+pub struct CursorMut<'a, T> {
+    cur: Link<T>,
+    list: &'a mut LinkedList<T>,
+    // None when pointing at the ghost.
+    index: Option<usize>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn move_next(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                // We are on a real element, go to its next (`back`)
+                self.cur = (*cur.as_ptr()).back;
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() += 1;
+                } else {
+                    // We just walked to the ghost
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            // We are at the ghost, and there is a real front element to go to
+            self.cur = self.list.front;
+            self.index = Some(0)
+        }
+        // else: We are at the ghost but the list is empty, so do nothing
+    }
+
+    pub fn move_prev(&mut self) {
+        if let Some(cur) = self.cur {
+            unsafe {
+                self.cur = (*cur.as_ptr()).front;
+                if self.cur.is_some() {
+                    *self.index.as_mut().unwrap() -= 1;
+                } else {
+                    self.index = None;
+                }
+            }
+        } else if !self.list.is_empty() {
+            self.cur = self.list.back;
+            self.index = Some(self.list.len - 1)
+        }
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        unsafe { self.cur.map(|node| &mut (*node.as_ptr()).elem) }
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        unsafe {
+            let next = if let Some(cur) = self.cur {
+                (*cur.as_ptr()).back
+            } else {
+                // The ghost's next is the front of the list
+                self.list.front
+            };
+
+            next.map(|node| &mut (*node.as_ptr()).elem)
+        }
+    }
+
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        unsafe {
+            let prev = if let Some(cur) = self.cur {
+                (*cur.as_ptr()).front
+            } else {
+                // The ghost's prev is the back of the list
+                self.list.back
+            };
+
+            prev.map(|node| &mut (*node.as_ptr()).elem)
+        }
+    }
+
+    // Inserts before the current element. At the ghost, that is the back of the list.
+    // The cursor keeps pointing at the same element.
+    pub fn insert_before(&mut self, elem: T) {
+        let Some(cur) = self.cur else {
+            self.list.push_back(elem);
+            return;
+        };
+
+        unsafe {
+            match (*cur.as_ptr()).front {
+                // Current is the front, so this is just a push_front
+                None => self.list.push_front(elem),
+                Some(prev) => {
+                    let new_node = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
+                        front: Some(prev),
+                        back: Some(cur),
+                        elem,
+                    })));
+
+                    (*prev.as_ptr()).back = Some(new_node);
+                    (*cur.as_ptr()).front = Some(new_node);
+                    self.list.len += 1;
+                }
+            }
+        }
+
+        // Everything from current and onwards moved one step
+        *self.index.as_mut().unwrap() += 1;
+    }
+
+    // Inserts after the current element. At the ghost, that is the front of the list.
+    // The cursor keeps pointing at the same element.
+    pub fn insert_after(&mut self, elem: T) {
+        let Some(cur) = self.cur else {
+            self.list.push_front(elem);
+            return;
+        };
+
+        unsafe {
+            match (*cur.as_ptr()).back {
+                None => self.list.push_back(elem),
+                Some(next) => {
+                    let new_node = NonNull::new_unchecked(Box::into_raw(Box::new(Node {
+                        front: Some(cur),
+                        back: Some(next),
+                        elem,
+                    })));
+
+                    (*next.as_ptr()).front = Some(new_node);
+                    (*cur.as_ptr()).back = Some(new_node);
+                    self.list.len += 1;
+                }
+            }
+        }
+    }
+
+    // Unlinks the current element and moves the cursor to the one after it
+    // (or the ghost, if it was the back).
+    pub fn remove_current(&mut self) -> Option<T> {
+        let cur = self.cur?;
+
+        unsafe {
+            let next = (*cur.as_ptr()).back;
+            let elem = self.list.unlink(cur);
+
+            self.cur = next;
+            if next.is_none() {
+                self.index = None;
+            }
+            // else: the next element took over the index of the removed one.
+
+            Some(elem)
+        }
+    }
+
+    // Returns everything before the current element as a new list, leaving the current
+    // element as the front of this list. At the ghost, the whole list is returned.
+    pub fn split_before(&mut self) -> LinkedList<T> {
+        let Some(cur) = self.cur else {
+            return core::mem::take(self.list);
+        };
+
+        unsafe {
+            let output_len = self.index.unwrap();
+            let prev = (*cur.as_ptr()).front;
+
+            let output = LinkedList {
+                // Nothing before current means there is nothing to split off.
+                front: prev.and(self.list.front),
+                back: prev,
+                len: output_len,
+                _boo: PhantomData,
+            };
+
+            // Break the links between the two lists
+            if let Some(prev) = prev {
+                (*cur.as_ptr()).front = None;
+                (*prev.as_ptr()).back = None;
+            }
+
+            self.list.front = Some(cur);
+            self.list.len -= output_len;
+            self.index = Some(0);
+
+            output
+        }
+    }
+
+    // Returns everything after the current element as a new list, leaving the current
+    // element as the back of this list. At the ghost, the whole list is returned.
+    pub fn split_after(&mut self) -> LinkedList<T> {
+        let Some(cur) = self.cur else {
+            return core::mem::take(self.list);
+        };
+
+        unsafe {
+            let output_len = self.list.len - self.index.unwrap() - 1;
+            let next = (*cur.as_ptr()).back;
+
+            let output = LinkedList {
+                front: next,
+                back: next.and(self.list.back),
+                len: output_len,
+                _boo: PhantomData,
+            };
+
+            if let Some(next) = next {
+                (*cur.as_ptr()).back = None;
+                (*next.as_ptr()).front = None;
+            }
+
+            self.list.back = Some(cur);
+            self.list.len -= output_len;
+            // The index of current does not change
+
+            output
+        }
+    }
+}
+
+pub struct ExtractIf<'a, T, F: FnMut(&mut T) -> bool> {
+    list: &'a mut LinkedList<T>,
+    // The next node to run the predicate on
+    next: Link<T>,
+    pred: F,
+}
+
+impl<'a, T, F: FnMut(&mut T) -> bool> Iterator for ExtractIf<'a, T, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.next {
+            unsafe {
+                // Move on before calling the predicate. If it panics, the list is untouched
+                // and still fully linked, so nothing leaks or gets freed twice.
+                self.next = (*node.as_ptr()).back;
+
+                if (self.pred)(&mut (*node.as_ptr()).elem) {
+                    return Some(self.list.unlink(node));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.list.len))
+    }
+}
+
+pub struct IntoIter<T> {
+    list: LinkedList<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.list.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {
+    fn len(&self) -> usize {
+        self.list.len
+    }
+}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+// Whatever was not iterated over is freed by the list's Drop.
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { list: self }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        rc::Rc,
+    };
+
+    use super::LinkedList;
+
+    #[test]
+    fn test_basic_front() {
+        let mut list = LinkedList::new();
+
+        // Try to break an empty list
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+
+        // Try to break a one item list
+        list.push_front(10);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+
+        // Mess around
+        list.push_front(10);
+        assert_eq!(list.len(), 1);
+        list.push_front(20);
+        assert_eq!(list.len(), 2);
+        list.push_front(30);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_front(), Some(30));
+        assert_eq!(list.len(), 2);
+        list.push_front(40);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_front(), Some(40));
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.pop_front(), Some(20));
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+    }
+
+    #[test]
+    fn test_basic_back() {
+        let mut list = LinkedList::new();
+
+        // Try to break an empty list
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_back(), None);
+        assert_eq!(list.len(), 0);
+
+        // Try to break a one item list
+        list.push_back(10);
+        assert_eq!(list.len(), 1);
+        assert_eq!(list.pop_back(), Some(10));
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_back(), None);
+        assert_eq!(list.len(), 0);
+
+        // Mess around
+        list.push_back(10);
+        list.push_back(20);
+        list.push_back(30);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_back(), Some(30));
+        list.push_back(40);
+        assert_eq!(list.pop_back(), Some(40));
+        assert_eq!(list.pop_back(), Some(20));
+        assert_eq!(list.pop_back(), Some(10));
+        assert_eq!(list.len(), 0);
+        assert_eq!(list.pop_back(), None);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut list = LinkedList::new();
+        assert_eq!(list.front(), None);
+        assert_eq!(list.back(), None);
+        assert_eq!(list.front_mut(), None);
+        assert_eq!(list.back_mut(), None);
+
+        // With a single element front and back are the same node
+        list.push_back(1);
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(list.back(), Some(&1));
+
+        list.push_front(0);
+        list.push_back(2);
+        assert_eq!(list.front(), Some(&0));
+        assert_eq!(list.back(), Some(&2));
+
+        *list.back_mut().unwrap() *= 10;
+        *list.front_mut().unwrap() += 5;
+        assert_eq!(list.front(), Some(&5));
+        assert_eq!(list.back(), Some(&20));
+
+        assert_eq!(list.pop_back(), Some(20));
+        assert_eq!(list.back(), Some(&1));
+        assert_eq!(list.pop_front(), Some(5));
+        assert_eq!(list.front(), Some(&1));
+        assert_eq!(list.back(), Some(&1));
+
+        assert_eq!(list.pop_back(), Some(1));
+        assert_eq!(list.front(), None);
+        assert_eq!(list.back(), None);
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut list = LinkedList::new();
+        assert_eq!(list.iter_mut().next(), None);
+
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+
+        for elem in list.iter_mut() {
+            *elem *= 10;
+        }
+
+        for elem in &mut list {
+            *elem += 1;
+        }
+
+        let mut iter = list.iter_mut();
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.next(), Some(&mut 11));
+        assert_eq!(iter.next(), Some(&mut 21));
+        assert_eq!(iter.next(), Some(&mut 31));
+        assert_eq!(iter.next(), None);
+
+        // Holding on to the references from the iterator at the same time is fine
+        let refs: Vec<&mut i32> = list.iter_mut().collect();
+        for elem in refs {
+            *elem -= 1;
+        }
+
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.front(), Some(&20));
+        assert_eq!(list.back(), Some(&30));
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut list = LinkedList::new();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        list.push_back(4);
+
+        let mut iter = list.into_iter();
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next_back(), Some(4));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next_back(), Some(3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        let mut list = LinkedList::new();
+        list.push_front(2);
+        list.push_front(1);
+        let collected: Vec<i32> = list.into_iter().collect();
+        assert_eq!(collected, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_into_iter_drops_remaining() {
+        let elem = Rc::new(0);
+        let mut list = LinkedList::new();
+        for _ in 0..5 {
+            list.push_back(elem.clone());
+        }
+
+        let mut iter = list.into_iter();
+        iter.next();
+        iter.next_back();
+        assert_eq!(Rc::strong_count(&elem), 4);
+
+        drop(iter);
+        assert_eq!(Rc::strong_count(&elem), 1);
+    }
+
+    #[test]
+    fn test_iter_both_ends() {
+        let mut list = LinkedList::new();
+        for i in 1..=5 {
+            list.push_back(i);
+        }
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&5));
+        assert_eq!(iter.next_back(), Some(&4));
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next_back(), Some(&3));
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+        // Fused: stays done
+        assert_eq!(iter.next(), None);
+
+        let reversed: Vec<&i32> = list.iter().rev().collect();
+        assert_eq!(reversed, vec![&5, &4, &3, &2, &1]);
+
+        // Meeting in the middle of an odd and even number of elements
+        let mut iter = list.iter_mut();
+        assert_eq!(iter.next_back(), Some(&mut 5));
+        assert_eq!(iter.next(), Some(&mut 1));
+        assert_eq!(iter.next(), Some(&mut 2));
+        assert_eq!(iter.next_back(), Some(&mut 4));
+        assert_eq!(iter.len(), 1);
+        assert_eq!(iter.next_back(), Some(&mut 3));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        for elem in list.iter_mut().rev().take(2) {
+            *elem *= 10;
+        }
+        let collected: Vec<i32> = list.into_iter().rev().collect();
+        assert_eq!(collected, vec![50, 40, 3, 2, 1]);
+    }
+
+    fn list_from(elems: &[i32]) -> LinkedList<i32> {
+        let mut list = LinkedList::new();
+        for elem in elems {
+            list.push_back(*elem);
+        }
+        list
+    }
+
+    fn check_links(list: &LinkedList<i32>) -> Vec<i32> {
+        // Walk both directions to make sure the links agree with each other and with len
+        let forward: Vec<i32> = list.iter().copied().collect();
+        let mut backward: Vec<i32> = list.iter().rev().copied().collect();
+        backward.reverse();
+        assert_eq!(forward, backward);
+        assert_eq!(forward.len(), list.len());
+        assert_eq!(list.front(), forward.first());
+        assert_eq!(list.back(), forward.last());
+        forward
+    }
+
+    #[test]
+    fn test_cursor_move_peek() {
+        let mut list = list_from(&[1, 2, 3, 4, 5, 6]);
+
+        let mut cursor = list.cursor_mut();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.peek_next(), Some(&mut 1));
+        assert_eq!(cursor.peek_prev(), Some(&mut 6));
+
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.peek_next(), Some(&mut 2));
+        assert_eq!(cursor.peek_prev(), None);
+        assert_eq!(cursor.index(), Some(0));
+
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 6));
+        assert_eq!(cursor.index(), Some(5));
+        assert_eq!(cursor.peek_next(), None);
+
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 3));
+        assert_eq!(cursor.index(), Some(2));
+
+        *cursor.current().unwrap() *= 10;
+        *cursor.peek_next().unwrap() *= 10;
+        *cursor.peek_prev().unwrap() *= 10;
+        assert_eq!(check_links(&list), vec![1, 20, 30, 40, 5, 6]);
+
+        // Moving around an empty list stays on the ghost
+        let mut list = LinkedList::<i32>::new();
+        let mut cursor = list.cursor_mut();
+        cursor.move_next();
+        assert_eq!(cursor.current(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.peek_prev(), None);
+    }
+
+    #[test]
+    fn test_cursor_insert() {
+        let mut list = list_from(&[2, 4]);
+
+        let mut cursor = list.cursor_mut();
+        // At the ghost, insert_before pushes to the back and insert_after to the front
+        cursor.insert_before(5);
+        cursor.insert_after(1);
+        assert_eq!(cursor.index(), None);
+
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 2));
+        cursor.insert_after(3);
+        assert_eq!(cursor.index(), Some(1));
+        cursor.insert_before(15);
+        assert_eq!(cursor.index(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 2));
+
+        cursor.move_prev();
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 5));
+        cursor.insert_after(6);
+        cursor.insert_before(45);
+        assert_eq!(cursor.index(), Some(6));
+
+        assert_eq!(check_links(&list), vec![1, 15, 2, 3, 4, 45, 5, 6]);
+
+        // Front and back edge cases
+        let mut list = list_from(&[1]);
+        let mut cursor = list.cursor_mut();
+        cursor.move_next();
+        cursor.insert_before(0);
+        cursor.insert_after(2);
+        assert_eq!(cursor.index(), Some(1));
+        assert_eq!(check_links(&list), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_cursor_remove() {
+        let mut list = list_from(&[1, 2, 3, 4]);
+
+        let mut cursor = list.cursor_mut();
+        assert_eq!(cursor.remove_current(), None);
+
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 3));
+        assert_eq!(cursor.index(), Some(1));
+
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(1));
+        assert_eq!(cursor.current(), Some(&mut 3));
+        assert_eq!(cursor.index(), Some(0));
+
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(4));
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.index(), None);
+        assert_eq!(check_links(&list), vec![3]);
+
+        let mut cursor = list.cursor_mut();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(3));
+        assert_eq!(cursor.current(), None);
+        assert!(list.is_empty());
+        assert_eq!(list.front(), None);
+        assert_eq!(list.back(), None);
+
+        list.push_back(7);
+        assert_eq!(check_links(&list), vec![7]);
+    }
+
+    #[test]
+    fn test_cursor_split() {
+        let mut list = list_from(&[1, 2, 3, 4, 5, 6]);
+
+        let mut cursor = list.cursor_mut();
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 3));
+
+        let before = cursor.split_before();
+        assert_eq!(cursor.index(), Some(0));
+        assert_eq!(cursor.current(), Some(&mut 3));
+        assert_eq!(check_links(&before), vec![1, 2]);
+
+        cursor.move_next();
+        let after = cursor.split_after();
+        assert_eq!(cursor.index(), Some(1));
+        assert_eq!(cursor.current(), Some(&mut 4));
+        assert_eq!(check_links(&after), vec![5, 6]);
+        assert_eq!(check_links(&list), vec![3, 4]);
+
+        // Splitting at the ends gives an empty list
+        let mut cursor = list.cursor_mut();
+        cursor.move_next();
+        let before = cursor.split_before();
+        assert_eq!(check_links(&before), vec![]);
+        cursor.move_next();
+        let after = cursor.split_after();
+        assert_eq!(check_links(&after), vec![]);
+        assert_eq!(check_links(&list), vec![3, 4]);
+
+        // Splitting at the ghost takes everything
+        let mut cursor = list.cursor_mut();
+        let all = cursor.split_after();
+        assert_eq!(check_links(&all), vec![3, 4]);
+        assert!(list.is_empty());
+
+        // The split off lists are fully independent
+        let mut before = before;
+        before.push_back(1);
+        let mut after = after;
+        after.push_front(2);
+        assert_eq!(check_links(&before), vec![1]);
+        assert_eq!(check_links(&after), vec![2]);
+    }
+
+    #[test]
+    fn test_split_off() {
+        for at in 0..=6 {
+            let mut list = list_from(&[0, 1, 2, 3, 4, 5]);
+            let tail = list.split_off(at);
+
+            let expected: Vec<i32> = (0..6).collect();
+            assert_eq!(check_links(&list), expected[..at]);
+            assert_eq!(check_links(&tail), expected[at..]);
+        }
+
+        let mut list = LinkedList::<i32>::new();
+        let tail = list.split_off(0);
+        assert!(list.is_empty());
+        assert!(tail.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_split_off_out_of_bounds() {
+        let mut list = list_from(&[1, 2]);
+        list.split_off(3);
+    }
+
+    #[test]
+    fn test_append() {
+        let mut list = list_from(&[1, 2]);
+        let mut other = list_from(&[3, 4, 5]);
+
+        list.append(&mut other);
+        assert_eq!(check_links(&list), vec![1, 2, 3, 4, 5]);
+        assert_eq!(check_links(&other), vec![]);
+
+        // Appending an empty list does nothing
+        list.append(&mut other);
+        assert_eq!(check_links(&list), vec![1, 2, 3, 4, 5]);
+
+        // Appending onto an empty list takes everything
+        let mut empty = LinkedList::new();
+        empty.append(&mut list);
+        assert_eq!(check_links(&empty), vec![1, 2, 3, 4, 5]);
+        assert_eq!(check_links(&list), vec![]);
+
+        // Both lists are still usable afterwards
+        list.push_back(6);
+        other.push_front(7);
+        empty.push_back(8);
+        assert_eq!(check_links(&list), vec![6]);
+        assert_eq!(check_links(&other), vec![7]);
+        assert_eq!(check_links(&empty), vec![1, 2, 3, 4, 5, 8]);
+
+        // And splitting undoes appending
+        let tail = empty.split_off(3);
+        assert_eq!(check_links(&empty), vec![1, 2, 3]);
+        assert_eq!(check_links(&tail), vec![4, 5, 8]);
+    }
+
+    fn hash_of(list: &LinkedList<i32>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        list.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_traits() {
+        let list = list_from(&[1, 2, 3]);
+
+        let cloned = list.clone();
+        assert_eq!(check_links(&cloned), vec![1, 2, 3]);
+        assert_eq!(list, cloned);
+        assert_eq!(hash_of(&list), hash_of(&cloned));
+
+        let default: LinkedList<i32> = Default::default();
+        assert!(default.is_empty());
+        assert_eq!(format!("{:?}", default), "[]");
+        assert_eq!(format!("{:?}", list), "[1, 2, 3]");
+
+        let shorter = list_from(&[1, 2]);
+        let bigger = list_from(&[1, 3]);
+        assert_ne!(list, shorter);
+        assert!(shorter < list);
+        assert!(list < bigger);
+        assert_eq!(list.cmp(&cloned), std::cmp::Ordering::Equal);
+        assert_eq!(bigger.cmp(&shorter), std::cmp::Ordering::Greater);
+        assert_ne!(hash_of(&list), hash_of(&shorter));
+
+        // Clones are independent
+        let mut cloned = cloned;
+        cloned.push_back(4);
+        assert_eq!(check_links(&list), vec![1, 2, 3]);
+        assert_eq!(check_links(&cloned), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_from_iter_extend() {
+        let mut list: LinkedList<i32> = (1..=3).collect();
+        assert_eq!(check_links(&list), vec![1, 2, 3]);
+
+        list.extend(vec![4, 5]);
+        list.extend(&[6]);
+        assert_eq!(check_links(&list), vec![1, 2, 3, 4, 5, 6]);
+
+        list.extend_front(vec![0, -1]);
+        assert_eq!(check_links(&list), vec![-1, 0, 1, 2, 3, 4, 5, 6]);
+
+        // Round trips through the iterators
+        let doubled: LinkedList<i32> = list.iter().map(|x| x * 2).collect();
+        assert_eq!(check_links(&doubled), vec![-2, 0, 2, 4, 6, 8, 10, 12]);
+
+        let reversed: LinkedList<i32> = list.into_iter().rev().collect();
+        assert_eq!(check_links(&reversed), vec![6, 5, 4, 3, 2, 1, 0, -1]);
+
+        let mut empty: LinkedList<i32> = std::iter::empty().collect();
+        assert!(empty.is_empty());
+        empty.extend(Vec::<i32>::new());
+        empty.extend_front(Vec::new());
+        assert_eq!(check_links(&empty), vec![]);
+    }
+
+    mod auto_traits {
+        use std::{cell::Cell, collections::linked_list as std_list, rc::Rc, sync::MutexGuard};
+
+        use static_assertions::{assert_impl_all, assert_not_impl_any};
+
+        use crate::unsafe_dll::{CursorMut, IntoIter, Iter, IterMut, LinkedList};
+
+        // Send + Sync
+        assert_impl_all!(LinkedList<i32>: Send, Sync);
+        assert_impl_all!(Iter<'static, i32>: Send, Sync);
+        assert_impl_all!(IterMut<'static, i32>: Send, Sync);
+        assert_impl_all!(IntoIter<i32>: Send, Sync);
+        assert_impl_all!(CursorMut<'static, i32>: Send, Sync);
+
+        // Neither
+        assert_not_impl_any!(LinkedList<Rc<i32>>: Send, Sync);
+        assert_not_impl_any!(Iter<'static, Rc<i32>>: Send, Sync);
+        assert_not_impl_any!(IterMut<'static, Rc<i32>>: Send, Sync);
+        assert_not_impl_any!(IntoIter<Rc<i32>>: Send, Sync);
+        assert_not_impl_any!(CursorMut<'static, Rc<i32>>: Send, Sync);
+
+        // Send but not Sync
+        assert_impl_all!(LinkedList<Cell<i32>>: Send);
+        assert_not_impl_any!(LinkedList<Cell<i32>>: Sync);
+        assert_not_impl_any!(Iter<'static, Cell<i32>>: Send, Sync);
+        assert_impl_all!(IterMut<'static, Cell<i32>>: Send);
+        assert_not_impl_any!(IterMut<'static, Cell<i32>>: Sync);
+
+        // Sync but not Send
+        assert_impl_all!(LinkedList<MutexGuard<'static, i32>>: Sync);
+        assert_not_impl_any!(LinkedList<MutexGuard<'static, i32>>: Send);
+        assert_impl_all!(Iter<'static, MutexGuard<'static, i32>>: Send, Sync);
+        assert_not_impl_any!(IterMut<'static, MutexGuard<'static, i32>>: Send);
+
+        // Same answers for std's LinkedList
+        assert_impl_all!(std_list::LinkedList<i32>: Send, Sync);
+        assert_not_impl_any!(std_list::LinkedList<Rc<i32>>: Send, Sync);
+        assert_impl_all!(std_list::LinkedList<Cell<i32>>: Send);
+        assert_not_impl_any!(std_list::LinkedList<Cell<i32>>: Sync);
+        assert_not_impl_any!(std_list::Iter<'static, Cell<i32>>: Send, Sync);
+        assert_impl_all!(std_list::IterMut<'static, Cell<i32>>: Send);
+        assert_not_impl_any!(std_list::IterMut<'static, Cell<i32>>: Sync);
+        assert_impl_all!(std_list::LinkedList<MutexGuard<'static, i32>>: Sync);
+        assert_not_impl_any!(std_list::LinkedList<MutexGuard<'static, i32>>: Send);
+        assert_impl_all!(std_list::Iter<'static, MutexGuard<'static, i32>>: Send, Sync);
+        assert_not_impl_any!(std_list::IterMut<'static, MutexGuard<'static, i32>>: Send);
+    }
+
+    #[test]
+    fn test_send_to_thread() {
+        let list = list_from(&[1, 2, 3]);
+
+        let sum = std::thread::spawn(move || list.iter().sum::<i32>())
+            .join()
+            .unwrap();
+        assert_eq!(sum, 6);
+
+        let mut list = list_from(&[1, 2, 3]);
+        std::thread::scope(|scope| {
+            let iter = list.iter_mut();
+            scope.spawn(move || iter.for_each(|elem| *elem *= 2));
+        });
+        assert_eq!(check_links(&list), vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut list: LinkedList<i32> = (1..=10).collect();
+        list.retain(|x| x % 3 != 0);
+        assert_eq!(check_links(&list), vec![1, 2, 4, 5, 7, 8, 10]);
+
+        // Removing the front and back
+        list.retain(|x| *x != 1 && *x != 10);
+        assert_eq!(check_links(&list), vec![2, 4, 5, 7, 8]);
+
+        list.retain(|_| true);
+        assert_eq!(check_links(&list), vec![2, 4, 5, 7, 8]);
+
+        list.retain(|_| false);
+        assert_eq!(check_links(&list), vec![]);
+        list.retain(|_| false);
+
+        list.push_back(1);
+        assert_eq!(check_links(&list), vec![1]);
+    }
+
+    #[test]
+    fn test_extract_if() {
+        let mut list: LinkedList<i32> = (1..=8).collect();
+
+        let evens: Vec<i32> = list.extract_if(|x| *x % 2 == 0).collect();
+        assert_eq!(evens, vec![2, 4, 6, 8]);
+        assert_eq!(check_links(&list), vec![1, 3, 5, 7]);
+
+        // The predicate can change the elements it keeps
+        let big: Vec<i32> = list
+            .extract_if(|x| {
+                *x *= 10;
+                *x > 40
+            })
+            .collect();
+        assert_eq!(big, vec![50, 70]);
+        assert_eq!(check_links(&list), vec![10, 30]);
+
+        // Dropping the iterator early keeps whatever was not visited yet
+        let mut list: LinkedList<i32> = (1..=6).collect();
+        let mut iter = list.extract_if(|_| true);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), Some(2));
+        drop(iter);
+        assert_eq!(check_links(&list), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_extract_if_panic_safety() {
+        let elem = Rc::new(0);
+        let mut list: LinkedList<(i32, Rc<i32>)> = (0..6).map(|i| (i, elem.clone())).collect();
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut calls = 0;
+            list.extract_if(|(i, _)| {
+                calls += 1;
+                if calls == 4 {
+                    panic!("predicate panicked");
+                }
+                *i % 2 == 0
+            })
+            .for_each(drop);
+        }));
+        assert!(result.is_err());
+
+        // 0 and 2 were removed and freed, everything else is still linked up.
+        let remaining: Vec<i32> = list.iter().map(|(i, _)| *i).collect();
+        assert_eq!(remaining, vec![1, 3, 4, 5]);
+        let reversed: Vec<i32> = list.iter().rev().map(|(i, _)| *i).collect();
+        assert_eq!(reversed, vec![5, 4, 3, 1]);
+        assert_eq!(list.len(), 4);
+        assert_eq!(Rc::strong_count(&elem), 5);
+
+        // Same for retain
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            list.retain(|(i, _)| {
+                if *i == 4 {
+                    panic!("predicate panicked");
+                }
+                *i != 1
+            })
+        }));
+        assert!(result.is_err());
+        let remaining: Vec<i32> = list.iter().map(|(i, _)| *i).collect();
+        assert_eq!(remaining, vec![3, 4, 5]);
+        assert_eq!(Rc::strong_count(&elem), 4);
+
+        drop(list);
+        assert_eq!(Rc::strong_count(&elem), 1);
+    }
+
+    #[test]
+    fn test_indexed_access() {
+        let mut list: LinkedList<i32> = (0..7).collect();
+
+        for i in 0..7 {
+            assert_eq!(list.get(i), Some(&(i as i32)));
+        }
+        assert_eq!(list.get(7), None);
+        assert_eq!(LinkedList::<i32>::new().get(0), None);
+
+        *list.get_mut(1).unwrap() = 10;
+        *list.get_mut(5).unwrap() = 50;
+        assert_eq!(list.get_mut(7), None);
+        assert_eq!(check_links(&list), vec![0, 10, 2, 3, 4, 50, 6]);
+
+        assert_eq!(list.remove(7), None);
+        assert_eq!(list.remove(0), Some(0));
+        assert_eq!(list.remove(5), Some(6));
+        assert_eq!(list.remove(1), Some(2));
+        assert_eq!(list.remove(2), Some(4));
+        assert_eq!(check_links(&list), vec![10, 3, 50]);
+
+        list.insert(0, 1);
+        list.insert(4, 5);
+        list.insert(2, 2);
+        list.insert(4, 4);
+        assert_eq!(check_links(&list), vec![1, 10, 2, 3, 4, 50, 5]);
+
+        let mut list = LinkedList::new();
+        list.insert(0, 1);
+        list.insert(0, 0);
+        assert_eq!(check_links(&list), vec![0, 1]);
+        assert_eq!(list.remove(1), Some(1));
+        assert_eq!(list.remove(0), Some(0));
+        assert_eq!(check_links(&list), vec![]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_insert_out_of_bounds() {
+        let mut list = list_from(&[1]);
+        list.insert(2, 2);
+    }
+
+    #[test]
+    fn test_both_ends() {
+        let mut list = LinkedList::new();
+
+        // One element pushed at one end can be popped from the other
+        list.push_front(1);
+        assert_eq!(list.pop_back(), Some(1));
+        assert_eq!(list.pop_front(), None);
+        list.push_back(2);
+        assert_eq!(list.pop_front(), Some(2));
+        assert_eq!(list.pop_back(), None);
+
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_front(), None);
+        assert_eq!(list.len(), 0);
+
+        // Leave some elements behind for the destructor
+        list.push_back(4);
+        list.push_front(5);
     }
 }
@@ -1,4 +1,7 @@
-use std::ptr;
+use alloc::boxed::Box;
+use core::ptr;
+
+use crate::list_ops::ListOps;
 
 // The lessons:
 // - Usage of *raw pointers* to assign new_tail to two locations.
@@ -7,26 +10,39 @@ use std::ptr;
 //   safe pointers introduce extra contraints (i.e. pointer aliasing, etc..) that we
 //   are not obeying with raw pointers.
 
-struct List<T> {
+pub struct List<T> {
     head: Link<T>,
     tail: Link<T>,
+    len: usize,
 }
 
-struct Iter<'a, T> {
+pub struct Iter<'a, T> {
     next: Option<&'a Node<T>>,
 }
 
+pub struct IterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+}
+
+pub struct IntoIter<T>(List<T>);
+
+// Pops whatever is left in the queue, even if the iterator isn't run to the end.
+pub struct Drain<'a, T> {
+    list: &'a mut List<T>,
+}
+
 type Link<T> = *mut Node<T>;
 
 impl<T> List<T> {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             head: ptr::null_mut(),
             tail: ptr::null_mut(),
+            len: 0,
         }
     }
 
-    fn push(&mut self, elem: T) {
+    pub fn push(&mut self, elem: T) {
         unsafe {
             let new_tail = Box::into_raw(Box::new(Node {
                 elem,
@@ -40,10 +56,26 @@ impl<T> List<T> {
             }
 
             self.tail = new_tail;
+            self.len += 1;
         }
     }
 
-    fn pop(&mut self) -> Option<T> {
+    // Jumps the queue. Cheap because the head is where singly-linked lists like to grow.
+    pub fn push_front(&mut self, elem: T) {
+        let new_head = Box::into_raw(Box::new(Node {
+            elem,
+            next: self.head,
+        }));
+
+        if self.tail.is_null() {
+            self.tail = new_head;
+        }
+
+        self.head = new_head;
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
         unsafe {
             if self.head.is_null() {
                 None
@@ -56,11 +88,26 @@ impl<T> List<T> {
                     self.tail = ptr::null_mut();
                 }
 
+                self.len -= 1;
+
                 Some(head.elem)
             }
         }
     }
-    fn iter(&self) -> Iter<'_, T> {
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain { list: self }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
         unsafe {
             Iter {
                 next: self.head.as_ref(),
@@ -68,12 +115,44 @@ impl<T> List<T> {
         }
     }
 
-    fn peek(&self) -> Option<&T> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        unsafe {
+            IterMut {
+                next: self.head.as_mut(),
+            }
+        }
+    }
+
+    pub fn peek(&self) -> Option<&T> {
         unsafe { self.head.as_ref().map(|node| &node.elem) }
     }
 
-    fn peek_mut(&self) -> Option<&mut T> {
-        todo!("Implement peek_mut if i feel like it when i come back")
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.head.as_mut().map(|node| &mut node.elem) }
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ListOps<T> for List<T> {
+    fn push_front(&mut self, elem: T) {
+        self.push_front(elem)
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        self.pop()
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn for_each<F: FnMut(&T)>(&self, f: F) {
+        self.iter().for_each(f)
     }
 }
 
@@ -96,6 +175,70 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            self.next.take().map(|node| {
+                self.next = node.next.as_mut();
+                &mut node.elem
+            })
+        }
+    }
+}
+
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop()
+    }
+}
+
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.list.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> Drop for Drain<'_, T> {
+    fn drop(&mut self) {
+        while self.list.pop().is_some() {}
+    }
+}
+
+impl<T> Extend<T> for List<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = List::new();
+        list.extend(iter);
+        list
+    }
+}
+
 struct Node<T> {
     elem: T,
     next: Link<T>,
@@ -142,6 +285,141 @@ mod test {
         assert_eq!(list.pop(), None);
     }
 
+    #[test]
+    fn len() {
+        let mut list = List::new();
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+
+        list.push(1);
+        list.push(2);
+        assert_eq!(list.len(), 2);
+        assert!(!list.is_empty());
+
+        // Exhaust the queue so both head and tail get reset
+        assert_eq!(list.pop(), Some(1));
+        assert_eq!(list.pop(), Some(2));
+        assert_eq!(list.pop(), None);
+        assert_eq!(list.len(), 0);
+        assert!(list.is_empty());
+
+        // Refill it, pushing through the reset tail pointer
+        list.push(3);
+        list.push(4);
+        list.push(5);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&3, &4, &5]);
+        assert_eq!(list.pop(), Some(3));
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.peek(), Some(&4));
+    }
+
+    #[test]
+    fn push_front() {
+        let mut list = List::new();
+
+        list.push_front(2);
+        list.push(3);
+        list.push_front(1);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&1, &2, &3]);
+
+        // Drain it through the front and make sure the tail got reset
+        assert_eq!(list.pop(), Some(1));
+        assert_eq!(list.pop(), Some(2));
+        assert_eq!(list.pop(), Some(3));
+        list.push_front(4);
+        list.push(5);
+        assert_eq!(list.pop(), Some(4));
+        assert_eq!(list.pop(), Some(5));
+        assert_eq!(list.pop(), None);
+    }
+
+    #[test]
+    fn peek() {
+        let mut list = List::new();
+        assert_eq!(list.peek(), None);
+        assert_eq!(list.peek_mut(), None);
+
+        list.push(1);
+        list.push(2);
+        assert_eq!(list.peek(), Some(&1));
+
+        if let Some(value) = list.peek_mut() {
+            *value = 42;
+        }
+        assert_eq!(list.peek(), Some(&42));
+        assert_eq!(list.pop(), Some(42));
+        assert_eq!(list.peek_mut(), Some(&mut 2));
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut list = List::new();
+        assert_eq!(list.iter_mut().next(), None);
+
+        list.push(1);
+        list.push(2);
+        list.push(3);
+
+        let mut iter = list.iter_mut();
+        assert_eq!(iter.next(), Some(&mut 1));
+        assert_eq!(iter.next(), Some(&mut 2));
+        assert_eq!(iter.next(), Some(&mut 3));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut list = List::new();
+        list.push(1);
+        list.push(2);
+        list.push(3);
+        assert_eq!(list.pop(), Some(1));
+        list.push(4);
+
+        let mut iter = list.into_iter();
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next(), Some(4));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn extend_and_collect() {
+        let mut list: List<i32> = (1..=3).collect();
+        assert_eq!(list.len(), 3);
+
+        list.extend(vec![4, 5]);
+        assert_eq!(list.len(), 5);
+        assert_eq!(list.pop(), Some(1));
+
+        list.extend(Vec::new());
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![&2, &3, &4, &5]);
+    }
+
+    #[test]
+    fn drain() {
+        let mut list: List<i32> = (1..=4).collect();
+
+        let mut drain = list.drain();
+        assert_eq!(drain.size_hint(), (4, Some(4)));
+        assert_eq!(drain.next(), Some(1));
+        assert_eq!(drain.next(), Some(2));
+        drop(drain);
+
+        // Dropping the drain early still empties the queue
+        assert!(list.is_empty());
+        assert_eq!(list.pop(), None);
+
+        // And the queue is usable afterwards
+        list.push(5);
+        list.extend([6, 7]);
+        assert_eq!(list.drain().collect::<Vec<_>>(), vec![5, 6, 7]);
+        assert!(list.is_empty());
+        assert_eq!(list.peek(), None);
+    }
+
     #[test]
     fn miri_food() {
         let mut list = List::new();
@@ -157,24 +435,28 @@ mod test {
 
         assert!(list.peek() == Some(&3));
         list.push(6);
-        // list.peek_mut().map(|x| *x *= 10);
-        assert!(list.peek() == Some(&3));
-        assert!(list.pop() == Some(3));
+        if let Some(x) = list.peek_mut() {
+            *x *= 10;
+        }
+        assert!(list.peek() == Some(&30));
+        assert!(list.pop() == Some(30));
 
-        // for elem in list.iter_mut() {
-        //     *elem *= 100;
-        // }
+        for elem in list.iter_mut() {
+            *elem *= 100;
+        }
 
         let mut iter = list.iter();
-        assert_eq!(iter.next(), Some(&4));
-        assert_eq!(iter.next(), Some(&5));
-        assert_eq!(iter.next(), Some(&6));
+        assert_eq!(iter.next(), Some(&400));
+        assert_eq!(iter.next(), Some(&500));
+        assert_eq!(iter.next(), Some(&600));
         assert_eq!(iter.next(), None);
         assert_eq!(iter.next(), None);
 
-        assert!(list.pop() == Some(4));
-        // list.peek_mut().map(|x| *x *= 10);
-        assert!(list.peek() == Some(&5));
+        assert!(list.pop() == Some(400));
+        if let Some(x) = list.peek_mut() {
+            *x *= 10;
+        }
+        assert!(list.peek() == Some(&5000));
         list.push(7);
 
         // Drop it on the ground and let the dtor (destructor) exercise itself
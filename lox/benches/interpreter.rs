@@ -0,0 +1,134 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use lox::{interpreter::Interpreter, parser::Parser, resolver::Resolver, scanner::Scanner};
+
+// Representative programs. None of them print, so the numbers are not dominated by stdout.
+fn programs() -> Vec<(&'static str, String)> {
+    let string_building = format!("var s = \"\";\n{}", "s = s + \"part\";\n".repeat(500));
+
+    let arithmetic = format!(
+        "var x = 0;\n{}",
+        "x = (x + 1) * 2 - x / 3 + 4 * (5 - 6);\n".repeat(500)
+    );
+
+    // A single long expression, so nearly all of the time is spent in binary operations.
+    let arithmetic_heavy = format!("1{};", " + 2 * 3 - 4 / 5".repeat(1000));
+
+    let deep_nesting = format!(
+        "{}var a = {}1{};{}",
+        "{ ".repeat(200),
+        "(".repeat(200),
+        ")".repeat(200),
+        " }".repeat(200)
+    );
+
+    // Dominated by calls.
+    let fib = "fun fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); } fib(15);".to_owned();
+
+    vec![
+        ("string_building", string_building),
+        ("arithmetic", arithmetic),
+        ("arithmetic_heavy", arithmetic_heavy),
+        ("deep_nesting", deep_nesting),
+        ("fib", fib),
+    ]
+}
+
+fn scanning(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+    for (name, source) in programs() {
+        group.bench_function(name, |b| {
+            b.iter(|| Scanner::new(black_box(source.as_bytes())).scan_tokens())
+        });
+    }
+    group.finish();
+}
+
+// Scanning time per line should stay the same as scripts get longer, scanning is linear.
+fn scanning_long_scripts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan_long_scripts");
+    let line = "var total = total + 1.5 * count; // \"running\" total\n";
+    for lines in [1000, 4000, 16000] {
+        let source = line.repeat(lines);
+
+        group.throughput(Throughput::Elements(lines as u64));
+        group.bench_function(lines.to_string(), |b| {
+            b.iter(|| Scanner::new(black_box(source.as_bytes())).scan_tokens())
+        });
+    }
+    group.finish();
+}
+
+fn parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, source) in programs() {
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Benchmark program should scan");
+
+        group.bench_function(name, |b| {
+            b.iter(|| Parser::new(black_box(tokens.clone())).parse())
+        });
+    }
+    group.finish();
+}
+
+fn interpreting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interpret");
+    for (name, source) in programs() {
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Benchmark program should scan");
+        let stmts = Parser::new(tokens)
+            .parse()
+            .expect("Benchmark program should parse");
+
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let interpreter = Interpreter::new();
+                Resolver::new(&interpreter)
+                    .resolve(black_box(&stmts))
+                    .expect("Benchmark program should resolve");
+                interpreter.interpret(&stmts)
+            })
+        });
+    }
+    group.finish();
+}
+
+// Repeatedly appending to the same string, which is quadratic with naive immutable strings.
+fn string_building(c: &mut Criterion) {
+    let mut group = c.benchmark_group("string_building");
+    for appends in [500, 2000] {
+        let source = format!(
+            "var s = \"\";\n{}",
+            "s = s + \"some longer part\";\n".repeat(appends)
+        );
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Benchmark program should scan");
+        let stmts = Parser::new(tokens)
+            .parse()
+            .expect("Benchmark program should parse");
+
+        group.bench_function(appends.to_string(), |b| {
+            b.iter(|| {
+                let interpreter = Interpreter::new();
+                Resolver::new(&interpreter)
+                    .resolve(black_box(&stmts))
+                    .expect("Benchmark program should resolve");
+                interpreter.interpret(&stmts)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    scanning,
+    scanning_long_scripts,
+    parsing,
+    interpreting,
+    string_building
+);
+criterion_main!(benches);
@@ -0,0 +1,243 @@
+use std::{fs, path::PathBuf};
+
+use crate::{
+    scanner::{Errors, Scanner},
+    token::{Literal, Token, TokenType},
+};
+
+// Where `lox` keeps scanned programs, relative to where it runs.
+pub const DEFAULT_DIR: &str = ".lox-cache";
+
+// The code that decides what the scanner turns a source into, and how that is written to an
+// entry. It is part of the key, so a lox built from different code never picks up entries of
+// another one, without anyone having to remember to bump a version.
+const SCANNER_CODE: [&str; 3] = [
+    include_str!("scanner.rs"),
+    include_str!("token.rs"),
+    include_str!("cache.rs"),
+];
+
+// Cache of scanned programs, keyed by a hash of their source.
+//
+// Rerunning an unchanged file skips scanning it. Scanning is linear, so this mostly pays off for
+// large programs and for running the same files over and over, like the test runner does. The
+// AST can't be written to disk (yet), parsing the cached tokens is cheap in comparison.
+//
+// An entry starts with the source it was scanned from, and is only used when that is the source
+// being scanned, so a hash collision can't hand out the tokens of another program.
+//
+// The cache is only an optimization: entries that can't be read or written are ignored
+// and the source is scanned as usual.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn scan(&self, source: &[u8]) -> Result<Vec<Token>, Errors> {
+        let path = self.dir.join(format!("{:016x}.tokens", Self::key(source)));
+
+        let cached = fs::read_to_string(&path).ok().and_then(|entry| {
+            let (scanned, tokens) = entry.split_once('\n')?;
+            (unescape(scanned)?.as_bytes() == source).then(|| decode(tokens))?
+        });
+        if let Some(tokens) = cached {
+            return Ok(tokens);
+        }
+
+        let tokens = Scanner::new(source).scan_tokens()?;
+
+        // Written to a temporary file first, so a concurrent reader never sees half an entry.
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|_| {
+                let source = escape(&String::from_utf8_lossy(source));
+                fs::write(&tmp, format!("{source}\n{}", encode(&tokens)))
+            })
+            .and_then(|_| fs::rename(&tmp, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+
+        Ok(tokens)
+    }
+
+    // 64 bit FNV-1a, which unlike the std hashers gives the same hash in every build.
+    fn key(source: &[u8]) -> u64 {
+        SCANNER_CODE
+            .iter()
+            .map(|code| code.as_bytes())
+            .chain([env!("CARGO_PKG_VERSION").as_bytes(), source])
+            .flatten()
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+}
+
+// One token per line: `type line offset lexeme literal`, separated by tabs.
+fn encode(tokens: &[Token]) -> String {
+    let mut out = String::new();
+
+    for token in tokens {
+        let literal = match token.literal() {
+            None => "-".to_owned(),
+            // Through the bits, so the number comes back exactly.
+            Some(Literal::Number(n)) => format!("n{:x}", n.to_bits()),
+            Some(Literal::Int(n)) => format!("i{n}"),
+            Some(Literal::String(s)) => format!("s{}", escape(&s)),
+            Some(Literal::True) => "t".to_owned(),
+            Some(Literal::False) => "f".to_owned(),
+            Some(Literal::Nil) => "0".to_owned(),
+        };
+
+        out.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            <&str>::from(token.token_type()),
+            token.line(),
+            token.offset(),
+            escape(token.lexeme()),
+            literal
+        ));
+    }
+
+    out
+}
+
+// Anything that doesn't look right means the entry is from something else, and is rescanned.
+fn decode(s: &str) -> Option<Vec<Token>> {
+    s.lines()
+        .map(|line| {
+            let mut fields = line.split('\t');
+            let token_type = fields.next()?.parse::<TokenType>().ok()?;
+            let line = fields.next()?.parse().ok()?;
+            let offset = fields.next()?.parse().ok()?;
+            let lexeme = unescape(fields.next()?)?;
+
+            let literal = fields.next()?;
+            let literal = match literal.split_at_checked(1)? {
+                ("-", "") => None,
+                ("n", bits) => Some(Literal::Number(f64::from_bits(
+                    u64::from_str_radix(bits, 16).ok()?,
+                ))),
+                ("i", n) => Some(Literal::Int(n.parse().ok()?)),
+                ("s", s) => Some(Literal::String(unescape(s)?)),
+                ("t", "") => Some(Literal::True),
+                ("f", "") => Some(Literal::False),
+                ("0", "") => Some(Literal::Nil),
+                _ => return None,
+            };
+
+            if fields.next().is_some() {
+                return None;
+            }
+
+            Some(Token::new(token_type, lexeme, literal, line).with_offset(offset))
+        })
+        .collect()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        out.push(match chars.next()? {
+            '\\' => '\\',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            _ => return None,
+        });
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    const SOURCE: &str = "var a = 1.5; // comment\n\
+        print \"multi\\tline\n string\\\\\" + a;\n\
+        { a = (a - 2) * 3 / 4; } a != 1 == true ? false : nil;\n\
+        a < 1 <= 2 > 3 >= 4 is Number, !a; x.y; m[\"k\"];\n\
+        and class else for fun if or return super this while break continue loop try catch finally throw";
+
+    // Tokens have no PartialEq, but their Debug output covers every field.
+    fn same(left: &[Token], right: &[Token]) -> bool {
+        format!("{left:?}") == format!("{right:?}")
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let tokens = Scanner::new(SOURCE.as_bytes()).scan_tokens().unwrap();
+        let decoded = decode(&encode(&tokens)).expect("Encoded tokens should decode");
+
+        assert!(same(&tokens, &decoded));
+    }
+
+    #[test]
+    fn broken_entries_are_ignored() {
+        assert!(decode("Nope\t1\t0\ta\t-").is_none());
+        assert!(decode("Identifier\t1\t0\ta").is_none());
+        assert!(decode("Number\t1\t0\t1\tnzz").is_none());
+        assert!(decode("Identifier\t1\t0\ta\\q\t-").is_none());
+    }
+
+    #[test]
+    fn scan_reuses_cached_tokens() {
+        let dir = env::temp_dir().join(format!("lox-cache-test-{}", std::process::id()));
+        let cache = Cache::new(&dir);
+
+        let scanned = cache.scan(SOURCE.as_bytes()).unwrap();
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        let cached = cache.scan(SOURCE.as_bytes()).unwrap();
+        assert!(same(&scanned, &cached));
+
+        // Errors are not cached, the source is scanned again each time
+        assert!(cache.scan(b"var a = @;").is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entries_of_other_sources_are_not_used() {
+        let dir = env::temp_dir().join(format!("lox-cache-collision-{}", std::process::id()));
+        let cache = Cache::new(&dir);
+
+        // An entry for another program where this one's would be, like after a hash collision
+        let other = Scanner::new(b"print 2;").scan_tokens().unwrap();
+        let path = dir.join(format!("{:016x}.tokens", Cache::key(b"print 1;")));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, format!("print 2;\n{}", encode(&other))).unwrap();
+
+        let tokens = cache.scan(b"print 1;").unwrap();
+        assert_eq!(tokens[1].lexeme(), "1");
+        // And it got replaced with the right one
+        assert!(same(&cache.scan(b"print 1;").unwrap(), &tokens));
+        assert!(fs::read_to_string(&path).unwrap().starts_with("print 1;\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use crate::{
+    heap::{Allocation, Heap},
+    interpreter::{IError, Value},
+    token::Token,
+};
+
+type IResult<V> = Result<V, IError>;
+
+// An environment holds the bindings that associate variables to values.
+// Each block gets its own environment which points to the one surrounding it.
+// Environments are shared: a function keeps the one it was declared in alive, and sees changes
+// made to it after the function was created.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Binding>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+    // Scopes created while running a script count against its memory, see Heap.
+    allocation: Option<Allocation>,
+}
+
+#[derive(Debug)]
+struct Binding {
+    // None for a variable declared without a value, that hasn't been assigned one yet.
+    value: Option<Value>,
+    // Declared with `const`, so assigning to it is an error.
+    constant: bool,
+}
+
+impl Binding {
+    fn get(&self, name: &Token) -> IResult<Value> {
+        self.value
+            .clone()
+            .ok_or_else(|| IError::UninitializedVariable {
+                token: name.clone(),
+            })
+    }
+
+    fn set(&mut self, name: &Token, value: Value) -> IResult<()> {
+        if self.constant {
+            return Err(IError::AssignToConstant {
+                token: name.clone(),
+            });
+        }
+
+        self.value = Some(value);
+        Ok(())
+    }
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+            allocation: None,
+        }
+    }
+
+    pub fn with_heap(mut self, heap: &Heap) -> Self {
+        self.allocation = Some(heap.allocate(size_of::<Environment>()));
+        self
+    }
+
+    // NOTE: Redefining a variable is allowed. At least at the top level in the REPL this is nice.
+    pub fn define(&mut self, name: String, value: Value) {
+        self.insert(name, Some(value), false);
+    }
+
+    // Like `define`, but reading the variable is an error until it is assigned.
+    pub fn declare(&mut self, name: String) {
+        self.insert(name, None, false);
+    }
+
+    // Like `define`, but assigning to the variable is an error. Redefining it is still allowed.
+    pub fn define_constant(&mut self, name: String, value: Value) {
+        self.insert(name, Some(value), true);
+    }
+
+    fn insert(&mut self, name: String, value: Option<Value>, constant: bool) {
+        self.values.insert(name, Binding { value, constant });
+    }
+
+    pub fn get(&self, name: &Token) -> IResult<Value> {
+        if let Some(binding) = self.values.get(name.lexeme()) {
+            return binding.get(name);
+        }
+
+        if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow().get(name);
+        }
+
+        Err(IError::UndefinedVariable {
+            token: name.clone(),
+        })
+    }
+
+    // Like `get`, but only looks in the environment `distance` scopes up, where the resolver
+    // found the variable.
+    pub fn get_at(&self, distance: usize, name: &Token) -> IResult<Value> {
+        if distance == 0 {
+            return match self.values.get(name.lexeme()) {
+                Some(binding) => binding.get(name),
+                None => Err(IError::UndefinedVariable {
+                    token: name.clone(),
+                }),
+            };
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_at(distance - 1, name),
+            None => Err(IError::UndefinedVariable {
+                token: name.clone(),
+            }),
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Value) -> IResult<()> {
+        if distance == 0 {
+            return match self.values.get_mut(name.lexeme()) {
+                Some(binding) => binding.set(name, value),
+                None => Err(IError::UndefinedVariable {
+                    token: name.clone(),
+                }),
+            };
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(IError::UndefinedVariable {
+                token: name.clone(),
+            }),
+        }
+    }
+
+    // The variables defined in this environment itself that have a value, in no
+    // particular order.
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.values
+            .iter()
+            .filter_map(|(name, binding)| Some((name, binding.value.as_ref()?)))
+    }
+
+    // Whether `name` is declared here or in an enclosing environment, with a value or not.
+    pub fn contains(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+            || self
+                .enclosing
+                .as_ref()
+                .is_some_and(|enclosing| enclosing.borrow().contains(name))
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> IResult<()> {
+        if let Some(binding) = self.values.get_mut(name.lexeme()) {
+            return binding.set(name, value);
+        }
+
+        if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow_mut().assign(name, value);
+        }
+
+        Err(IError::UndefinedVariable {
+            token: name.clone(),
+        })
+    }
+}
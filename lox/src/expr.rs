@@ -1,16 +1,96 @@
-use std::fmt::Write;
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use crate::token::{self, Token};
 
 #[derive(Debug, Clone)]
 pub enum Expr {
     Binary(Box<Expr>, BinaryOperator, Box<Expr>),
+    // `and` and `or`, which only evaluate the right operand when the left one doesn't decide.
+    Logical(Box<Expr>, BinaryOperator, Box<Expr>),
     Grouping(Box<Expr>),
     Literal(token::Literal),
     Unary(UnaryOperator, Box<Expr>),
-    Variable(Name),
+    Variable(Name, Resolution),
+    Assign(Name, Box<Expr>, Resolution),
+    // `value is TypeName`, the type name is resolved like a variable when it is a class.
+    Is(Box<Expr>, Name, Resolution),
     // ternary condition. it was a challenge.
     Condition(Box<Expr>, Box<Expr>, Box<Expr>),
+    // Extended language level only.
+    // `{ stmt* expr? }`: runs the statements in a new scope and evaluates to the trailing
+    // expression, or nil when there is none.
+    Block(Vec<Stmt>, Option<Box<Expr>>),
+    // `if (cond) { ... } else { ... }`. Evaluates to the taken branch, or nil without an else.
+    If(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
+    // `label: loop { ... }` runs its body until a `break`, and evaluates to the value given to
+    // it. The label is optional.
+    Loop(Option<Name>, Box<Expr>),
+    // callee, the closing paren (for its line in errors), arguments
+    Call(Box<Expr>, Token, Vec<Expr>),
+    // `object.name`
+    Get(Box<Expr>, Name),
+    // `object.name = value`
+    Set(Box<Expr>, Name, Box<Expr>),
+    // `this` inside a method.
+    This(Token, Resolution),
+    // `{ key: value, ... }`, with the '{' for its line in errors.
+    Map(Token, Vec<(Expr, Expr)>),
+    // `object[key]`, with the ']' for its line in errors.
+    Index(Box<Expr>, Token, Box<Expr>),
+    // `object[key] = value`
+    SetIndex(Box<Expr>, Token, Box<Expr>, Box<Expr>),
+    // `super.method`: the keyword and the method name.
+    Super(Token, Name, Resolution),
+}
+
+// Where the variable an expression uses lives, filled in by the Resolver: how many scopes up
+// from the one the expression runs in, or None for a global. Kept in the tree rather than in a
+// table in the interpreter, so it goes away with the code it belongs to and can't be picked up
+// by other code. Atomic because trees are shared between threads, see Stmt::Function.
+#[derive(Debug)]
+pub struct Resolution(AtomicUsize);
+
+const UNRESOLVED: usize = usize::MAX;
+const GLOBAL: usize = usize::MAX - 1;
+
+impl Resolution {
+    pub fn set(&self, depth: Option<usize>) {
+        self.0.store(depth.unwrap_or(GLOBAL), Ordering::Relaxed);
+    }
+
+    // None when the expression never went through the Resolver.
+    pub fn get(&self) -> Option<Option<usize>> {
+        match self.0.load(Ordering::Relaxed) {
+            UNRESOLVED => None,
+            GLOBAL => Some(None),
+            depth => Some(Some(depth)),
+        }
+    }
+}
+
+impl Default for Resolution {
+    fn default() -> Self {
+        Self(AtomicUsize::new(UNRESOLVED))
+    }
+}
+
+impl Clone for Resolution {
+    fn clone(&self) -> Self {
+        Self(AtomicUsize::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+impl Expr {
+    // Expressions ending in a `}` may stand on their own as a statement without a trailing ';'.
+    pub fn is_block_like(&self) -> bool {
+        matches!(self, Expr::Block(..) | Expr::If(..) | Expr::Loop(..))
+    }
 }
 
 impl From<Box<Expr>> for Expr {
@@ -27,7 +107,71 @@ type Name = Token;
 pub enum Stmt {
     Expression(Expr),
     Print(Expr),
-    Var(Name, Expr),
+    // `var name;` without an initializer leaves the variable uninitialized.
+    Var(Name, Option<Expr>),
+    // `const name = expr;`, a variable that can't be assigned to.
+    Const(Name, Expr),
+    Block(Vec<Stmt>),
+    // `if (cond) stmt else stmt`, the else branch is optional.
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    // `label: while (cond) body`. A `for` loop is a while loop with the increment, which runs
+    // after every iteration, also one ended by `continue`. The label is optional.
+    While(Option<Name>, Expr, Box<Stmt>, Option<Expr>),
+    // `break label? expr?;`, only valid inside a loop. Without a value the loop evaluates to nil.
+    // Without a label it breaks out of the innermost loop.
+    Break(Name, Option<Name>, Option<Expr>),
+    // `continue label?;`, only valid inside a loop. Skips the rest of the body.
+    Continue(Name, Option<Name>),
+    // `return expr?;`, only valid inside a function. Without a value the call evaluates to nil.
+    Return(Name, Option<Expr>),
+    // Shared with the function values created from it, so they don't copy the body.
+    // Arc and not Rc, files are parsed on a thread pool.
+    Function(Arc<Function>),
+    // `try { ... } catch (name) { ... } finally { ... }`, at least one of catch and finally.
+    Try(Vec<Stmt>, Option<(Name, Vec<Stmt>)>, Option<Vec<Stmt>>),
+    // `throw expr;`
+    Throw(Name, Expr),
+    // `switch (expr) { case literal: stmt* ... default: stmt* }`. Runs the first case equal
+    // to the value, or the default. Cases don't fall through.
+    Switch(Expr, Vec<(token::Literal, Vec<Stmt>)>, Option<Vec<Stmt>>),
+    // `label: for (name in collection) body`: the keyword, the optional label, the variable, the
+    // collection and the body.
+    ForIn(Name, Option<Name>, Name, Expr, Box<Stmt>),
+    // `class Name < Superclass { method() { ... } ... }`, the superclass is optional.
+    Class(Name, Option<Expr>, Vec<Arc<Function>>),
+    // `import "path/to/name.lox";`: the keyword, the path, and `name`, the variable the
+    // module is bound to.
+    Import(Name, String, Name),
+}
+
+#[derive(Debug)]
+pub struct Function {
+    pub name: Name,
+    pub params: Vec<Name>,
+    // The defaults of the last `defaults.len()` params, evaluated when the call leaves them out.
+    pub defaults: Vec<Expr>,
+    // `...name` after the params, gets the arguments left over after them.
+    pub rest: Option<Name>,
+    pub body: Vec<Stmt>,
+    pub kind: FunctionKind,
+}
+
+impl Function {
+    // How many arguments a call needs at least.
+    pub fn required_params(&self) -> usize {
+        self.params.len() - self.defaults.len()
+    }
+}
+
+// Getters and setters only occur in class bodies, they are called when a property is read or
+// assigned instead of by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionKind {
+    Function,
+    // `area { ... }`, no parameter list.
+    Getter,
+    // `set area(value) { ... }`, exactly one parameter.
+    Setter,
 }
 
 pub trait Visitor<T> {
@@ -61,6 +205,42 @@ impl AstPrinter {
         return buf;
     }
 
+    fn function(&self, function: &Function) -> String {
+        let required = function.required_params();
+        let params = function
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| match i.checked_sub(required) {
+                Some(default) => format!(
+                    "(= {} {})",
+                    param.lexeme(),
+                    self.visit_expr(&function.defaults[default])
+                ),
+                None => param.lexeme().to_owned(),
+            })
+            .chain(
+                function
+                    .rest
+                    .iter()
+                    .map(|rest| format!("...{}", rest.lexeme())),
+            )
+            .collect::<Vec<String>>()
+            .join(" ");
+        let body = function
+            .body
+            .iter()
+            .map(|stmt| self.visit_stmt(stmt))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let keyword = match function.kind {
+            FunctionKind::Function => "fun",
+            FunctionKind::Getter => "getter",
+            FunctionKind::Setter => "setter",
+        };
+        format!("({keyword} {} ({params}) {body})", function.name.lexeme())
+    }
+
     pub fn print(&mut self, stmts: &Vec<Stmt>) -> String {
         let mut output = vec![];
         for stmt in stmts {
@@ -81,7 +261,7 @@ impl Visitor<String> for AstPrinter {
                 buf.write_str(&literal.to_string())
                     .expect("Failed to write string");
             }
-            Expr::Binary(lhs, op, rhs) => {
+            Expr::Binary(lhs, op, rhs) | Expr::Logical(lhs, op, rhs) => {
                 buf.write_str(&self.parenthesize(op.lexeme(), vec![lhs.as_ref(), rhs.as_ref()]))
                     .expect("Failed to write string");
             }
@@ -99,7 +279,95 @@ impl Visitor<String> for AstPrinter {
                     vec![cond.as_ref(), inner_true.as_ref(), inner_false.as_ref()],
                 ))
                 .expect("Failed to write string"),
-            Expr::Variable(name) => todo!(),
+            Expr::Variable(name, _) | Expr::This(name, _) => {
+                buf.write_str(name.lexeme())
+                    .expect("Failed to write string");
+            }
+            Expr::Is(value, type_name, _) => {
+                buf.write_str(&format!(
+                    "(is {} {})",
+                    self.visit_expr(value),
+                    type_name.lexeme()
+                ))
+                .expect("Failed to write string");
+            }
+            Expr::Assign(name, value, _) => {
+                buf.write_str(&format!("(= {} {})", name.lexeme(), self.visit_expr(value)))
+                    .expect("Failed to write string");
+            }
+            Expr::Block(stmts, value) => {
+                let mut inner = stmts
+                    .iter()
+                    .map(|stmt| self.visit_stmt(stmt))
+                    .collect::<Vec<String>>();
+                if let Some(value) = value {
+                    inner.push(self.visit_expr(value));
+                }
+                buf.write_str(&format!("(block {})", inner.join(" ")))
+                    .expect("Failed to write string");
+            }
+            Expr::If(condition, then_branch, else_branch) => {
+                let mut exprs = vec![condition.as_ref(), then_branch.as_ref()];
+                if let Some(else_branch) = else_branch {
+                    exprs.push(else_branch.as_ref());
+                }
+                buf.write_str(&self.parenthesize("if", exprs))
+                    .expect("Failed to write string");
+            }
+            Expr::Loop(label, body) => {
+                let name = match label {
+                    Some(label) => format!("{}: loop", label.lexeme()),
+                    None => "loop".to_owned(),
+                };
+                buf.write_str(&self.parenthesize(&name, vec![body.as_ref()]))
+                    .expect("Failed to write string");
+            }
+            Expr::Get(object, name) => {
+                buf.write_str(&format!(
+                    "(get {} {})",
+                    self.visit_expr(object),
+                    name.lexeme()
+                ))
+                .expect("Failed to write string");
+            }
+            Expr::Map(_, entries) => {
+                let exprs = entries
+                    .iter()
+                    .flat_map(|(key, value)| [key, value])
+                    .collect();
+                buf.write_str(&self.parenthesize("map", exprs))
+                    .expect("Failed to write string");
+            }
+            Expr::Index(object, _, key) => {
+                buf.write_str(&self.parenthesize("index", vec![object.as_ref(), key.as_ref()]))
+                    .expect("Failed to write string");
+            }
+            Expr::SetIndex(object, _, key, value) => {
+                buf.write_str(&self.parenthesize(
+                    "set-index",
+                    vec![object.as_ref(), key.as_ref(), value.as_ref()],
+                ))
+                .expect("Failed to write string");
+            }
+            Expr::Super(_, method, _) => {
+                buf.write_str(&format!("(super {})", method.lexeme()))
+                    .expect("Failed to write string");
+            }
+            Expr::Set(object, name, value) => {
+                buf.write_str(&format!(
+                    "(set {} {} {})",
+                    self.visit_expr(object),
+                    name.lexeme(),
+                    self.visit_expr(value)
+                ))
+                .expect("Failed to write string");
+            }
+            Expr::Call(callee, _, arguments) => {
+                let mut exprs = vec![callee.as_ref()];
+                exprs.extend(arguments);
+                buf.write_str(&self.parenthesize("call", exprs))
+                    .expect("Failed to write string");
+            }
         };
 
         return buf;
@@ -111,9 +379,122 @@ impl Visitor<String> for AstPrinter {
             Stmt::Print(expr) => {
                 format!("(print {})", self.visit_expr(expr))
             }
-            Stmt::Var(name, initializer) => {
-                format!("(var {name} {})", self.visit_expr(initializer))
+            Stmt::Var(name, initializer) => match initializer {
+                Some(initializer) => {
+                    format!("(var {} {})", name.lexeme(), self.visit_expr(initializer))
+                }
+                None => format!("(var {})", name.lexeme()),
+            },
+            Stmt::Const(name, value) => {
+                format!("(const {} {})", name.lexeme(), self.visit_expr(value))
+            }
+            Stmt::Block(stmts) => {
+                let inner = stmts
+                    .iter()
+                    .map(|stmt| self.visit_stmt(stmt))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                format!("(block {inner})")
+            }
+            Stmt::If(condition, then_branch, else_branch) => match else_branch {
+                Some(else_branch) => format!(
+                    "(if {} {} {})",
+                    self.visit_expr(condition),
+                    self.visit_stmt(then_branch),
+                    self.visit_stmt(else_branch)
+                ),
+                None => format!(
+                    "(if {} {})",
+                    self.visit_expr(condition),
+                    self.visit_stmt(then_branch)
+                ),
+            },
+            Stmt::While(label, condition, body, increment) => format!(
+                "({}while {} {}{})",
+                label
+                    .as_ref()
+                    .map_or(String::new(), |label| format!("{}: ", label.lexeme())),
+                self.visit_expr(condition),
+                self.visit_stmt(body),
+                increment
+                    .as_ref()
+                    .map_or(String::new(), |increment| format!(
+                        " {}",
+                        self.visit_expr(increment)
+                    ))
+            ),
+            Stmt::Function(function) => self.function(function),
+            Stmt::Class(name, superclass, methods) => {
+                let mut inner = vec![name.lexeme().to_owned()];
+                if let Some(superclass) = superclass {
+                    inner.push(format!("< {}", self.visit_expr(superclass)));
+                }
+                inner.extend(methods.iter().map(|method| self.function(method)));
+                format!("(class {})", inner.join(" "))
+            }
+            Stmt::Continue(_, label) => match label {
+                Some(label) => format!("(continue {})", label.lexeme()),
+                None => "(continue)".to_owned(),
+            },
+            Stmt::Try(body, catch, finally) => {
+                let stmts = |stmts: &Vec<Stmt>| {
+                    stmts
+                        .iter()
+                        .map(|stmt| format!(" {}", self.visit_stmt(stmt)))
+                        .collect::<String>()
+                };
+
+                let mut inner = format!("(try{}", stmts(body));
+                if let Some((name, handler)) = catch {
+                    inner.push_str(&format!(" (catch {}{})", name.lexeme(), stmts(handler)));
+                }
+                if let Some(finally) = finally {
+                    inner.push_str(&format!(" (finally{})", stmts(finally)));
+                }
+                inner + ")"
+            }
+            Stmt::Throw(_, value) => format!("(throw {})", self.visit_expr(value)),
+            Stmt::Switch(value, cases, default) => {
+                let stmts = |stmts: &Vec<Stmt>| {
+                    stmts
+                        .iter()
+                        .map(|stmt| format!(" {}", self.visit_stmt(stmt)))
+                        .collect::<String>()
+                };
+
+                let mut inner = format!("(switch {}", self.visit_expr(value));
+                for (literal, body) in cases {
+                    inner.push_str(&format!(" (case {literal}{})", stmts(body)));
+                }
+                if let Some(default) = default {
+                    inner.push_str(&format!(" (default{})", stmts(default)));
+                }
+                inner + ")"
+            }
+            Stmt::Import(_, path, name) => format!("(import \"{path}\" {})", name.lexeme()),
+            Stmt::ForIn(_, label, name, collection, body) => format!(
+                "({}for {} in {} {})",
+                label
+                    .as_ref()
+                    .map_or(String::new(), |label| format!("{}: ", label.lexeme())),
+                name.lexeme(),
+                self.visit_expr(collection),
+                self.visit_stmt(body)
+            ),
+            Stmt::Break(_, label, value) => {
+                let mut inner = "(break".to_owned();
+                if let Some(label) = label {
+                    inner.push_str(&format!(" {}", label.lexeme()));
+                }
+                if let Some(value) = value {
+                    inner.push_str(&format!(" {}", self.visit_expr(value)));
+                }
+                inner + ")"
             }
+            Stmt::Return(_, value) => match value {
+                Some(value) => format!("(return {})", self.visit_expr(value)),
+                None => "(return)".to_owned(),
+            },
         }
     }
 }
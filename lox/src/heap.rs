@@ -0,0 +1,81 @@
+use std::{cell::Cell, rc::Rc};
+
+// The memory held by the values a script created, counted per interpreter, so a script can be
+// kept from taking all of it, see Interpreter::with_memory_limit.
+//
+// Sizes are rough, what a value takes itself without what the allocator adds: the bytes of a
+// string, a map or instance and each of its entries, a function, a class, a scope.
+#[derive(Debug, Clone, Default)]
+pub struct Heap(Rc<Cell<usize>>);
+
+impl Heap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // What is taken by values that are still alive.
+    pub fn bytes(&self) -> usize {
+        self.0.get()
+    }
+
+    pub fn allocate(&self, bytes: usize) -> Allocation {
+        self.0.set(self.0.get() + bytes);
+        Allocation {
+            heap: self.clone(),
+            bytes: Cell::new(bytes),
+        }
+    }
+}
+
+// Bytes taken from a Heap by one value, given back when the value is dropped.
+#[derive(Debug)]
+pub struct Allocation {
+    heap: Heap,
+    bytes: Cell<usize>,
+}
+
+impl Allocation {
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    pub fn grow(&self, bytes: usize) {
+        self.bytes.set(self.bytes.get() + bytes);
+        self.heap.0.set(self.heap.0.get() + bytes);
+    }
+
+    pub fn shrink(&self, bytes: usize) {
+        self.bytes.set(self.bytes.get() - bytes);
+        self.heap.0.set(self.heap.0.get() - bytes);
+    }
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        self.heap.0.set(self.heap.0.get() - self.bytes.get());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_given_back_when_dropped() {
+        let heap = Heap::new();
+        let other = Heap::new();
+
+        let a = heap.allocate(16);
+        let b = heap.allocate(8);
+        other.allocate(4).grow(4);
+        assert_eq!(heap.bytes(), 24);
+
+        a.grow(10);
+        b.shrink(8);
+        assert_eq!(heap.bytes(), 26);
+
+        drop(a);
+        assert_eq!(heap.bytes(), 0);
+        assert_eq!(other.bytes(), 0);
+    }
+}
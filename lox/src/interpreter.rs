@@ -1,49 +1,546 @@
 use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
     fmt::Display,
-    ops::{Add, Div, Mul, Neg, Not, Sub},
+    fs,
+    io::{self, Write},
+    ops::{Add, Div, Mul, Neg, Not, RangeInclusive, Rem, Sub},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
 };
 
+use indexmap::IndexMap;
 use thiserror::Error;
 
 use crate::{
-    expr::{self, Expr, Stmt, Visitor},
+    environment::Environment,
+    expr::{self, Expr, FunctionKind, Resolution, Stmt, Visitor},
+    heap::{Allocation, Heap},
+    lox_string::LoxString,
+    natives,
+    parser::{LanguageLevel, Parser},
+    resolver::Resolver,
+    scanner::Scanner,
+    timers::Timers,
     token::{Literal, Token, TokenType},
 };
 
 // NOTE: Difference between Literal and Value
 // A literal is something that appears in the user's source code, and is part of the parser's domain.
 // A value is produced by computation and don't necessarily exist in the code itself. They are an interpreter concept, part of the runtime world.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Value {
+    // A float. Arithmetic on an Int and a Number gives a Number.
     Number(f64),
-    String(String),
+    // Exact, as long as it doesn't overflow, which is a runtime error.
+    Int(i64),
+    String(LoxString),
     Bool(bool),
     Nil,
+    Callable(LoxCallable),
+    Instance(Rc<LoxInstance>),
+    Map(Rc<LoxMap>),
+}
+
+// Everything that can be called with `callee(arguments)`.
+#[derive(Debug, Clone)]
+pub enum LoxCallable {
+    // Declared with `fun`.
+    Function(Rc<LoxFunction>),
+    // Calling a class creates an instance of it.
+    Class(Rc<LoxClass>),
+    // Built into the interpreter, see natives.rs.
+    Native(Rc<NativeFunction>),
+    // Made by the `memoize` native.
+    Memoized(Rc<Memoized>),
+}
+
+// A function implemented in Rust. Gets the interpreter calling it, the closing parenthesis of
+// the call and exactly `arity` arguments, the call checks the count.
+#[derive(Debug, Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&Interpreter, &Token, &[Value]) -> Result<Value, NativeError>,
+}
+
+#[derive(Debug)]
+pub enum NativeError {
+    // What went wrong, the call adds where it happened.
+    Message(String),
+    // From Lox code the native called, passed on as it is.
+    Error(IError),
+}
+
+impl From<String> for NativeError {
+    fn from(message: String) -> Self {
+        NativeError::Message(message)
+    }
+}
+
+impl From<IError> for NativeError {
+    fn from(err: IError) -> Self {
+        NativeError::Error(err)
+    }
+}
+
+// Calls `function` once for the same arguments, and after that gives back what it returned.
+// Arguments that can't be a map key aren't remembered, the call always goes through.
+#[derive(Debug)]
+pub struct Memoized {
+    function: LoxCallable,
+    cache: RefCell<HashMap<Vec<MapKey>, Value>>,
+    // Grows with the cache.
+    allocation: Allocation,
+}
+
+impl Memoized {
+    pub(crate) fn new(function: LoxCallable, heap: &Heap) -> Self {
+        Self {
+            function,
+            cache: RefCell::new(HashMap::new()),
+            allocation: heap.allocate(size_of::<Memoized>()),
+        }
+    }
+
+    fn call(
+        &self,
+        interpreter: &Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> IResult<Value> {
+        let Some(key) = arguments
+            .iter()
+            .map(MapKey::from_value)
+            .collect::<Option<Vec<_>>>()
+        else {
+            return interpreter.invoke(&self.function, paren, arguments);
+        };
+
+        if let Some(value) = self.cache.borrow().get(&key) {
+            return Ok(value.clone());
+        }
+
+        let value = interpreter.invoke(&self.function, paren, arguments)?;
+        self.allocation
+            .grow(size_of::<(Vec<MapKey>, Value)>() + key.len() * size_of::<MapKey>());
+        self.cache.borrow_mut().insert(key, value.clone());
+        interpreter.check_memory_limit(paren)?;
+        Ok(value)
+    }
+}
+
+// What can be used as a key in a map: values that are equal when they look the same.
+// Nil isn't one of them, so it can stand for "no key" (see natives::next_key).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    // The bits of the number, for numbers with a fraction. Whole numbers are Ints, so 1 and
+    // 1.0 are the same key like they are equal. NaN is never equal to itself, so it can't
+    // be a key.
+    Number(u64),
+    Int(i64),
+    String(String),
+    Bool(bool),
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value) -> Option<MapKey> {
+        match value {
+            Value::Number(n) if n.is_nan() => None,
+            // Also turns -0 into 0.
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                Some(MapKey::Int(*n as i64))
+            }
+            Value::Number(n) => Some(MapKey::Number(n.to_bits())),
+            Value::Int(n) => Some(MapKey::Int(*n)),
+            Value::String(s) => Some(MapKey::String(s.as_str().to_owned())),
+            Value::Bool(b) => Some(MapKey::Bool(*b)),
+            _ => None,
+        }
+    }
+}
+
+impl From<&MapKey> for Value {
+    fn from(key: &MapKey) -> Self {
+        match key {
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::Int(n) => Value::Int(*n),
+            MapKey::String(s) => Value::String(s.as_str().into()),
+            MapKey::Bool(b) => Value::Bool(*b),
+        }
+    }
+}
+
+// Keeps its entries in insertion order, so printing and iterating are predictable.
+pub struct LoxMap {
+    entries: RefCell<IndexMap<MapKey, Value>>,
+    // Set while the map is printed, so a map that contains itself doesn't print forever.
+    printing: Cell<bool>,
+    // Grows and shrinks with the entries.
+    allocation: Allocation,
+}
+
+const ENTRY_SIZE: usize = size_of::<(MapKey, Value)>();
+
+// Same reason as for the printing flag.
+impl std::fmt::Debug for LoxMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxMap({} entries)", self.entries.borrow().len())
+    }
+}
+
+impl LoxMap {
+    fn new(entries: IndexMap<MapKey, Value>, heap: &Heap) -> Self {
+        Self {
+            allocation: heap.allocate(size_of::<LoxMap>() + entries.len() * ENTRY_SIZE),
+            entries: RefCell::new(entries),
+            printing: Cell::new(false),
+        }
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<Value> {
+        self.entries.borrow().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: MapKey, value: Value) {
+        if self.entries.borrow_mut().insert(key, value).is_none() {
+            self.allocation.grow(ENTRY_SIZE);
+        }
+    }
+
+    // Keeps the order of the other entries.
+    pub fn remove(&self, key: &MapKey) -> Option<Value> {
+        let removed = self.entries.borrow_mut().shift_remove(key);
+        if removed.is_some() {
+            self.allocation.shrink(ENTRY_SIZE);
+        }
+        removed
+    }
+
+    // The first key without `key`, otherwise the one after it. None at the end, and when `key`
+    // isn't in the map.
+    pub fn key_after(&self, key: Option<&MapKey>) -> Option<MapKey> {
+        let entries = self.entries.borrow();
+        let index = match key {
+            Some(key) => entries.get_index_of(key)? + 1,
+            None => 0,
+        };
+
+        entries.get_index(index).map(|(key, _)| key.clone())
+    }
+}
+
+// Strings in a map are printed with quotes, so `{"1": 1}` and `{1: 1}` can be told apart.
+struct Quoted<'a>(&'a Value);
+
+impl Display for Quoted<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Value::String(s) => write!(f, "\"{s}\""),
+            value => write!(f, "{value}"),
+        }
+    }
+}
+
+// A function declaration together with the environment it was declared in.
+pub struct LoxFunction {
+    declaration: Arc<expr::Function>,
+    closure: Rc<RefCell<Environment>>,
+    // The globals of the module it was declared in, which are the ones its body sees.
+    globals: Rc<RefCell<Environment>>,
+    // The `init` method of a class, which always returns the instance.
+    is_initializer: bool,
+    allocation: Allocation,
+}
+
+// The closure usually contains the function itself, so deriving this would never finish.
+impl std::fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxFunction({})", self.declaration.name.lexeme())
+    }
+}
+
+impl LoxFunction {
+    // The method with `this` defined in a scope between its closure and its body, so every
+    // call sees the instance it was looked up on.
+    // Counted against the same heap as the method.
+    fn bind(&self, instance: Rc<LoxInstance>) -> LoxFunction {
+        let heap = self.allocation.heap();
+        let mut environment = Environment::new_enclosed(self.closure.clone()).with_heap(heap);
+        environment.define("this".to_owned(), Value::Instance(instance));
+
+        LoxFunction {
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            globals: self.globals.clone(),
+            is_initializer: self.is_initializer,
+            allocation: heap.allocate(size_of::<LoxFunction>()),
+        }
+    }
+
+    fn arity(&self) -> RangeInclusive<usize> {
+        let declaration = &self.declaration;
+        let max = match declaration.rest {
+            Some(_) => usize::MAX,
+            None => declaration.params.len(),
+        };
+        declaration.required_params()..=max
+    }
+}
+
+pub struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+    // Called when the property with their name is read or assigned.
+    getters: HashMap<String, Rc<LoxFunction>>,
+    setters: HashMap<String, Rc<LoxFunction>>,
+    // Only held, so the class counts against the heap until it is dropped.
+    _allocation: Allocation,
+}
+
+type Members = HashMap<String, Rc<LoxFunction>>;
+
+impl LoxClass {
+    fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.find(name, |class| &class.methods)
+    }
+
+    fn find_getter(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.find(name, |class| &class.getters)
+    }
+
+    fn find_setter(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.find(name, |class| &class.setters)
+    }
+
+    // Members of the class itself win over inherited ones.
+    fn find(&self, name: &str, members: fn(&LoxClass) -> &Members) -> Option<Rc<LoxFunction>> {
+        match members(self).get(name) {
+            Some(member) => Some(member.clone()),
+            None => self.superclass.as_ref()?.find(name, members),
+        }
+    }
+
+    // Whether this is `other` or inherits from it.
+    fn is_subclass_of(self: &Rc<Self>, other: &Rc<LoxClass>) -> bool {
+        Rc::ptr_eq(self, other)
+            || self
+                .superclass
+                .as_ref()
+                .is_some_and(|superclass| superclass.is_subclass_of(other))
+    }
+}
+
+impl std::fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxClass({})", self.name)
+    }
+}
+
+// Fields live in the instance and can be added at any time, methods are looked up in the class.
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: RefCell<HashMap<String, Value>>,
+    // Grows with the fields.
+    allocation: Allocation,
+}
+
+// Fields can point back at the instance.
+impl std::fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LoxInstance({})", self.class.name)
+    }
+}
+
+impl LoxInstance {
+    // A getter wins over everything, fields shadow methods of the same name.
+    fn get(self: &Rc<Self>, interpreter: &Interpreter, name: &Token) -> IResult<Value> {
+        if let Some(getter) = self.class.find_getter(name.lexeme()) {
+            return interpreter.call_accessor(&getter.bind(self.clone()), name, vec![]);
+        }
+
+        if let Some(value) = self.fields.borrow().get(name.lexeme()) {
+            return Ok(value.clone());
+        }
+
+        if let Some(method) = self.class.find_method(name.lexeme()) {
+            let method = method.bind(self.clone());
+            return Ok(Value::Callable(LoxCallable::Function(method.into())));
+        }
+
+        Err(IError::UndefinedProperty {
+            token: name.clone(),
+        })
+    }
+
+    // A setter is called instead of creating a field. A property with only a getter can't
+    // be assigned.
+    fn set(self: &Rc<Self>, interpreter: &Interpreter, name: &Token, value: Value) -> IResult<()> {
+        if let Some(setter) = self.class.find_setter(name.lexeme()) {
+            interpreter.call_accessor(&setter.bind(self.clone()), name, vec![value])?;
+            return Ok(());
+        }
+
+        if self.class.find_getter(name.lexeme()).is_some() {
+            return Err(IError::ReadOnlyProperty {
+                token: name.clone(),
+            });
+        }
+
+        let added = self
+            .fields
+            .borrow_mut()
+            .insert(name.lexeme().to_owned(), value)
+            .is_none();
+        if added {
+            self.allocation
+                .grow(size_of::<(String, Value)>() + name.lexeme().len());
+        }
+        Ok(())
+    }
+}
+
+impl LoxCallable {
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            LoxCallable::Function(function) => function.declaration.name.lexeme(),
+            LoxCallable::Class(class) => &class.name,
+            LoxCallable::Native(native) => native.name,
+            LoxCallable::Memoized(memoized) => memoized.function.name(),
+        }
+    }
+
+    // How many arguments a call can pass, parameters with a default can be left out.
+    // A class takes the arguments of its `init`.
+    pub(crate) fn arity(&self) -> RangeInclusive<usize> {
+        match self {
+            LoxCallable::Function(function) => function.arity(),
+            LoxCallable::Class(class) => match class.find_method("init") {
+                Some(initializer) => initializer.arity(),
+                None => 0..=0,
+            },
+            LoxCallable::Native(native) => native.arity..=native.arity,
+            LoxCallable::Memoized(memoized) => memoized.function.arity(),
+        }
+    }
+
+    fn call(
+        &self,
+        interpreter: &Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> IResult<Value> {
+        match self {
+            LoxCallable::Function(function) => interpreter.call_function(function, arguments),
+            LoxCallable::Class(class) => {
+                let instance = Rc::new(LoxInstance {
+                    class: class.clone(),
+                    fields: RefCell::new(HashMap::new()),
+                    allocation: interpreter.heap.allocate(size_of::<LoxInstance>()),
+                });
+                interpreter.check_memory_limit(paren)?;
+                if let Some(initializer) = class.find_method("init") {
+                    interpreter.call_function(&initializer.bind(instance.clone()), arguments)?;
+                }
+                Ok(Value::Instance(instance))
+            }
+            LoxCallable::Native(native) => (native.function)(interpreter, paren, &arguments)
+                .map_err(|err| match err {
+                    NativeError::Message(message) => IError::Native {
+                        name: native.name,
+                        message,
+                        token: paren.clone(),
+                    },
+                    NativeError::Error(err) => err,
+                }),
+            LoxCallable::Memoized(memoized) => memoized.call(interpreter, paren, arguments),
+        }
+    }
+}
+
+impl PartialEq for LoxCallable {
+    // Functions and classes are only equal to themselves. Running the same declaration twice
+    // creates two of them, which can have different closures.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoxCallable::Function(left), LoxCallable::Function(right)) => Rc::ptr_eq(left, right),
+            (LoxCallable::Class(left), LoxCallable::Class(right)) => Rc::ptr_eq(left, right),
+            (LoxCallable::Native(left), LoxCallable::Native(right)) => Rc::ptr_eq(left, right),
+            (LoxCallable::Memoized(left), LoxCallable::Memoized(right)) => Rc::ptr_eq(left, right),
+            _ => false,
+        }
+    }
 }
 
 impl Value {
-    fn number(&self) -> Option<f64> {
-        if let Value::Number(n) = self {
-            return Some(*n);
+    // Ints are turned into floats, for arithmetic with a Number.
+    pub(crate) fn number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Int(n) => Some(*n as f64),
+            _ => None,
         }
+    }
 
-        None
+    fn ints(&self, other: &Value) -> Option<(i64, i64)> {
+        match (self, other) {
+            (Value::Int(left), Value::Int(right)) => Some((*left, *right)),
+            _ => None,
+        }
     }
 
-    fn string(&self) -> Option<String> {
+    fn string(&self) -> Option<&LoxString> {
         if let Value::String(s) = self {
-            return Some(s.clone());
+            return Some(s);
         }
 
         None
     }
 
+    // The name `is` checks against. Instances are also checked against their class, and
+    // both Ints and Floats are a Number, see interpret_is.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "Float",
+            Value::Int(_) => "Int",
+            Value::String(_) => "String",
+            Value::Bool(_) => "Bool",
+            Value::Nil => "Nil",
+            Value::Callable(
+                LoxCallable::Function(_) | LoxCallable::Native(_) | LoxCallable::Memoized(_),
+            ) => "Function",
+            Value::Callable(LoxCallable::Class(_)) => "Class",
+            Value::Instance(_) => "Instance",
+            Value::Map(_) => "Map",
+        }
+    }
+
+    // What `for (element in value)` goes over: the keys of a map, in order, or the characters
+    // of a string. Taken up front, so changing a map in the loop doesn't affect it.
+    fn elements(&self) -> Option<Vec<Value>> {
+        match self {
+            Value::Map(map) => Some(map.entries.borrow().keys().map(Value::from).collect()),
+            Value::String(s) => Some(
+                s.as_str()
+                    .chars()
+                    .map(|c| Value::String(c.to_string().as_str().into()))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
     fn is_true(&self) -> bool {
         match self {
-            Value::Number(_) => true,
+            Value::Number(_) | Value::Int(_) => true,
             Value::String(_) => true,
             Value::Bool(b) => *b,
             Value::Nil => false,
+            Value::Callable(_) => true,
+            Value::Instance(_) => true,
+            Value::Map(_) => true,
         }
     }
 }
@@ -51,16 +548,33 @@ impl Value {
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::Number(n) => {
-                let mut s = format!("{:.1}", n);
-                if s.ends_with(".0") {
-                    s = format!("{:}", n);
-                }
-                write!(f, "{s}")
-            }
+            // The shortest form that reads back as the same number, `2.0` prints as `2`.
+            Value::Number(n) => write!(f, "{n}"),
+            Value::Int(n) => write!(f, "{n}"),
             Value::String(s) => write!(f, "{s}"),
             Value::Bool(b) => write!(f, "{}", b.to_string()),
             Value::Nil => write!(f, "nil"),
+            Value::Callable(callable @ (LoxCallable::Function(_) | LoxCallable::Memoized(_))) => {
+                write!(f, "<fn {}>", callable.name())
+            }
+            Value::Callable(callable @ LoxCallable::Class(_)) => write!(f, "{}", callable.name()),
+            Value::Callable(LoxCallable::Native(_)) => write!(f, "<native fn>"),
+            Value::Instance(instance) => write!(f, "{} instance", instance.class.name),
+            Value::Map(map) => {
+                if map.printing.replace(true) {
+                    return write!(f, "{{...}}");
+                }
+
+                let entries = map
+                    .entries
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", Quoted(&key.into()), Quoted(value)))
+                    .collect::<Vec<_>>();
+
+                map.printing.set(false);
+                write!(f, "{{{}}}", entries.join(", "))
+            }
         }
     }
 }
@@ -74,6 +588,22 @@ pub enum VError {
         operator_type: String,
         value_type: String,
     },
+    #[error("Cannot compare {left} with {right}")]
+    Incomparable {
+        left: &'static str,
+        right: &'static str,
+    },
+    #[error("Integer overflow in {operator_type}")]
+    Overflow { operator_type: String },
+    #[error("Integer division by zero in {operator_type}")]
+    DivisionByZero { operator_type: String },
+}
+
+// Int arithmetic that doesn't fit in an Int.
+fn overflow(operator_type: &str) -> VError {
+    VError::Overflow {
+        operator_type: operator_type.to_owned(),
+    }
 }
 
 pub type VResult = Result<Value, VError>;
@@ -81,9 +611,15 @@ pub type VResult = Result<Value, VError>;
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::Int(left), Value::Int(right)) => left == right,
+            (Value::Number(_) | Value::Int(_), Value::Number(_) | Value::Int(_)) => {
+                self.number() == other.number()
+            }
             (Value::String(left), Value::String(right)) => left == right,
             (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Callable(left), Value::Callable(right)) => left == right,
+            (Value::Instance(left), Value::Instance(right)) => Rc::ptr_eq(left, right),
+            (Value::Map(left), Value::Map(right)) => Rc::ptr_eq(left, right),
             (Value::Nil, Value::Nil) => true,
             (Value::Nil, _) => false,
             (_, _) => false,
@@ -93,9 +629,18 @@ impl PartialEq for Value {
 
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        if let Some((left, right)) = self.ints(other) {
+            return left.partial_cmp(&right);
+        }
+
         if let (Some(left), Some(right)) = (self.number(), other.number()) {
             return left.partial_cmp(&right);
         }
+
+        // By code point, so "B" < "a".
+        if let (Some(left), Some(right)) = (self.string(), other.string()) {
+            return left.as_str().partial_cmp(&right.as_str());
+        }
         None
     }
 }
@@ -104,12 +649,28 @@ impl Add for Value {
     type Output = VResult;
 
     fn add(self, rhs: Self) -> Self::Output {
+        if let Some((left, right)) = self.ints(&rhs) {
+            return left
+                .checked_add(right)
+                .map(Value::Int)
+                .ok_or_else(|| overflow("+"));
+        }
+
         if let (Some(left), Some(right)) = (self.number(), rhs.number()) {
             return Ok(Value::Number(left + right));
         }
 
         if let (Some(left), Some(right)) = (self.string(), rhs.string()) {
-            return Ok(Value::String(format!("{left}{right}")));
+            return Ok(Value::String(left.concat(&right.as_str())));
+        }
+
+        // A string and a number concatenate, with the number written like `print` does:
+        // `"count: " + 3` is "count: 3". Any other mix is still an error.
+        if let (Some(left), Value::Number(_) | Value::Int(_)) = (self.string(), &rhs) {
+            return Ok(Value::String(left.concat(&rhs.to_string())));
+        }
+        if let (Value::Number(_) | Value::Int(_), Some(right)) = (&self, rhs.string()) {
+            return Ok(Value::String(LoxString::new(format!("{self}{right}"))));
         }
 
         Err(VError::InvalidOperation {
@@ -124,6 +685,13 @@ impl Sub for Value {
     type Output = VResult;
 
     fn sub(self, rhs: Self) -> Self::Output {
+        if let Some((left, right)) = self.ints(&rhs) {
+            return left
+                .checked_sub(right)
+                .map(Value::Int)
+                .ok_or_else(|| overflow("-"));
+        }
+
         if let (Some(left), Some(right)) = (self.number(), rhs.number()) {
             return Ok(Value::Number(left - right));
         }
@@ -136,6 +704,7 @@ impl Sub for Value {
     }
 }
 
+// Always a Number, `7 / 2` is 3.5.
 impl Div for Value {
     type Output = VResult;
 
@@ -157,6 +726,13 @@ impl Mul for Value {
     type Output = VResult;
 
     fn mul(self, rhs: Self) -> Self::Output {
+        if let Some((left, right)) = self.ints(&rhs) {
+            return left
+                .checked_mul(right)
+                .map(Value::Int)
+                .ok_or_else(|| overflow("*"));
+        }
+
         if let (Some(left), Some(right)) = (self.number(), rhs.number()) {
             return Ok(Value::Number(left * right));
         }
@@ -169,6 +745,60 @@ impl Mul for Value {
     }
 }
 
+// Takes the sign of the left operand, like in C: `-7 % 3` is -1.
+impl Rem for Value {
+    type Output = VResult;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        if let Some((left, right)) = self.ints(&rhs) {
+            if right == 0 {
+                return Err(VError::DivisionByZero {
+                    operator_type: "%".to_owned(),
+                });
+            }
+            return left
+                .checked_rem(right)
+                .map(Value::Int)
+                .ok_or_else(|| overflow("%"));
+        }
+
+        if let (Some(left), Some(right)) = (self.number(), rhs.number()) {
+            return Ok(Value::Number(left % right));
+        }
+
+        Err(VError::InvalidOperation {
+            operator: "Binary".to_owned(),
+            operator_type: "%".to_owned(),
+            value_type: format!("{self:?}"),
+        })
+    }
+}
+
+impl Value {
+    // `**`, which has no trait in std::ops. An Int to a negative power is a Number.
+    fn pow(self, rhs: Self) -> VResult {
+        if let Some((left, right)) = self.ints(&rhs) {
+            if right >= 0 {
+                return u32::try_from(right)
+                    .ok()
+                    .and_then(|right| left.checked_pow(right))
+                    .map(Value::Int)
+                    .ok_or_else(|| overflow("**"));
+            }
+        }
+
+        if let (Some(left), Some(right)) = (self.number(), rhs.number()) {
+            return Ok(Value::Number(left.powf(right)));
+        }
+
+        Err(VError::InvalidOperation {
+            operator: "Binary".to_owned(),
+            operator_type: "**".to_owned(),
+            value_type: format!("{self:?}"),
+        })
+    }
+}
+
 // Ruby's simple rule: false and nil are falsey. Everything else is truthy.
 // So in Not trait impl, everything is just the opposite of the above line.
 impl Not for Value {
@@ -176,10 +806,13 @@ impl Not for Value {
 
     fn not(self) -> Self::Output {
         match self {
-            Value::Number(_) => Ok(Value::Bool(false)),
+            Value::Number(_) | Value::Int(_) => Ok(Value::Bool(false)),
             Value::String(_) => Ok(Value::Bool(false)),
             Value::Bool(b) => Ok(Value::Bool(!b)),
             Value::Nil => Ok(Value::Bool(true)),
+            Value::Callable(_) => Ok(Value::Bool(false)),
+            Value::Instance(_) => Ok(Value::Bool(false)),
+            Value::Map(_) => Ok(Value::Bool(false)),
         }
     }
 }
@@ -188,13 +821,20 @@ impl Neg for Value {
     type Output = VResult;
 
     fn neg(self) -> Self::Output {
+        if let Value::Int(left) = self {
+            return left
+                .checked_neg()
+                .map(Value::Int)
+                .ok_or_else(|| overflow("-"));
+        }
+
         if let Some(left) = self.number() {
             return Ok(Value::Number(-left));
         }
         Err(VError::InvalidOperation {
             operator: "Unary".to_owned(),
             operator_type: "-".to_owned(),
-            value_type: "{self:?}".to_owned(),
+            value_type: format!("{self:?}"),
         })
     }
 }
@@ -203,7 +843,8 @@ impl From<&Literal> for Value {
     fn from(value: &Literal) -> Self {
         match value {
             Literal::Number(n) => Value::Number(*n),
-            Literal::String(s) => Value::String(s.clone()),
+            Literal::Int(n) => Value::Int(*n),
+            Literal::String(s) => Value::String(s.as_str().into()),
             Literal::True => Value::Bool(true),
             Literal::False => Value::Bool(false),
             Literal::Nil => Value::Nil,
@@ -227,9 +868,132 @@ pub enum IError {
     },
     #[error("Reached unexpected state when evaluating token at line {}.", token.line())]
     UnexpectedError { token: Token },
+    #[error("Unknown type '{}' at line {}.", token.lexeme(), token.line())]
+    UnknownType { token: Token },
+    #[error("Undefined variable '{}' at line {}.", token.lexeme(), token.line())]
+    UndefinedVariable { token: Token },
+    #[error("Variable '{}' is read before it is assigned a value at line {}.", token.lexeme(), token.line())]
+    UninitializedVariable { token: Token },
+    #[error("Can't assign to constant '{}' at line {}.", token.lexeme(), token.line())]
+    AssignToConstant { token: Token },
+    #[error("Out of memory: exceeded the limit of {limit} bytes at line {}.", token.line())]
+    OutOfMemory { limit: usize, token: Token },
+    #[error("Nesting too deep: exceeded the limit of {limit} while evaluating.")]
+    NestingTooDeep { limit: usize },
+    #[error("Stack overflow: more than {limit} calls deep at line {line}.")]
+    StackOverflow { limit: usize, line: i32 },
+    #[error("Can only call functions and classes at line {}.", token.line())]
+    NotCallable { token: Token },
+    #[error("Expected {} arguments but got {got} at line {}.", arity_range(*required, *expected), token.line())]
+    WrongArity {
+        // Less than `expected` when parameters have defaults. `expected` is usize::MAX with a
+        // rest parameter.
+        required: usize,
+        expected: usize,
+        got: usize,
+        token: Token,
+    },
+    #[error("Only instances have properties at line {}.", token.line())]
+    NotAnInstance { token: Token },
+    #[error("Undefined property '{}' at line {}.", token.lexeme(), token.line())]
+    UndefinedProperty { token: Token },
+    #[error("Property '{}' has a getter but no setter at line {}.", token.lexeme(), token.line())]
+    ReadOnlyProperty { token: Token },
+    #[error("Superclass must be a class at line {}.", token.line())]
+    SuperclassNotClass { token: Token },
+    #[error("Only maps can be indexed at line {}.", token.line())]
+    NotIndexable { token: Token },
+    #[error("'compare' has to return a number, not {type_name}, at line {}.", token.line())]
+    InvalidComparison {
+        type_name: &'static str,
+        token: Token,
+    },
+    #[error("Can only loop over maps and strings, not {type_name}, at line {}.", token.line())]
+    NotIterable {
+        type_name: &'static str,
+        token: Token,
+    },
+    #[error("Map keys must be numbers, strings or booleans at line {}.", token.line())]
+    InvalidKey { token: Token },
+    #[error("{message} in call to '{name}' at line {}.", token.line())]
+    Native {
+        name: &'static str,
+        message: String,
+        token: Token,
+    },
+    #[error("Could not write output: {message}")]
+    Output { message: String },
+    // Not really an error: unwinds to the enclosing loop. The parser makes sure there always is one.
+    // The value is held by the interpreter, Values can't be sent between threads but errors can.
+    // With a label, loops without that label pass it on.
+    #[error("'break' outside of a loop at line {}.", token.line())]
+    Break { token: Token, label: Option<String> },
+    // Unwinds to the enclosing loop, which goes on with its next iteration.
+    #[error("'continue' outside of a loop at line {}.", token.line())]
+    Continue { token: Token, label: Option<String> },
+    // Unwinds to the function call like `break` does to its loop.
+    #[error("'return' outside of a function at line {}.", token.line())]
+    Return { token: Token },
+    // Unwinds to the closest `catch`. Reaching the top means nothing caught it, so this is also
+    // the error for an uncaught exception. The interpreter holds the value while unwinding,
+    // `value` is how it prints.
+    #[error("Uncaught exception: {value} at line {}.", token.line())]
+    Throw { value: String, token: Token },
+    #[error("Can't import '{path}' at line {}: {message}", token.line())]
+    ImportFailed {
+        path: String,
+        message: String,
+        token: Token,
+    },
+    #[error("Import cycle at line {}: {cycle}.", token.line())]
+    ImportCycle { cycle: String, token: Token },
+}
+
+// The methods that binary operators on two instances call, see Interpreter::overloaded_binary.
+const OVERLOADS: &[(TokenType, &str)] = &[
+    (TokenType::Plus, "plus"),
+    (TokenType::Minus, "minus"),
+    (TokenType::Star, "times"),
+    (TokenType::Slash, "divide"),
+    (TokenType::Percent, "remainder"),
+    (TokenType::StarStar, "power"),
+    (TokenType::EqualEqual, "equals"),
+    (TokenType::BangEqual, "equals"),
+    (TokenType::Greater, "compare"),
+    (TokenType::GreaterEqual, "compare"),
+    (TokenType::Less, "compare"),
+    (TokenType::LessEqual, "compare"),
+];
+
+// Whether a `break` or `continue` to the label `target` stops at the loop labeled `label`.
+// Without a target it stops at the innermost loop.
+fn targets(target: &Option<String>, label: Option<&Token>) -> bool {
+    target
+        .as_ref()
+        .is_none_or(|target| label.is_some_and(|label| label.lexeme() == target))
+}
+
+// "2", "1 to 2" for a function with a default parameter, or "at least 1" for one with a rest
+// parameter.
+fn arity_range(required: usize, expected: usize) -> String {
+    if expected == usize::MAX {
+        format!("at least {required}")
+    } else if required == expected {
+        expected.to_string()
+    } else {
+        format!("{required} to {expected}")
+    }
 }
 
 impl IError {
+    // `break`, `continue` and `return` unwind like errors, but aren't any.
+    fn is_control_flow(&self) -> bool {
+        matches!(
+            self,
+            IError::Break { .. } | IError::Continue { .. } | IError::Return { .. }
+        )
+    }
+
     fn unary_op_error(err: VError, token: Token) -> Self {
         Self::UnaryOpError { source: err, token }
     }
@@ -241,22 +1005,311 @@ impl IError {
 
 type IResult<V> = Result<V, IError>;
 
-pub struct Interpreter;
-impl Interpreter {
-    pub fn new() -> Self {
-        Self
-    }
+// Report of every statement that failed in a call to `interpret`.
+#[derive(Error, Debug, Clone)]
+pub struct RuntimeErrors {
+    errors: Vec<IError>,
+    // The calls each error unwound through, same order as `errors`.
+    traces: Vec<Vec<Frame>>,
+    statements: usize,
+}
 
-    pub fn interpret(&self, stmts: &Vec<Stmt>) {
-        for stmt in stmts {
-            if let Err(err) = self.visit_stmt(stmt) {
-                eprintln!("{err}");
-            }
-        }
-    }
+// A function call that hasn't returned yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: String,
+    // Where it was called.
+    pub line: i32,
+}
 
-    fn interpret_literal(&self, literal: &Literal) -> IResult<Value> {
-        Ok(literal.into())
+// Deep recursion would print thousands of identical lines otherwise.
+const MAX_TRACE_FRAMES: usize = 16;
+
+impl RuntimeErrors {
+    pub fn errors(&self) -> &Vec<IError> {
+        &self.errors
+    }
+
+    // Each error with the calls it happened in, innermost first:
+    //
+    //     Undefined variable 'x' at line 3.
+    //       in inner(), called from outer() at line 6
+    //       in outer(), called from script at line 9
+    pub fn reports(&self) -> impl Iterator<Item = String> + '_ {
+        self.errors.iter().zip(&self.traces).map(|(err, trace)| {
+            let mut report = err.to_string();
+
+            for (i, frame) in trace.iter().enumerate().rev().take(MAX_TRACE_FRAMES) {
+                let caller = match i {
+                    0 => "script".to_owned(),
+                    _ => format!("{}()", trace[i - 1].function),
+                };
+                report.push_str(&format!(
+                    "\n  in {}(), called from {caller} at line {}",
+                    frame.function, frame.line
+                ));
+            }
+
+            if trace.len() > MAX_TRACE_FRAMES {
+                report.push_str(&format!(
+                    "\n  ... and {} more calls",
+                    trace.len() - MAX_TRACE_FRAMES
+                ));
+            }
+
+            report
+        })
+    }
+}
+
+impl Display for RuntimeErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let output = self.reports().collect::<Vec<String>>().join("\n");
+
+        write!(f, "{}", output)?;
+
+        if self.errors.len() > 1 {
+            write!(
+                f,
+                "\n{} of {} statements failed.",
+                self.errors.len(),
+                self.statements
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// The parser already caps how deep the AST can nest, but trees can also be built by hand.
+// Keep a separate limit so evaluation of any tree returns an error instead of overflowing.
+// Counted per function body, calls are limited by MAX_CALL_DEPTH instead.
+pub const MAX_EVAL_DEPTH: usize = 2048;
+
+// How deep Lox functions can recurse. The stack grows on the heap, so this is about
+// memory rather than the size of the native stack.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+// See the parser: grow the stack on the heap instead of overflowing it.
+const RED_ZONE: usize = 64 * 1024;
+const STACK_GROWTH: usize = 1024 * 1024;
+
+// What `interpret` does when a statement fails at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    // Stop at the first error. What running a script should do.
+    Abort,
+    // Report the error, carry on with the next statement and summarize at the end.
+    // Useful in the REPL or when running a batch of independent statements.
+    #[default]
+    Continue,
+}
+
+pub struct Interpreter {
+    // Holds the natives, around the globals of every module.
+    builtins: Rc<RefCell<Environment>>,
+    // The outermost scope of the module being run, where top level declarations go.
+    globals: RefCell<Rc<RefCell<Environment>>>,
+    // The innermost scope of the code being run.
+    environment: RefCell<Rc<RefCell<Environment>>>,
+    error_policy: ErrorPolicy,
+    depth: Cell<usize>,
+    // What the values created by the script take, and how much they may.
+    heap: Heap,
+    memory_limit: Option<usize>,
+    // Where `print` writes to.
+    output: RefCell<Box<dyn Write>>,
+    // The value of the `break` currently unwinding to its loop.
+    break_value: RefCell<Option<Value>>,
+    // The value of the `return` currently unwinding to its call.
+    return_value: RefCell<Option<Value>>,
+    // The value of the `throw` currently unwinding to a `catch`.
+    thrown: RefCell<Option<Value>>,
+    // The functions being called, outermost first.
+    call_stack: RefCell<Vec<Frame>>,
+    // The call stack when the error currently unwinding happened, see interpret_call.
+    trace: RefCell<Option<Vec<Frame>>>,
+    // Modules that have been imported, by their canonical path.
+    modules: RefCell<HashMap<PathBuf, Value>>,
+    // The modules being imported right now, outermost first, as canonical path and as written.
+    importing: RefCell<Vec<(PathBuf, String)>>,
+    // Import paths are relative to this, the directory of the module being run.
+    import_dir: RefCell<PathBuf>,
+    // What imported modules are parsed with. At the extended level functions also return their
+    // last expression, see call_function.
+    language_level: LanguageLevel,
+    // Whether the Resolver rejects assignments to globals that are never declared.
+    strict_globals: bool,
+    // What `clock()` returns next when running deterministically, see with_deterministic.
+    ticks: Option<Cell<u64>>,
+    // The state of `random()`, see natives::random.
+    random_state: Cell<u64>,
+    timers: RefCell<Timers>,
+}
+
+// What `random()` starts from when running deterministically.
+pub const RANDOM_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let builtins = Rc::new(RefCell::new(Environment::new()));
+        let globals = Rc::new(RefCell::new(Environment::new_enclosed(builtins.clone())));
+
+        let interpreter = Self {
+            builtins,
+            environment: RefCell::new(globals.clone()),
+            globals: RefCell::new(globals),
+            error_policy: ErrorPolicy::default(),
+            depth: Cell::new(0),
+            heap: Heap::new(),
+            memory_limit: None,
+            output: RefCell::new(Box::new(io::stdout())),
+            break_value: RefCell::new(None),
+            return_value: RefCell::new(None),
+            thrown: RefCell::new(None),
+            call_stack: RefCell::new(vec![]),
+            trace: RefCell::new(None),
+            modules: RefCell::new(HashMap::new()),
+            importing: RefCell::new(vec![]),
+            import_dir: RefCell::new(PathBuf::new()),
+            language_level: LanguageLevel::default(),
+            strict_globals: false,
+            ticks: None,
+            random_state: Cell::new(natives::seed()),
+            timers: RefCell::new(Timers::default()),
+        };
+
+        natives::NATIVES
+            .iter()
+            .fold(interpreter, |interpreter, native| {
+                interpreter.with_native(native.clone())
+            })
+    }
+
+    pub fn with_output(mut self, output: impl Write + 'static) -> Self {
+        self.output = RefCell::new(Box::new(output));
+        self
+    }
+
+    // Caps the memory held by the values the script creates and still uses, see Heap for what
+    // is counted. Each interpreter has its own. The limit is checked when the script creates a
+    // value: concatenating strings, map literals, instances, functions and classes, and new map
+    // entries and fields. Going over it is an OutOfMemory runtime error, which the script can
+    // catch.
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    pub fn with_language_level(mut self, language_level: LanguageLevel) -> Self {
+        self.language_level = language_level;
+        self
+    }
+
+    // Makes assigning to a global that no code declares an error when resolving, instead of only
+    // once the assignment runs. Applies to imported modules too.
+    pub fn with_strict_globals(mut self, strict_globals: bool) -> Self {
+        self.strict_globals = strict_globals;
+        self
+    }
+
+    // Makes runs reproducible: `clock()` counts 0, 1, 2, ... instead of telling the time,
+    // `random()` always starts from RANDOM_SEED, and `runEventLoop()` doesn't wait for timers.
+    // Meant for tests comparing output.
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        if deterministic {
+            self.ticks = Some(Cell::new(0));
+            self.random_state = Cell::new(RANDOM_SEED);
+        }
+        self
+    }
+
+    pub(crate) fn is_deterministic(&self) -> bool {
+        self.ticks.is_some()
+    }
+
+    // The next count of a deterministic clock, or None when clock() tells the time.
+    pub(crate) fn tick(&self) -> Option<u64> {
+        self.ticks
+            .as_ref()
+            .map(|ticks| ticks.replace(ticks.get() + 1))
+    }
+
+    pub(crate) fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    pub(crate) fn timers(&self) -> &RefCell<Timers> {
+        &self.timers
+    }
+
+    pub(crate) fn random_state(&self) -> &Cell<u64> {
+        &self.random_state
+    }
+
+    pub(crate) fn strict_globals(&self) -> bool {
+        self.strict_globals
+    }
+
+    // Whether `name` is a global or a native of the module being run, declared by code that
+    // ran before.
+    pub(crate) fn is_global(&self, name: &str) -> bool {
+        self.globals.borrow().borrow().contains(name)
+    }
+
+    // Where `import` paths in the code run next are relative to, the working directory
+    // by default. Set it to the directory of a script before running it.
+    pub fn set_import_dir(&self, dir: impl Into<PathBuf>) {
+        self.import_dir.replace(dir.into());
+    }
+
+    // Makes `native` callable as a global function. Interpreter::new already defines the ones
+    // in natives::NATIVES.
+    pub fn with_native(self, native: NativeFunction) -> Self {
+        self.builtins.borrow_mut().define(
+            native.name.to_owned(),
+            Value::Callable(LoxCallable::Native(native.into())),
+        );
+        self
+    }
+
+    // Errors are handed back to the caller instead of printed, so it can decide how to report them
+    // and which exit code to use. With ErrorPolicy::Abort there is at most one.
+    pub fn interpret(&self, stmts: &[Stmt]) -> Result<(), RuntimeErrors> {
+        let mut errors = RuntimeErrors {
+            errors: vec![],
+            traces: vec![],
+            statements: stmts.len(),
+        };
+
+        for stmt in stmts {
+            if let Err(err) = self.visit_stmt(stmt) {
+                if let IError::Throw { .. } = err {
+                    self.thrown.take();
+                }
+                errors.errors.push(err);
+                errors.traces.push(self.trace.take().unwrap_or_default());
+
+                if self.error_policy == ErrorPolicy::Abort {
+                    break;
+                }
+            }
+        }
+
+        if !errors.errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(())
+    }
+
+    fn interpret_literal(&self, literal: &Literal) -> IResult<Value> {
+        Ok(literal.into())
     }
 
     fn interpret_grouping(&self, expr: &Expr) -> IResult<Value> {
@@ -267,7 +1320,6 @@ impl Interpreter {
         let right = self.visit_expr(right)?;
         let operator = token.token_type();
 
-        // TODO(thusanarul): verify this works
         match operator {
             TokenType::Bang => {
                 let new_value = !right;
@@ -290,6 +1342,24 @@ impl Interpreter {
 
         let operator = token.token_type();
 
+        if let (Value::Number(left), Value::Number(right)) = (&left, &right) {
+            if let Some(value) = Self::numeric_binary(operator, *left, *right) {
+                return Ok(value);
+            }
+        }
+
+        if let (Value::Int(left), Value::Int(right)) = (&left, &right) {
+            if let Some(value) = Self::int_binary(operator, *left, *right) {
+                return Ok(value);
+            }
+        }
+
+        if let (Value::Instance(instance), Value::Instance(_)) = (&left, &right) {
+            if let Some(value) = self.overloaded_binary(token, instance, &right)? {
+                return Ok(value);
+            }
+        }
+
         match operator {
             TokenType::Minus => {
                 let new_value = left - right;
@@ -303,66 +1373,2910 @@ impl Interpreter {
                 let new_value = left * right;
                 new_value.map_err(|err| IError::binary_op_error(err, token.clone()))
             }
+            TokenType::Percent => {
+                let new_value = left % right;
+                new_value.map_err(|err| IError::binary_op_error(err, token.clone()))
+            }
+            TokenType::StarStar => {
+                let new_value = left.pow(right);
+                new_value.map_err(|err| IError::binary_op_error(err, token.clone()))
+            }
             TokenType::Plus => {
                 let new_value = left + right;
-                new_value.map_err(|err| IError::binary_op_error(err, token.clone()))
+                let new_value =
+                    new_value.map_err(|err| IError::binary_op_error(err, token.clone()))?;
+                // Concatenating is how a script grows strings.
+                if let Value::String(s) = &new_value {
+                    s.track(&self.heap);
+                    self.check_memory_limit(token)?;
+                }
+                Ok(new_value)
             }
-            TokenType::Greater => Ok(Value::Bool(left > right)),
-            TokenType::GreaterEqual => Ok(Value::Bool(left >= right)),
-            TokenType::Less => Ok(Value::Bool(left < right)),
-            TokenType::LessEqual => Ok(Value::Bool(left <= right)),
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => Self::compare(token, &left, &right),
             TokenType::BangEqual => Ok(Value::Bool(left != right)),
             TokenType::EqualEqual => Ok(Value::Bool(left == right)),
+            TokenType::Comma => Ok(right),
             _ => Err(IError::UnexpectedError {
                 token: token.clone(),
             }),
         }
     }
 
-    fn interpret_ternary_condition(
+    // `or` gives the left operand when it is true, `and` when it is false, without evaluating
+    // the right one.
+    fn interpret_logical(&self, token: &Token, left: &Expr, right: &Expr) -> IResult<Value> {
+        let left = self.visit_expr(left)?;
+
+        let decided = match token.token_type() {
+            TokenType::Or => left.is_true(),
+            _ => !left.is_true(),
+        };
+        if decided {
+            return Ok(left);
+        }
+
+        self.visit_expr(right)
+    }
+
+    fn interpret_is(
         &self,
-        condition: &Expr,
-        inner_true: &Expr,
-        inner_false: &Expr,
+        value: &Expr,
+        type_name: &Token,
+        resolution: &Resolution,
     ) -> IResult<Value> {
-        let c = self.visit_expr(condition)?;
+        let value = self.visit_expr(value)?;
 
-        return if c.is_true() {
-            self.visit_expr(inner_true)
-        } else {
-            self.visit_expr(inner_false)
+        match type_name.lexeme() {
+            "Number" => Ok(Value::Bool(matches!(
+                value,
+                Value::Number(_) | Value::Int(_)
+            ))),
+            "Int" | "Float" | "String" | "Bool" | "Nil" | "Function" | "Class" | "Instance"
+            | "Map" => Ok(Value::Bool(value.type_name() == type_name.lexeme())),
+            // Anything else has to be a class.
+            _ => match self.look_up_variable(type_name, resolution) {
+                Ok(Value::Callable(LoxCallable::Class(class))) => Ok(Value::Bool(matches!(
+                    value,
+                    Value::Instance(instance) if instance.class.is_subclass_of(&class)
+                ))),
+                _ => Err(IError::UnknownType {
+                    token: type_name.clone(),
+                }),
+            },
+        }
+    }
+
+    // Code that never went through the Resolver looks the name up through the scopes it
+    // runs in instead.
+    fn look_up_variable(&self, name: &Token, resolution: &Resolution) -> IResult<Value> {
+        match resolution.get() {
+            Some(Some(distance)) => self.environment.borrow().borrow().get_at(distance, name),
+            Some(None) => self.globals.borrow().borrow().get(name),
+            None => self.environment.borrow().borrow().get(name),
+        }
+    }
+
+    fn interpret_assign(
+        &self,
+        name: &Token,
+        value: &Expr,
+        resolution: &Resolution,
+    ) -> IResult<Value> {
+        let value = self.visit_expr(value)?;
+
+        match resolution.get() {
+            Some(Some(distance)) => {
+                self.environment
+                    .borrow()
+                    .borrow_mut()
+                    .assign_at(distance, name, value.clone())?
+            }
+            Some(None) => self
+                .globals
+                .borrow()
+                .borrow_mut()
+                .assign(name, value.clone())?,
+            None => self
+                .environment
+                .borrow()
+                .borrow_mut()
+                .assign(name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    fn interpret_call(&self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> IResult<Value> {
+        let callee = self.visit_expr(callee)?;
+
+        let arguments = arguments
+            .iter()
+            .map(|argument| self.visit_expr(argument))
+            .collect::<IResult<Vec<Value>>>()?;
+
+        let Value::Callable(callable) = callee else {
+            return Err(IError::NotCallable {
+                token: paren.clone(),
+            });
         };
+
+        self.invoke(&callable, paren, arguments)
     }
-}
 
-impl Visitor<Value> for Interpreter {
-    type ExprOutput = IResult<Value>;
-    type StmtOutput = IResult<()>;
-    fn visit_expr(&self, expr: &Expr) -> Self::ExprOutput {
-        match expr {
-            Expr::Binary(left, token, right) => self.interpret_binary(token, left, right),
-            Expr::Grouping(expr) => self.interpret_grouping(expr.as_ref()),
-            Expr::Literal(literal) => self.interpret_literal(literal),
-            Expr::Unary(token, expr) => self.interpret_unary(token, expr.as_ref()),
-            Expr::Condition(condition, inner_true, inner_false) => {
-                self.interpret_ternary_condition(condition, inner_true, inner_false)
+    // Checks the number of arguments, and calls `callable` with them.
+    pub(crate) fn invoke(
+        &self,
+        callable: &LoxCallable,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> IResult<Value> {
+        let arity = callable.arity();
+        if !arity.contains(&arguments.len()) {
+            return Err(IError::WrongArity {
+                required: *arity.start(),
+                expected: *arity.end(),
+                got: arguments.len(),
+                token: paren.clone(),
+            });
+        }
+
+        self.tracked(callable.name(), *paren.line(), || {
+            callable.call(self, paren, arguments)
+        })
+    }
+
+    // Getters and setters are called by reading or assigning the property `name`.
+    fn call_accessor(
+        &self,
+        accessor: &LoxFunction,
+        name: &Token,
+        arguments: Vec<Value>,
+    ) -> IResult<Value> {
+        self.tracked(accessor.declaration.name.lexeme(), *name.line(), || {
+            self.call_function(accessor, arguments)
+        })
+    }
+
+    // Runs the call to `function` from `line` with a frame for it on the call stack.
+    // The body starts counting its nesting from 0 again.
+    fn tracked(
+        &self,
+        function: &str,
+        line: i32,
+        call: impl FnOnce() -> IResult<Value>,
+    ) -> IResult<Value> {
+        if self.call_stack.borrow().len() >= MAX_CALL_DEPTH {
+            return Err(IError::StackOverflow {
+                limit: MAX_CALL_DEPTH,
+                line,
+            });
+        }
+
+        self.call_stack.borrow_mut().push(Frame {
+            function: function.to_owned(),
+            line,
+        });
+
+        let depth = self.depth.replace(0);
+        let result = call();
+        self.depth.set(depth);
+
+        // The innermost call sees the error first, while the stack is still complete.
+        if let Err(err) = &result {
+            if !err.is_control_flow() && self.trace.borrow().is_none() {
+                self.trace.replace(Some(self.call_stack.borrow().clone()));
             }
-            Expr::Variable(name) => todo!(),
         }
+
+        self.call_stack.borrow_mut().pop();
+        result
     }
 
-    fn visit_stmt(&self, stmt: &Stmt) -> Self::StmtOutput {
-        match stmt {
-            expr::Stmt::Expression(expr) => {
-                self.visit_expr(expr)?;
+    // The body runs in a scope of its own on top of the environment the function was declared in,
+    // so it sees its parameters and the variables around the declaration, but none of the
+    // variables where it was called.
+    // Parameters left out of the call get their default, evaluated in that scope after the
+    // parameters before them are defined.
+    // At the extended level a body that ends in an expression returns its value, unless a
+    // `return` runs first. Initializers still return `this`.
+    fn call_function(&self, function: &LoxFunction, arguments: Vec<Value>) -> IResult<Value> {
+        let declaration = &function.declaration;
+        let (body, value) = match declaration.body.split_last() {
+            Some((Stmt::Expression(value), body))
+                if self.language_level == LanguageLevel::Extended && !function.is_initializer =>
+            {
+                (body, Some(value))
+            }
+            _ => (&declaration.body[..], None),
+        };
+        let scope = Environment::new_enclosed(function.closure.clone()).with_heap(&self.heap);
+
+        let globals = self.globals.replace(function.globals.clone());
+        let result = self.with_environment(scope, || {
+            let mut arguments = arguments.into_iter();
+            for (i, param) in declaration.params.iter().enumerate() {
+                let value = match arguments.next() {
+                    Some(argument) => argument,
+                    None => {
+                        self.visit_expr(&declaration.defaults[i - declaration.required_params()])?
+                    }
+                };
+                self.environment
+                    .borrow()
+                    .borrow_mut()
+                    .define(param.lexeme().to_owned(), value);
+            }
+
+            // There are no arrays (yet), so the rest is a map from 0, 1, ... to the arguments.
+            if let Some(rest) = &declaration.rest {
+                let rest_arguments = (0..).map(MapKey::Int).zip(arguments.by_ref()).collect();
+                self.environment.borrow().borrow_mut().define(
+                    rest.lexeme().to_owned(),
+                    Value::Map(LoxMap::new(rest_arguments, &self.heap).into()),
+                );
+            }
+
+            body.iter().try_for_each(|stmt| self.visit_stmt(stmt))?;
+            value.map_or(Ok(Value::Nil), |value| self.visit_expr(value))
+        });
+        self.globals.replace(globals);
+
+        // Also after `return;`, or `return` with a value.
+        if function.is_initializer {
+            match result {
+                Ok(_) => {}
+                Err(IError::Return { .. }) => {
+                    self.return_value.take();
+                }
+                Err(err) => return Err(err),
             }
-            expr::Stmt::Print(expr) => {
-                let value = self.visit_expr(expr)?;
-                println!("{value}");
+            let this = Token::new(TokenType::This, "this".to_owned(), None, 0);
+            return function.closure.borrow().get_at(0, &this);
+        }
+
+        match result {
+            Ok(value) => Ok(value),
+            Err(IError::Return { .. }) => Ok(self.return_value.take().unwrap_or(Value::Nil)),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn execute_function(&self, declaration: &Arc<expr::Function>) -> IResult<()> {
+        let environment = self.environment.borrow();
+        let function = LoxFunction {
+            declaration: declaration.clone(),
+            closure: environment.clone(),
+            globals: self.globals.borrow().clone(),
+            is_initializer: false,
+            allocation: self.heap.allocate(size_of::<LoxFunction>()),
+        };
+
+        environment.borrow_mut().define(
+            declaration.name.lexeme().to_owned(),
+            Value::Callable(LoxCallable::Function(function.into())),
+        );
+        self.check_memory_limit(&declaration.name)
+    }
+
+    fn execute_class(
+        &self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Arc<expr::Function>],
+    ) -> IResult<()> {
+        let superclass = match superclass {
+            Some(superclass) => match self.visit_expr(superclass)? {
+                Value::Callable(LoxCallable::Class(class)) => Some(class),
+                _ => {
+                    return Err(IError::SuperclassNotClass {
+                        token: name.clone(),
+                    })
+                }
+            },
+            None => None,
+        };
+
+        let environment = self.environment.borrow();
+
+        // Methods of a subclass close over a scope with `super` in it, which is where
+        // `super.method` starts looking.
+        let closure = match &superclass {
+            Some(superclass) => {
+                let mut scope =
+                    Environment::new_enclosed(environment.clone()).with_heap(&self.heap);
+                scope.define(
+                    "super".to_owned(),
+                    Value::Callable(LoxCallable::Class(superclass.clone())),
+                );
+                Rc::new(RefCell::new(scope))
             }
-            expr::Stmt::Var(name, initializer) => todo!(),
+            None => environment.clone(),
         };
 
+        let members = |kind: FunctionKind| {
+            methods
+                .iter()
+                .filter(|method| method.kind == kind)
+                .map(|method| {
+                    let function = LoxFunction {
+                        declaration: method.clone(),
+                        closure: closure.clone(),
+                        globals: self.globals.borrow().clone(),
+                        is_initializer: kind == FunctionKind::Function
+                            && method.name.lexeme() == "init",
+                        allocation: self.heap.allocate(size_of::<LoxFunction>()),
+                    };
+                    (method.name.lexeme().to_owned(), Rc::new(function))
+                })
+                .collect()
+        };
+
+        let class = LoxClass {
+            name: name.lexeme().to_owned(),
+            methods: members(FunctionKind::Function),
+            getters: members(FunctionKind::Getter),
+            setters: members(FunctionKind::Setter),
+            superclass,
+            _allocation: self.heap.allocate(size_of::<LoxClass>()),
+        };
+
+        environment.borrow_mut().define(
+            name.lexeme().to_owned(),
+            Value::Callable(LoxCallable::Class(class.into())),
+        );
+        self.check_memory_limit(name)
+    }
+
+    // Each case runs in a scope of its own, like a block.
+    fn execute_switch(
+        &self,
+        value: &Expr,
+        cases: &[(Literal, Vec<Stmt>)],
+        default: Option<&Vec<Stmt>>,
+    ) -> IResult<()> {
+        let value = self.visit_expr(value)?;
+
+        let body = cases
+            .iter()
+            .find(|(literal, _)| Value::from(literal) == value)
+            .map(|(_, body)| body)
+            .or(default);
+
+        match body {
+            Some(body) => self.execute_block(body),
+            None => Ok(()),
+        }
+    }
+
+    fn execute_import(&self, keyword: &Token, path: &str, name: &Token) -> IResult<()> {
+        let module = self.import(keyword, path)?;
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define(name.lexeme().to_owned(), module);
         Ok(())
     }
+
+    // Runs the module the first time it is imported. Later imports, also from other modules,
+    // get the same module without running it again.
+    fn import(&self, keyword: &Token, path: &str) -> IResult<Value> {
+        let failed = |message: String| IError::ImportFailed {
+            path: path.to_owned(),
+            message,
+            token: keyword.clone(),
+        };
+
+        let full_path = self
+            .import_dir
+            .borrow()
+            .join(path)
+            .canonicalize()
+            .map_err(|err| failed(err.to_string()))?;
+
+        if let Some(module) = self.modules.borrow().get(&full_path) {
+            return Ok(module.clone());
+        }
+
+        // Still running, so one of the modules it imports imported it again.
+        let importing = self.importing.borrow();
+        if let Some(start) = importing.iter().position(|(other, _)| *other == full_path) {
+            let cycle = importing[start..]
+                .iter()
+                .map(|(_, written)| written.as_str())
+                .chain([path])
+                .collect::<Vec<&str>>()
+                .join(" -> ");
+            return Err(IError::ImportCycle {
+                cycle,
+                token: keyword.clone(),
+            });
+        }
+        drop(importing);
+
+        let stmts = self.load_module(&full_path).map_err(failed)?;
+
+        self.importing
+            .borrow_mut()
+            .push((full_path.clone(), path.to_owned()));
+        let module = self.run_module(&full_path, &stmts, failed);
+        self.importing.borrow_mut().pop();
+
+        let module = module?;
+        self.modules.borrow_mut().insert(full_path, module.clone());
+        Ok(module)
+    }
+
+    // Scanned and parsed like any other script. It is resolved once its globals are in place,
+    // see run_module.
+    fn load_module(&self, path: &Path) -> Result<Vec<Stmt>, String> {
+        let bytes = fs::read(path).map_err(|err| err.to_string())?;
+        let tokens = Scanner::new(&bytes)
+            .scan_tokens()
+            .map_err(|err| err.to_string())?;
+        Parser::new(tokens)
+            .with_language_level(self.language_level)
+            .parse()
+            .map_err(|err| err.to_string())
+    }
+
+    // A module runs in globals of its own, so it can't see or change the ones of whoever
+    // imports it. It evaluates to a map of everything it defined, by name.
+    // Resolving happens in those globals too, so strict globals only count what the module
+    // declares itself. Resolver errors become `failed` errors.
+    fn run_module(
+        &self,
+        path: &Path,
+        stmts: &[Stmt],
+        failed: impl FnOnce(String) -> IError,
+    ) -> IResult<Value> {
+        let globals = Rc::new(RefCell::new(
+            Environment::new_enclosed(self.builtins.clone()).with_heap(&self.heap),
+        ));
+
+        let previous_globals = self.globals.replace(globals.clone());
+        let previous_environment = self.environment.replace(globals.clone());
+        let previous_dir = self
+            .import_dir
+            .replace(path.parent().map(Path::to_owned).unwrap_or_default());
+
+        let result = match Resolver::new(self).resolve(stmts) {
+            Ok(()) => stmts.iter().try_for_each(|stmt| self.visit_stmt(stmt)),
+            Err(err) => Err(failed(err.to_string())),
+        };
+
+        self.globals.replace(previous_globals);
+        self.environment.replace(previous_environment);
+        self.import_dir.replace(previous_dir);
+        result?;
+
+        let globals = globals.borrow();
+        let mut bindings = globals.bindings().collect::<Vec<_>>();
+        bindings.sort_by_key(|(name, _)| *name);
+        let exports = bindings
+            .into_iter()
+            .map(|(name, value)| (MapKey::String(name.clone()), value.clone()))
+            .collect();
+
+        Ok(Value::Map(LoxMap::new(exports, &self.heap).into()))
+    }
+
+    // `this` is bound in the scope right inside the one holding `super`, see LoxFunction::bind.
+    fn interpret_super(
+        &self,
+        keyword: &Token,
+        method: &Token,
+        resolution: &Resolution,
+    ) -> IResult<Value> {
+        let Value::Callable(LoxCallable::Class(superclass)) =
+            self.look_up_variable(keyword, resolution)?
+        else {
+            return Err(IError::UnexpectedError {
+                token: keyword.clone(),
+            });
+        };
+
+        let this = Token::new(TokenType::This, "this".to_owned(), None, *keyword.line());
+        let depth = resolution.get();
+        // Not borrowed past this, a getter runs code that needs the environment.
+        let instance = {
+            let environment = self.environment.borrow();
+            let environment = environment.borrow();
+            match depth {
+                Some(Some(distance)) if distance > 0 => environment.get_at(distance - 1, &this)?,
+                _ => environment.get(&this)?,
+            }
+        };
+        let Value::Instance(instance) = instance else {
+            return Err(IError::UnexpectedError { token: this });
+        };
+
+        if let Some(getter) = superclass.find_getter(method.lexeme()) {
+            return self.call_accessor(&getter.bind(instance), method, vec![]);
+        }
+
+        match superclass.find_method(method.lexeme()) {
+            Some(function) => Ok(Value::Callable(LoxCallable::Function(
+                function.bind(instance).into(),
+            ))),
+            None => Err(IError::UndefinedProperty {
+                token: method.clone(),
+            }),
+        }
+    }
+
+    // Later entries win when a key is given twice.
+    fn interpret_map(&self, brace: &Token, entries: &[(Expr, Expr)]) -> IResult<Value> {
+        let mut map = IndexMap::new();
+        for (key, value) in entries {
+            let key = self.map_key(key, brace)?;
+            map.insert(key, self.visit_expr(value)?);
+        }
+
+        let map = LoxMap::new(map, &self.heap);
+        self.check_memory_limit(brace)?;
+        Ok(Value::Map(map.into()))
+    }
+
+    fn map_key(&self, key: &Expr, token: &Token) -> IResult<MapKey> {
+        MapKey::from_value(&self.visit_expr(key)?).ok_or_else(|| IError::InvalidKey {
+            token: token.clone(),
+        })
+    }
+
+    // A key that isn't in the map gives nil.
+    fn interpret_index(&self, object: &Expr, bracket: &Token, key: &Expr) -> IResult<Value> {
+        let Value::Map(map) = self.visit_expr(object)? else {
+            return Err(IError::NotIndexable {
+                token: bracket.clone(),
+            });
+        };
+
+        let key = self.map_key(key, bracket)?;
+        Ok(map.get(&key).unwrap_or(Value::Nil))
+    }
+
+    fn interpret_set_index(
+        &self,
+        object: &Expr,
+        bracket: &Token,
+        key: &Expr,
+        value: &Expr,
+    ) -> IResult<Value> {
+        let Value::Map(map) = self.visit_expr(object)? else {
+            return Err(IError::NotIndexable {
+                token: bracket.clone(),
+            });
+        };
+
+        let key = self.map_key(key, bracket)?;
+        let value = self.visit_expr(value)?;
+        map.insert(key, value.clone());
+        self.check_memory_limit(bracket)?;
+        Ok(value)
+    }
+
+    fn interpret_get(&self, object: &Expr, name: &Token) -> IResult<Value> {
+        match self.visit_expr(object)? {
+            Value::Instance(instance) => instance.get(self, name),
+            _ => Err(IError::NotAnInstance {
+                token: name.clone(),
+            }),
+        }
+    }
+
+    fn interpret_set(&self, object: &Expr, name: &Token, value: &Expr) -> IResult<Value> {
+        let Value::Instance(instance) = self.visit_expr(object)? else {
+            return Err(IError::NotAnInstance {
+                token: name.clone(),
+            });
+        };
+
+        let value = self.visit_expr(value)?;
+        instance.set(self, name, value.clone())?;
+        self.check_memory_limit(name)?;
+        Ok(value)
+    }
+
+    fn execute_var(&self, name: &Token, initializer: Option<&Expr>) -> IResult<()> {
+        let Some(initializer) = initializer else {
+            self.environment
+                .borrow()
+                .borrow_mut()
+                .declare(name.lexeme().to_owned());
+            return Ok(());
+        };
+
+        let value = self.visit_expr(initializer)?;
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define(name.lexeme().to_owned(), value);
+        Ok(())
+    }
+
+    fn execute_const(&self, name: &Token, value: &Expr) -> IResult<()> {
+        let value = self.visit_expr(value)?;
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define_constant(name.lexeme().to_owned(), value);
+        Ok(())
+    }
+
+    fn execute_block(&self, stmts: &[Stmt]) -> IResult<()> {
+        self.in_scope(|| stmts.iter().try_for_each(|stmt| self.visit_stmt(stmt)))
+    }
+
+    fn interpret_block(&self, stmts: &[Stmt], value: Option<&Expr>) -> IResult<Value> {
+        self.in_scope(|| {
+            stmts.iter().try_for_each(|stmt| self.visit_stmt(stmt))?;
+            value.map_or(Ok(Value::Nil), |value| self.visit_expr(value))
+        })
+    }
+
+    fn interpret_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Expr,
+        else_branch: Option<&Expr>,
+    ) -> IResult<Value> {
+        if self.visit_expr(condition)?.is_true() {
+            return self.visit_expr(then_branch);
+        }
+
+        else_branch.map_or(Ok(Value::Nil), |else_branch| self.visit_expr(else_branch))
+    }
+
+    fn execute_if(
+        &self,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: Option<&Stmt>,
+    ) -> IResult<()> {
+        if self.visit_expr(condition)?.is_true() {
+            return self.visit_stmt(then_branch);
+        }
+
+        else_branch.map_or(Ok(()), |else_branch| self.visit_stmt(else_branch))
+    }
+
+    fn execute_while(
+        &self,
+        label: Option<&Token>,
+        condition: &Expr,
+        body: &Stmt,
+        increment: Option<&Expr>,
+    ) -> IResult<()> {
+        while self.visit_expr(condition)?.is_true() {
+            match self.visit_stmt(body) {
+                Ok(()) => {}
+                Err(IError::Continue { label: target, .. }) if targets(&target, label) => {}
+                // A value given to `break` has nowhere to go.
+                Err(IError::Break { label: target, .. }) if targets(&target, label) => {
+                    self.break_value.take();
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+
+            if let Some(increment) = increment {
+                self.visit_expr(increment)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn interpret_loop(&self, label: Option<&Token>, body: &Expr) -> IResult<Value> {
+        loop {
+            match self.visit_expr(body) {
+                Ok(_) => continue,
+                Err(IError::Continue { label: target, .. }) if targets(&target, label) => continue,
+                Err(IError::Break { label: target, .. }) if targets(&target, label) => {
+                    return Ok(self.break_value.take().unwrap_or(Value::Nil));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Every iteration runs in a scope of its own with the variable in it, so closures created in
+    // the body each see their own element.
+    fn execute_for_in(
+        &self,
+        keyword: &Token,
+        label: Option<&Token>,
+        name: &Token,
+        collection: &Expr,
+        body: &Stmt,
+    ) -> IResult<()> {
+        let collection = self.visit_expr(collection)?;
+        let Some(elements) = collection.elements() else {
+            return Err(IError::NotIterable {
+                type_name: collection.type_name(),
+                token: keyword.clone(),
+            });
+        };
+
+        for element in elements {
+            let enclosing = self.environment.borrow().clone();
+            let mut scope = Environment::new_enclosed(enclosing).with_heap(&self.heap);
+            scope.define(name.lexeme().to_owned(), element);
+
+            match self.with_environment(scope, || self.visit_stmt(body)) {
+                Ok(()) => continue,
+                Err(IError::Continue { label: target, .. }) if targets(&target, label) => continue,
+                // A value given to `break` has nowhere to go.
+                Err(IError::Break { label: target, .. }) if targets(&target, label) => {
+                    self.break_value.take();
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_break(
+        &self,
+        token: &Token,
+        label: Option<&Token>,
+        value: Option<&Expr>,
+    ) -> IResult<()> {
+        let value = value.map_or(Ok(Value::Nil), |value| self.visit_expr(value))?;
+        self.break_value.replace(Some(value));
+
+        Err(IError::Break {
+            token: token.clone(),
+            label: label.map(|label| label.lexeme().to_owned()),
+        })
+    }
+
+    fn execute_throw(&self, token: &Token, value: &Expr) -> IResult<()> {
+        let value = self.visit_expr(value)?;
+        let printed = value.to_string();
+        self.thrown.replace(Some(value));
+
+        Err(IError::Throw {
+            value: printed,
+            token: token.clone(),
+        })
+    }
+
+    // `finally` runs however the rest ended, also on `break` and `return`. When it fails
+    // itself, its error replaces the one it ran after.
+    fn execute_try(
+        &self,
+        body: &[Stmt],
+        catch: Option<&(Token, Vec<Stmt>)>,
+        finally: Option<&Vec<Stmt>>,
+    ) -> IResult<()> {
+        let mut result = self.execute_block(body);
+
+        if let (Err(err), Some((name, handler))) = (&result, catch) {
+            if let Some(value) = self.caught(err) {
+                self.trace.take();
+                result = self.in_scope(|| {
+                    self.environment
+                        .borrow()
+                        .borrow_mut()
+                        .define(name.lexeme().to_owned(), value);
+                    handler.iter().try_for_each(|stmt| self.visit_stmt(stmt))
+                });
+            }
+        }
+
+        // A failing finally reports where it failed, not where the error it ran after did.
+        // The values the unwinding is carrying are put aside, so returns, throws and breaks
+        // inside the finally, also ones that are handled there, don't overwrite them.
+        if let Some(finally) = finally {
+            let trace = self.trace.take();
+            let return_value = self.return_value.take();
+            let thrown = self.thrown.take();
+            let break_value = self.break_value.take();
+
+            self.execute_block(finally)?;
+
+            self.trace.replace(trace);
+            self.return_value.replace(return_value);
+            self.thrown.replace(thrown);
+            self.break_value.replace(break_value);
+        }
+
+        result
+    }
+
+    // What a `catch` gets for `err`: the thrown value, or the message of a runtime error.
+    // Control flow isn't an error, and running out of stack or failing to print can't be
+    // recovered from inside the script. Running out of memory can: the value that didn't fit
+    // is never created.
+    fn caught(&self, err: &IError) -> Option<Value> {
+        match err {
+            IError::Throw { .. } => self.thrown.take(),
+            IError::NestingTooDeep { .. }
+            | IError::StackOverflow { .. }
+            | IError::Output { .. } => None,
+            err if err.is_control_flow() => None,
+            err => Some(Value::String(err.to_string().as_str().into())),
+        }
+    }
+
+    fn execute_return(&self, token: &Token, value: Option<&Expr>) -> IResult<()> {
+        let value = value.map_or(Ok(Value::Nil), |value| self.visit_expr(value))?;
+        self.return_value.replace(Some(value));
+
+        Err(IError::Return {
+            token: token.clone(),
+        })
+    }
+
+    // Runs `f` in a new scope nested in the current one.
+    fn in_scope<T>(&self, f: impl FnOnce() -> IResult<T>) -> IResult<T> {
+        let enclosing = self.environment.borrow().clone();
+        self.with_environment(
+            Environment::new_enclosed(enclosing).with_heap(&self.heap),
+            f,
+        )
+    }
+
+    // Runs `f` in `environment`, and switches back to the current one afterwards,
+    // also when `f` failed.
+    fn with_environment<T>(
+        &self,
+        environment: Environment,
+        f: impl FnOnce() -> IResult<T>,
+    ) -> IResult<T> {
+        let previous = self.environment.replace(Rc::new(RefCell::new(environment)));
+
+        let result = f();
+
+        self.environment.replace(previous);
+        result
+    }
+
+    fn check_memory_limit(&self, token: &Token) -> IResult<()> {
+        if let Some(limit) = self.memory_limit {
+            if self.heap.bytes() > limit {
+                return Err(IError::OutOfMemory {
+                    limit,
+                    token: token.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Runs a single step of the tree walk, keeping the recursion depth in check.
+    fn nested<T>(&self, f: impl FnOnce() -> IResult<T>) -> IResult<T> {
+        let depth = self.depth.get();
+        if depth >= MAX_EVAL_DEPTH {
+            return Err(IError::NestingTooDeep {
+                limit: MAX_EVAL_DEPTH,
+            });
+        }
+
+        self.depth.set(depth + 1);
+        let result = stacker::maybe_grow(RED_ZONE, STACK_GROWTH, f);
+        self.depth.set(depth);
+
+        result
+    }
+
+    // `a + b` on two instances calls `a.plus(b)` when the class of `a` has that method, see
+    // OVERLOADS. `!=` is the opposite of `equals`, and the comparisons call `a.compare(b)`,
+    // which returns a number below, equal to or above 0. Without the method the operator works
+    // like it does for any other value.
+    fn overloaded_binary(
+        &self,
+        token: &Token,
+        instance: &Rc<LoxInstance>,
+        right: &Value,
+    ) -> IResult<Option<Value>> {
+        let operator = token.token_type();
+        let Some((_, name)) = OVERLOADS
+            .iter()
+            .find(|(overloaded, _)| overloaded == operator)
+        else {
+            return Ok(None);
+        };
+        let Some(method) = instance.class.find_method(name) else {
+            return Ok(None);
+        };
+
+        let method = LoxCallable::Function(method.bind(instance.clone()).into());
+        let result = self.invoke(&method, token, vec![right.clone()])?;
+
+        let value = match operator {
+            TokenType::EqualEqual => Value::Bool(result.is_true()),
+            TokenType::BangEqual => Value::Bool(!result.is_true()),
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                let Some(order) = result.number() else {
+                    return Err(IError::InvalidComparison {
+                        type_name: result.type_name(),
+                        token: token.clone(),
+                    });
+                };
+                Value::Bool(match operator {
+                    TokenType::Greater => order > 0.0,
+                    TokenType::GreaterEqual => order >= 0.0,
+                    TokenType::Less => order < 0.0,
+                    _ => order <= 0.0,
+                })
+            }
+            _ => result,
+        };
+        Ok(Some(value))
+    }
+
+    // Numbers compare with numbers and strings with strings. NaN isn't ordered, so every
+    // comparison with it is false, but comparing a string with a number is an error.
+    fn compare(token: &Token, left: &Value, right: &Value) -> IResult<Value> {
+        let comparable = (left.number().is_some() && right.number().is_some())
+            || (left.string().is_some() && right.string().is_some());
+        if !comparable {
+            let err = VError::Incomparable {
+                left: left.type_name(),
+                right: right.type_name(),
+            };
+            return Err(IError::binary_op_error(err, token.clone()));
+        }
+
+        let result = match token.token_type() {
+            TokenType::Greater => left > right,
+            TokenType::GreaterEqual => left >= right,
+            TokenType::Less => left < right,
+            _ => left <= right,
+        };
+        Ok(Value::Bool(result))
+    }
+
+    // Same for two Ints. Overflows are left to the trait impls, which report them.
+    fn int_binary(operator: &TokenType, left: i64, right: i64) -> Option<Value> {
+        let value = match operator {
+            TokenType::Minus => Value::Int(left.checked_sub(right)?),
+            TokenType::Star => Value::Int(left.checked_mul(right)?),
+            TokenType::Plus => Value::Int(left.checked_add(right)?),
+            TokenType::Greater => Value::Bool(left > right),
+            TokenType::GreaterEqual => Value::Bool(left >= right),
+            TokenType::Less => Value::Bool(left < right),
+            TokenType::LessEqual => Value::Bool(left <= right),
+            TokenType::BangEqual => Value::Bool(left != right),
+            TokenType::EqualEqual => Value::Bool(left == right),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    // Fast path for the common case of two numbers, which skips the operator trait impls
+    // on Value and their re-matching on variants.
+    fn numeric_binary(operator: &TokenType, left: f64, right: f64) -> Option<Value> {
+        let value = match operator {
+            TokenType::Minus => Value::Number(left - right),
+            TokenType::Slash => Value::Number(left / right),
+            TokenType::Star => Value::Number(left * right),
+            TokenType::Percent => Value::Number(left % right),
+            TokenType::StarStar => Value::Number(left.powf(right)),
+            TokenType::Plus => Value::Number(left + right),
+            TokenType::Greater => Value::Bool(left > right),
+            TokenType::GreaterEqual => Value::Bool(left >= right),
+            TokenType::Less => Value::Bool(left < right),
+            TokenType::LessEqual => Value::Bool(left <= right),
+            TokenType::BangEqual => Value::Bool(left != right),
+            TokenType::EqualEqual => Value::Bool(left == right),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    fn interpret_ternary_condition(
+        &self,
+        condition: &Expr,
+        inner_true: &Expr,
+        inner_false: &Expr,
+    ) -> IResult<Value> {
+        let c = self.visit_expr(condition)?;
+
+        return if c.is_true() {
+            self.visit_expr(inner_true)
+        } else {
+            self.visit_expr(inner_false)
+        };
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Visitor<Value> for Interpreter {
+    type ExprOutput = IResult<Value>;
+    type StmtOutput = IResult<()>;
+    fn visit_expr(&self, expr: &Expr) -> Self::ExprOutput {
+        self.nested(|| match expr {
+            Expr::Binary(left, token, right) => self.interpret_binary(token, left, right),
+            Expr::Logical(left, token, right) => self.interpret_logical(token, left, right),
+            Expr::Grouping(expr) => self.interpret_grouping(expr.as_ref()),
+            Expr::Literal(literal) => self.interpret_literal(literal),
+            Expr::Unary(token, expr) => self.interpret_unary(token, expr.as_ref()),
+            Expr::Condition(condition, inner_true, inner_false) => {
+                self.interpret_ternary_condition(condition, inner_true, inner_false)
+            }
+            Expr::Variable(name, resolution) => self.look_up_variable(name, resolution),
+            Expr::Assign(name, value, resolution) => self.interpret_assign(name, value, resolution),
+            Expr::Is(value, type_name, resolution) => {
+                self.interpret_is(value, type_name, resolution)
+            }
+            Expr::Block(stmts, value) => self.interpret_block(stmts, value.as_deref()),
+            Expr::If(condition, then_branch, else_branch) => {
+                self.interpret_if(condition, then_branch, else_branch.as_deref())
+            }
+            Expr::Loop(label, body) => self.interpret_loop(label.as_ref(), body),
+            Expr::Call(callee, paren, arguments) => self.interpret_call(callee, paren, arguments),
+            Expr::Get(object, name) => self.interpret_get(object, name),
+            Expr::This(keyword, resolution) => self.look_up_variable(keyword, resolution),
+            Expr::Super(keyword, method, resolution) => {
+                self.interpret_super(keyword, method, resolution)
+            }
+            Expr::Set(object, name, value) => self.interpret_set(object, name, value),
+            Expr::Map(brace, entries) => self.interpret_map(brace, entries),
+            Expr::Index(object, bracket, key) => self.interpret_index(object, bracket, key),
+            Expr::SetIndex(object, bracket, key, value) => {
+                self.interpret_set_index(object, bracket, key, value)
+            }
+        })
+    }
+
+    fn visit_stmt(&self, stmt: &Stmt) -> Self::StmtOutput {
+        self.nested(|| {
+            match stmt {
+                expr::Stmt::Expression(expr) => {
+                    self.visit_expr(expr)?;
+                }
+                expr::Stmt::Print(expr) => {
+                    let value = self.visit_expr(expr)?;
+                    writeln!(self.output.borrow_mut(), "{value}").map_err(|err| {
+                        IError::Output {
+                            message: err.to_string(),
+                        }
+                    })?;
+                }
+                expr::Stmt::Var(name, initializer) => {
+                    self.execute_var(name, initializer.as_ref())?
+                }
+                expr::Stmt::Const(name, value) => self.execute_const(name, value)?,
+                expr::Stmt::Block(stmts) => self.execute_block(stmts)?,
+                expr::Stmt::If(condition, then_branch, else_branch) => {
+                    self.execute_if(condition, then_branch, else_branch.as_deref())?
+                }
+                expr::Stmt::While(label, condition, body, increment) => {
+                    self.execute_while(label.as_ref(), condition, body, increment.as_ref())?
+                }
+                expr::Stmt::Function(function) => self.execute_function(function)?,
+                expr::Stmt::Class(name, superclass, methods) => {
+                    self.execute_class(name, superclass, methods)?
+                }
+                expr::Stmt::Break(token, label, value) => {
+                    self.execute_break(token, label.as_ref(), value.as_ref())?
+                }
+                expr::Stmt::Try(body, catch, finally) => {
+                    self.execute_try(body, catch.as_ref(), finally.as_ref())?
+                }
+                expr::Stmt::Throw(token, value) => self.execute_throw(token, value)?,
+                expr::Stmt::Switch(value, cases, default) => {
+                    self.execute_switch(value, cases, default.as_ref())?
+                }
+                expr::Stmt::ForIn(keyword, label, name, collection, body) => {
+                    self.execute_for_in(keyword, label.as_ref(), name, collection, body)?
+                }
+                expr::Stmt::Import(keyword, path, name) => {
+                    self.execute_import(keyword, path, name)?
+                }
+                expr::Stmt::Continue(token, label) => {
+                    return Err(IError::Continue {
+                        token: token.clone(),
+                        label: label.as_ref().map(|label| label.lexeme().to_owned()),
+                    })
+                }
+                expr::Stmt::Return(token, value) => self.execute_return(token, value.as_ref())?,
+            };
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{
+        parser::{LanguageLevel, Parser},
+        resolver::Resolver,
+        scanner::Scanner,
+    };
+
+    // Collects everything the interpreter prints.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Runs a whole program and returns what it printed.
+    fn run(source: &str) -> String {
+        run_at(LanguageLevel::Standard, source)
+    }
+
+    fn run_at(level: LanguageLevel, source: &str) -> String {
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Source should scan");
+        let stmts = Parser::new(tokens)
+            .with_language_level(level)
+            .parse()
+            .expect("Source should parse");
+
+        let output = Output::default();
+        let interpreter = Interpreter::new()
+            .with_language_level(level)
+            .with_output(output.clone());
+        Resolver::new(&interpreter)
+            .resolve(&stmts)
+            .expect("Source should resolve");
+        interpreter.interpret(&stmts).expect("Program should run");
+
+        let printed = output.0.borrow();
+        String::from_utf8(printed.clone()).expect("Output should be UTF-8")
+    }
+
+    #[test]
+    fn string_literals_concatenate_without_quotes() {
+        assert_eq!(run("print \"a\" + \"b\";"), "ab\n");
+        assert_eq!(run("var s = \"\"; s = s + \"x\"; print s;"), "x\n");
+    }
+
+    #[test]
+    fn if_and_blocks_evaluate_to_values() {
+        let run = |source| run_at(LanguageLevel::Extended, source);
+
+        assert_eq!(run("var x = if (true) { 1 } else { 2 }; print x;"), "1\n");
+        assert_eq!(
+            run("print if (nil) { 1 } else if (1 > 2) { 2 } else { 3 };"),
+            "3\n"
+        );
+        assert_eq!(run("print if (false) { 1 };"), "nil\n");
+        assert_eq!(run("print { 1; };"), "nil\n");
+
+        // The block gets its own scope, and the trailing expression still sees it
+        assert_eq!(
+            run("var a = 1; var b = { var a = 2; a + 10 }; print a; print b;"),
+            "1\n12\n"
+        );
+
+        // Only the taken branch runs
+        assert_eq!(
+            run("var a = 0; if (a == 0) { a = 1; } else { a = 2; } print a;"),
+            "1\n"
+        );
+    }
+
+    #[test]
+    fn if_while_and_for_statements() {
+        let program = "fun f(n) { if (n == 0) return 0; return n; }
+                       print f(0); print f(3);
+                       if (f(1) > 1) print \"big\"; else print \"small\";";
+        assert_eq!(run(program), "0\n3\nsmall\n");
+        assert_eq!(run_at(LanguageLevel::Extended, program), "0\n3\nsmall\n");
+
+        assert_eq!(
+            run("var i = 0; while (i < 3) { print i; i = i + 1; }"),
+            "0\n1\n2\n"
+        );
+
+        // The loop variable is scoped to the loop
+        assert_eq!(
+            run("var i = \"outer\";
+                 var sum = 0; for (var i = 1; i <= 4; i = i + 1) sum = sum + i;
+                 print sum; print i;"),
+            "10\nouter\n"
+        );
+
+        // Recursion at the standard level
+        assert_eq!(
+            run(
+                "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+                 print fib(10);"
+            ),
+            "55\n"
+        );
+    }
+
+    #[test]
+    fn and_and_or_short_circuit() {
+        assert_eq!(
+            run("print true and false; print nil or \"default\"; print 1 and 2; print false or nil;"),
+            "false\ndefault\n2\nnil\n"
+        );
+
+        // The right operand only runs when the left one doesn't decide
+        assert_eq!(
+            run("fun loud(v) { print \"ran\"; return v; }
+                 print false and loud(true); print true or loud(false); print true and loud(1);"),
+            "false\ntrue\nran\n1\n"
+        );
+    }
+
+    #[test]
+    fn loop_evaluates_to_break_value() {
+        let run = |source| run_at(LanguageLevel::Extended, source);
+
+        assert_eq!(
+            run("var i = 0; var x = loop { i = i + 1; if (i == 3) { break i * 10; } }; print x;"),
+            "30\n"
+        );
+        assert_eq!(run("print loop { break; };"), "nil\n");
+
+        // Breaks out of the innermost loop only, and leaves the scopes it was in
+        assert_eq!(
+            run("var a = 1;
+                 var n = loop {
+                     var a = 2;
+                     var inner = loop { var a = 3; break a; };
+                     break inner + a;
+                 };
+                 print n; print a;"),
+            "5\n1\n"
+        );
+    }
+
+    #[test]
+    fn continue_skips_to_the_next_iteration() {
+        let run = |source| run_at(LanguageLevel::Extended, source);
+
+        assert_eq!(
+            run("var i = 0; var sum = 0;
+                 loop {
+                     i = i + 1;
+                     if (i > 5) { break; }
+                     if (i == 2) { continue; }
+                     { var skipped = i; if (i == 4) { continue; } }
+                     sum = sum + i;
+                 }
+                 print sum;"),
+            "9\n"
+        );
+
+        // Only the innermost loop continues
+        assert_eq!(
+            run("var outer = 0; var inner = 0;
+                 loop {
+                     outer = outer + 1;
+                     if (outer > 2) { break; }
+                     var j = 0;
+                     loop { j = j + 1; if (j > 3) { break; } if (j == 1) { continue; } inner = inner + 1; }
+                 }
+                 print outer; print inner;"),
+            "3\n4\n"
+        );
+    }
+
+    #[test]
+    fn break_and_continue_in_while_and_for() {
+        assert_eq!(
+            run("var i = 0;
+                 while (true) { i = i + 1; if (i == 2) continue; if (i > 4) break; print i; }"),
+            "1\n3\n4\n"
+        );
+
+        // `continue` still runs the increment, or this would never end
+        assert_eq!(
+            run("for (var i = 0; i < 5; i = i + 1) { if (i % 2 == 0) continue; print i; }"),
+            "1\n3\n"
+        );
+        assert_eq!(
+            run("for (var i = 0; ; i = i + 1) { if (i == 3) break; print i; }"),
+            "0\n1\n2\n"
+        );
+
+        // Labels reach the outer loop, and its increment runs on `continue outer`
+        assert_eq!(
+            run("outer: for (var i = 0; i < 3; i = i + 1) {
+                   var j = 0;
+                   inner: while (true) {
+                     j = j + 1;
+                     if (j > i) continue outer;
+                     if (i == 2) break outer;
+                     print i + j * 10;
+                   }
+                 }
+                 print \"done\";"),
+            "11\ndone\n"
+        );
+    }
+
+    #[test]
+    fn natives_are_global_functions() {
+        assert_eq!(
+            run("print clock; print clock is Function; print clock() > 0;
+                 var start = clock(); print clock() - start >= 0;"),
+            "<native fn>\ntrue\ntrue\ntrue\n"
+        );
+        assert!(matches!(
+            run_err("clock(1);"),
+            IError::WrongArity {
+                expected: 0,
+                got: 1,
+                ..
+            }
+        ));
+
+        let output = Output::default();
+        let interpreter =
+            Interpreter::new()
+                .with_output(output.clone())
+                .with_native(NativeFunction {
+                    name: "twice",
+                    arity: 1,
+                    function: |_, _, arguments| match arguments {
+                        [Value::Int(n)] => Ok(Value::Int(n * 2)),
+                        _ => Ok(Value::Nil),
+                    },
+                });
+        let tokens = Scanner::new(b"print twice(21); print twice(\"a\");")
+            .scan_tokens()
+            .unwrap();
+        interpreter
+            .interpret(&Parser::new(tokens).parse().unwrap())
+            .unwrap();
+        assert_eq!(*output.0.borrow(), b"42\nnil\n");
+    }
+
+    #[test]
+    fn deterministic_runs_print_the_same() {
+        let source = "print clock(); print clock();
+                      var r = random(); print r >= 0 and r < 1; print r;";
+        let run_deterministic = || {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+            let output = Output::default();
+            Interpreter::new()
+                .with_deterministic(true)
+                .with_output(output.clone())
+                .interpret(&Parser::new(tokens).parse().unwrap())
+                .unwrap();
+            let printed = output.0.borrow();
+            String::from_utf8(printed.clone()).unwrap()
+        };
+
+        let first = run_deterministic();
+        assert!(first.starts_with("0\n1\ntrue\n"));
+        assert_eq!(first, run_deterministic());
+
+        assert_eq!(
+            run("var r = random(); print r >= 0 and r < 1; print random() != r;"),
+            "true\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn memoized_functions_are_called_once_per_arguments() {
+        assert_eq!(
+            run("var calls = 0;
+                 fun fib(n) {
+                   calls = calls + 1;
+                   if (n < 2) return n;
+                   return fib(n - 1) + fib(n - 2);
+                 }
+                 fib = memoize(fib);
+                 print fib(30); print calls;
+                 print fib(30); print calls;
+                 print fib; print fib is Function; print fib == fib;"),
+            "832040\n31\n832040\n31\n<fn fib>\ntrue\ntrue\n"
+        );
+        // Instances can't be map keys, so calls with them aren't remembered.
+        assert_eq!(
+            run("class A {} var calls = 0;
+                 fun f(a) { calls = calls + 1; }
+                 var g = memoize(f); var a = A();
+                 g(a); g(a); print calls;"),
+            "2\n"
+        );
+        assert!(matches!(
+            run_err("memoize(1);"),
+            IError::Native {
+                name: "memoize",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn event_loop_runs_timers_in_order() {
+        let run_loop = |source: &str| {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+            let stmts = Parser::new(tokens).parse().unwrap();
+            let output = Output::default();
+            let interpreter = Interpreter::new()
+                .with_deterministic(true)
+                .with_error_policy(ErrorPolicy::Abort)
+                .with_output(output.clone());
+            Resolver::new(&interpreter).resolve(&stmts).unwrap();
+            let result = interpreter.interpret(&stmts);
+            let printed = output.0.borrow();
+            (result, String::from_utf8(printed.clone()).unwrap())
+        };
+
+        let (result, printed) = run_loop(
+            "var ticks = 0;
+             var id;
+             fun tick() {
+               ticks = ticks + 1;
+               print \"tick\";
+               if (ticks == 3) clearTimer(id);
+             }
+             fun later() { print \"later\"; }
+             fun first() { print \"first\"; setTimeout(later, 40); }
+             fun between() { print \"between\"; }
+             id = setInterval(tick, 10);
+             setTimeout(between, 25);
+             setTimeout(first, 0);
+             print \"start\";
+             runEventLoop();
+             print clearTimer(id);",
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            printed,
+            "start\nfirst\ntick\ntick\nbetween\ntick\nlater\nfalse\n"
+        );
+
+        // Errors in a callback come out of runEventLoop.
+        let (result, _) = run_loop("fun f() { undefined; } setTimeout(f, 0); runEventLoop();");
+        assert!(matches!(
+            result.unwrap_err().errors()[0],
+            IError::UndefinedVariable { .. }
+        ));
+        let (result, _) = run_loop("setTimeout(runEventLoop, 0); runEventLoop();");
+        assert!(matches!(
+            result.unwrap_err().errors()[0],
+            IError::Native {
+                name: "runEventLoop",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn functions_know_their_name_and_arity() {
+        assert_eq!(
+            run("fun add(a, b) { return a + b; }
+                 fun greet(name, greeting = \"hi\") {}
+                 fun all(...rest) {}
+                 class P { init(x) {} }
+                 class Q {}
+                 print name(add); print arity(add);
+                 print arity(greet); print arity(all);
+                 print name(P); print arity(P); print arity(Q);
+                 print name(clock); print arity(clock);
+                 print name(memoize(add)); print arity(memoize(add));
+                 var f = add; print f == add; print name(f);"),
+            "add\n2\n1\n0\nP\n1\n0\nclock\n0\nadd\n2\ntrue\nadd\n"
+        );
+        assert!(matches!(
+            run_err("name(1);"),
+            IError::Native { name: "name", .. }
+        ));
+    }
+
+    #[test]
+    fn maps_hold_values_by_key() {
+        assert_eq!(
+            run("var m = {\"a\": 1, 2: \"two\", true: nil};
+                 print m;
+                 print m[\"a\"]; print m[1 + 1]; print m[\"missing\"];
+                 m[\"a\"] = m[\"a\"] + 1; m[\"b\"] = {};
+                 print m[\"a\"]; print m;
+                 print m is Map; print m == m; print {} == {};"),
+            "{\"a\": 1, 2: \"two\", true: nil}\n\
+             1\ntwo\nnil\n\
+             2\n{\"a\": 2, 2: \"two\", true: nil, \"b\": {}}\n\
+             true\ntrue\nfalse\n"
+        );
+
+        // Maps are shared, -0 and 0 are the same key, and a map can contain itself
+        assert_eq!(
+            run("var a = {}; var b = a; b[0] = 1; print a[-0];
+                 a[\"self\"] = a; print a;"),
+            "1\n{0: 1, \"self\": {...}}\n"
+        );
+    }
+
+    #[test]
+    fn maps_can_be_iterated_and_shrunk() {
+        let run = |source| run_at(LanguageLevel::Extended, source);
+
+        assert_eq!(
+            run("var m = {\"x\": 1, \"y\": 2, \"z\": 3};
+                 print delete(m, \"y\"); print delete(m, \"y\");
+                 var key = next_key(m, nil);
+                 loop { if (key == nil) { break; } print key; key = next_key(m, key); }
+                 print next_key({}, nil);"),
+            "2\nnil\nx\nz\nnil\n"
+        );
+    }
+
+    #[test]
+    fn map_errors() {
+        assert!(matches!(
+            run_err("var a = 1; a[0];"),
+            IError::NotIndexable { .. }
+        ));
+        assert!(matches!(
+            run_err("var m = {}; m[nil] = 1;"),
+            IError::InvalidKey { .. }
+        ));
+        assert!(matches!(
+            run_err("var m = {}; m[m];"),
+            IError::InvalidKey { .. }
+        ));
+        assert!(matches!(
+            run_err("print {0/0: 1};"),
+            IError::InvalidKey { .. }
+        ));
+        assert!(matches!(
+            run_err("delete(1, 2);"),
+            IError::Native { name: "delete", .. }
+        ));
+        assert_eq!(
+            run_err("next_key({}, 1);").to_string(),
+            "Key 1 is not in the map in call to 'next_key' at line 1."
+        );
+    }
+
+    #[test]
+    fn exceptions_are_caught() {
+        assert_eq!(
+            run("fun fail(key) { store(key); return key; }
+                 fun store(key) { var m = {}; m[key] = 1; }
+                 try { throw {1: 2}; print 0; } catch (e) { print e[1]; }
+                 try { print 1; } catch (e) { print 0; } finally { print 2; }
+                 try { undefined; } catch (e) { print e; }
+                 try { fail(nil); } catch (e) { print e; } finally { print 3; }
+                 fun f() { try { return 4; } finally { print 5; } }
+                 print f();"),
+            "2\n1\n2\n\
+             Undefined variable 'undefined' at line 5.\n\
+             Map keys must be numbers, strings or booleans at line 2.\n3\n\
+             5\n4\n"
+        );
+
+        // The caught value is only visible in the handler, and rethrowing reaches the next try
+        assert_eq!(
+            run("var e = \"outer\";
+                 try {
+                   try { throw 1; } catch (e) { throw e + 1; } finally { print \"inner finally\"; }
+                 } catch (e) { print e; }
+                 print e;"),
+            "inner finally\n2\nouter\n"
+        );
+    }
+
+    #[test]
+    fn finally_keeps_what_is_unwinding() {
+        // Returns and throws inside the finally don't replace the ones it runs after
+        assert_eq!(
+            run("fun g() { return 2; }
+                 fun f() { try { return 1; } finally { g(); } }
+                 print f();
+                 try {
+                   try { throw \"first\"; }
+                   finally { try { throw \"second\"; } catch (e) { print e; } }
+                 } catch (e) { print e; }"),
+            "1\nsecond\nfirst\n"
+        );
+        assert_eq!(
+            run_at(
+                LanguageLevel::Extended,
+                "print loop { try { break 1; } finally { loop { break 2; } } };"
+            ),
+            "1\n"
+        );
+    }
+
+    #[test]
+    fn uncaught_exceptions_are_runtime_errors() {
+        let err = run_err("class Oops {} try { throw Oops(); } finally { print 1; }");
+        assert!(matches!(err, IError::Throw { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Uncaught exception: Oops instance at line 1."
+        );
+
+        // An error in finally replaces the one being unwound
+        assert!(matches!(
+            run_err("try { throw 1; } finally { undefined; }"),
+            IError::UndefinedVariable { .. }
+        ));
+    }
+
+    #[test]
+    fn runtime_errors_show_the_calls_they_happened_in() {
+        let errors = run_errors(
+            "fun inner() { return undefined; }
+             fun outer() {
+               return inner();
+             }
+             outer();",
+        );
+        assert_eq!(
+            errors.to_string(),
+            "Undefined variable 'undefined' at line 1.
+  in inner(), called from outer() at line 3
+  in outer(), called from script at line 5"
+        );
+
+        // Errors outside any function, and caught ones, have no trace
+        assert_eq!(
+            run_errors("undefined;").reports().next().unwrap(),
+            "Undefined variable 'undefined' at line 1."
+        );
+        let errors = run_errors(
+            "fun f() { undefined; }
+             try { f(); } catch (e) {}
+             undefined;",
+        );
+        assert_eq!(
+            errors.reports().next().unwrap(),
+            "Undefined variable 'undefined' at line 3."
+        );
+
+        // Deep recursion only shows the innermost calls
+        let errors = run_errors("fun f(n) { return n == 0 ? undefined : f(n - 1); } f(20);");
+        let report = errors.reports().next().unwrap();
+        assert_eq!(report.lines().count(), 1 + MAX_TRACE_FRAMES + 1);
+        assert!(report.ends_with("  ... and 5 more calls"));
+    }
+
+    // Writes `files` to a directory of their own and runs `main` as if it was in there.
+    fn run_with_files(
+        dir: &str,
+        files: &[(&str, &str)],
+        main: &str,
+    ) -> Result<String, RuntimeErrors> {
+        run_with_files_in(Interpreter::new(), dir, files, main)
+    }
+
+    fn run_with_files_in(
+        interpreter: Interpreter,
+        dir: &str,
+        files: &[(&str, &str)],
+        main: &str,
+    ) -> Result<String, RuntimeErrors> {
+        let dir =
+            std::env::temp_dir().join(format!("lox-import-test-{}-{dir}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (path, source) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+
+        let tokens = Scanner::new(main.as_bytes()).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let output = Output::default();
+        let interpreter = interpreter
+            .with_error_policy(ErrorPolicy::Abort)
+            .with_output(output.clone());
+        interpreter.set_import_dir(&dir);
+        Resolver::new(&interpreter).resolve(&stmts).unwrap();
+        let result = interpreter.interpret(&stmts);
+
+        fs::remove_dir_all(dir).unwrap();
+        result.map(|()| String::from_utf8(output.0.take()).unwrap())
+    }
+
+    #[test]
+    fn imported_modules_run_once_in_their_own_globals() {
+        let util = "print \"loading util\";
+                    var greeting = \"hi\";
+                    fun greet(name) { return greeting + \" \" + name; }";
+        assert_eq!(
+            run_with_files(
+                "once",
+                &[("util.lox", util)],
+                "import \"util.lox\";
+                 import \"./util.lox\";
+                 var greeting = \"not this one\";
+                 print util[\"greet\"](\"lox\");
+                 print util;"
+            )
+            .unwrap(),
+            "loading util\nhi lox\n{\"greet\": <fn greet>, \"greeting\": \"hi\"}\n"
+        );
+
+        // Paths are relative to the module doing the import
+        assert_eq!(
+            run_with_files(
+                "relative",
+                &[
+                    (
+                        "lib/a.lox",
+                        "import \"b.lox\"; var value = b[\"value\"] + 1;"
+                    ),
+                    ("lib/b.lox", "var value = 1;"),
+                ],
+                "import \"lib/a.lox\"; print a[\"value\"];"
+            )
+            .unwrap(),
+            "2\n"
+        );
+
+        // The importer's globals are out of reach
+        let errors = run_with_files(
+            "isolated",
+            &[("peek.lox", "print secret;")],
+            "var secret = 1; import \"peek.lox\";",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            errors.errors()[0],
+            IError::UndefinedVariable { .. }
+        ));
+    }
+
+    #[test]
+    fn import_errors() {
+        let errors = run_with_files(
+            "cycle",
+            &[
+                ("a.lox", "import \"b.lox\";"),
+                ("b.lox", "import \"c.lox\";"),
+                ("c.lox", "import \"b.lox\";"),
+            ],
+            "import \"a.lox\";",
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors.errors()[0].to_string(),
+            "Import cycle at line 1: b.lox -> c.lox -> b.lox."
+        );
+
+        let errors = run_with_files("missing", &[], "import \"missing.lox\";").unwrap_err();
+        assert!(matches!(errors.errors()[0], IError::ImportFailed { .. }));
+
+        let errors = run_with_files(
+            "broken",
+            &[("broken.lox", "print ;")],
+            "import \"broken.lox\";",
+        )
+        .unwrap_err();
+        assert!(matches!(errors.errors()[0], IError::ImportFailed { .. }));
+    }
+
+    #[test]
+    fn modules_are_resolved_in_their_own_globals() {
+        let strict = || Interpreter::new().with_strict_globals(true);
+
+        // The importer's `count` isn't the module's, so assigning it is undeclared there
+        let errors = run_with_files_in(
+            strict(),
+            "strict-undeclared",
+            &[("counter.lox", "fun bump() { count = count + 1; }")],
+            "var count = 0; import \"counter.lox\";",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            &errors.errors()[0],
+            IError::ImportFailed { message, .. } if message.contains("count")
+        ));
+
+        // Its own globals are fine, also ones declared after the function using them
+        assert_eq!(
+            run_with_files_in(
+                strict(),
+                "strict-declared",
+                &[(
+                    "counter.lox",
+                    "fun bump() { count = count + 1; return count; } var count = 0;"
+                )],
+                "import \"counter.lox\"; print counter[\"bump\"]();",
+            )
+            .unwrap(),
+            "1\n"
+        );
+    }
+
+    #[test]
+    fn switch_runs_the_matching_case() {
+        let program = |value| {
+            format!(
+                "fun describe(x) {{
+                   switch (x) {{
+                     case 1: return \"one\";
+                     case \"1\": return \"string one\";
+                     case true: var text = \"yes\"; return text;
+                     case nil: return \"nothing\";
+                     default: return \"something else\";
+                   }}
+                 }}
+                 print describe({value});"
+            )
+        };
+        assert_eq!(run(&program("1")), "one\n");
+        assert_eq!(run(&program("\"1\"")), "string one\n");
+        assert_eq!(run(&program("1 == 1")), "yes\n");
+        assert_eq!(run(&program("nil")), "nothing\n");
+        assert_eq!(run(&program("2")), "something else\n");
+
+        // Cases don't fall through, and without a default nothing runs
+        assert_eq!(
+            run("switch (1) { case 1: print \"a\"; case 2: print \"b\"; } print \"done\";"),
+            "a\ndone\n"
+        );
+        assert_eq!(run("switch (3) { case 1: print 1; } print 2;"), "2\n");
+
+        // `break` leaves the loop around the switch
+        assert_eq!(
+            run_at(
+                LanguageLevel::Extended,
+                "var i = 0;
+                 loop { i = i + 1; switch (i) { case 3: break; default: print i; } }"
+            ),
+            "1\n2\n"
+        );
+    }
+
+    #[test]
+    fn uninitialized_variables_cant_be_read() {
+        let err = run_err("var a; print a;");
+        assert!(matches!(err, IError::UninitializedVariable { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Variable 'a' is read before it is assigned a value at line 1."
+        );
+        // Also locals, found through the resolver
+        assert!(matches!(
+            run_err("{ var a; fun f() { return a; } f(); }"),
+            IError::UninitializedVariable { .. }
+        ));
+
+        assert_eq!(
+            run("var a; a = 1; print a; { var b; b = 2; print b; } var c = nil; print c;"),
+            "1\n2\nnil\n"
+        );
+    }
+
+    #[test]
+    fn constants_cant_be_assigned() {
+        let err = run_err("const a = 1;\na = 2;");
+        assert!(matches!(err, IError::AssignToConstant { .. }));
+        assert_eq!(err.to_string(), "Can't assign to constant 'a' at line 2.");
+        assert!(matches!(
+            run_err("{ const a = 1; fun f() { a = 2; } f(); }"),
+            IError::AssignToConstant { .. }
+        ));
+
+        // Shadowing one is fine, and it can be used like any variable
+        assert_eq!(
+            run("const a = 1; { var a = 2; a = 3; print a; } print a + 1;"),
+            "3\n2\n"
+        );
+    }
+
+    #[test]
+    fn functions_can_be_called() {
+        assert_eq!(
+            run(
+                "fun greet(greeting, name) { print greeting + \", \" + name; }
+                 greet(\"Hello\", \"Lox\");
+                 print greet;
+                 print greet(\"Hi\", \"again\");"
+            ),
+            "Hello, Lox\n<fn greet>\nHi, again\nnil\n"
+        );
+
+        // Globals are visible and can be changed, the caller's locals are not
+        assert_eq!(
+            run("var count = 0;
+                 fun bump() { count = count + 1; }
+                 { var count = 10; bump(); bump(); print count; }
+                 print count;"),
+            "10\n2\n"
+        );
+
+        assert_eq!(
+            run("fun f() {} print f == f; print f is Function;"),
+            "true\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn return_unwinds_to_the_call() {
+        assert_eq!(
+            run("fun fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); } print fib(15);"),
+            "610\n"
+        );
+
+        // Out of nested blocks, skipping the rest of the body, and leaving the scopes it was in
+        assert_eq!(
+            run("var a = \"global\";
+                 fun f() { { var a = \"inner\"; { return a; } } print \"unreachable\"; }
+                 print f(); print a;"),
+            "inner\nglobal\n"
+        );
+
+        assert_eq!(run("fun f() { return; } print f();"), "nil\n");
+    }
+
+    #[test]
+    fn closures_capture_their_environment() {
+        assert_eq!(
+            run("fun make_counter() {
+                     var i = 0;
+                     fun count() { i = i + 1; return i; }
+                     return count;
+                 }
+                 var a = make_counter();
+                 var b = make_counter();
+                 print a(); print a(); print b(); print a();"),
+            "1\n2\n1\n3\n"
+        );
+
+        // Changes to captured variables after the closure was created are visible in it,
+        // and changes it makes are visible outside
+        assert_eq!(
+            run("{
+                     var x = \"before\";
+                     fun show() { print x; }
+                     fun set() { x = \"set\"; }
+                     show();
+                     x = \"after\";
+                     show();
+                     set();
+                     print x;
+                 }"),
+            "before\nafter\nset\n"
+        );
+
+        // Every run of a declaration makes a new function
+        assert_eq!(
+            run("fun make() { fun f() {} return f; } print make() == make(); var f = make(); print f == f;"),
+            "false\ntrue\n"
+        );
+    }
+
+    #[test]
+    fn classes_create_instances_with_fields() {
+        assert_eq!(
+            run("class Bagel { eat() { print \"Crunch\"; } }
+                 var bagel = Bagel();
+                 print Bagel; print bagel;
+                 bagel.flavour = \"sesame\";
+                 print bagel.flavour;
+                 bagel.eat();
+                 print bagel is Bagel; print bagel is Instance; print Bagel is Class;"),
+            "Bagel\nBagel instance\nsesame\nCrunch\ntrue\ntrue\ntrue\n"
+        );
+
+        // Instances are references, and fields shadow methods
+        assert_eq!(
+            run("class Box {}
+                 var a = Box(); var b = a;
+                 b.value = 1; a.value = a.value + 1;
+                 print b.value; print a == b; print a == Box();
+                 class C { m() { return \"method\"; } }
+                 var c = C(); print c.m(); c.m = 1; print c.m;"),
+            "2\ntrue\nfalse\nmethod\n1\n"
+        );
+
+        // Methods close over the environment of the class declaration
+        assert_eq!(
+            run("fun make() { var n = 0; class Counter { next() { n = n + 1; return n; } } return Counter; }
+                 var counter = make()();
+                 counter.next(); print counter.next();"),
+            "2\n"
+        );
+    }
+
+    #[test]
+    fn this_is_the_instance_the_method_was_looked_up_on() {
+        assert_eq!(
+            run("class Person {
+                   greet() { print \"hi \" + this.name; }
+                   rename(name) { this.name = name; return this; }
+                 }
+                 var a = Person(); a.name = \"a\";
+                 var b = Person(); b.name = \"b\";
+                 a.greet(); b.greet();
+                 // Methods stay bound when taken off the instance
+                 var greet = a.greet; b.greet = greet; b.greet();
+                 print a.rename(\"c\") == a; a.greet();"),
+            "hi a\nhi b\nhi a\ntrue\nhi c\n"
+        );
+
+        // Functions inside methods close over `this`
+        assert_eq!(
+            run("class Counter {
+                   incrementer() { fun inc() { this.n = this.n + 1; } return inc; }
+                 }
+                 var c = Counter(); c.n = 0;
+                 var inc = c.incrementer(); inc(); inc();
+                 print c.n;"),
+            "2\n"
+        );
+    }
+
+    #[test]
+    fn initializers_run_when_a_class_is_called() {
+        assert_eq!(
+            run("class Point {
+                   init(x, y) { this.x = x; this.y = y; }
+                   sum() { return this.x + this.y; }
+                 }
+                 var p = Point(1, 2);
+                 print p.sum();
+                 // Calling it again returns the instance too, also after an early return
+                 class Early { init() { this.n = 1; return; this.n = 2; } }
+                 var e = Early(); print e.n; print e.init() == e;"),
+            "3\n1\ntrue\n"
+        );
+
+        // Inherited from the superclass
+        assert_eq!(
+            run("class A { init(name) { this.name = name; } }
+                 class B < A {}
+                 print B(\"b\").name;"),
+            "b\n"
+        );
+
+        assert!(matches!(
+            run_err("class A { init(x) {} } A();"),
+            IError::WrongArity {
+                expected: 1,
+                got: 0,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn subclasses_inherit_and_call_super() {
+        assert_eq!(
+            run("class Doughnut {
+                   cook() { print \"Fry until golden brown.\"; }
+                   name() { return \"doughnut\"; }
+                 }
+                 class BostonCream < Doughnut {
+                   cook() { super.cook(); print \"Pipe full of custard.\"; }
+                 }
+                 class Filled < BostonCream {}
+                 var d = Filled();
+                 d.cook();
+                 print d.name();
+                 print d is Doughnut; print d is Filled; print Doughnut() is Filled;"),
+            "Fry until golden brown.\nPipe full of custard.\ndoughnut\ntrue\ntrue\nfalse\n"
+        );
+
+        // `super` is the superclass of the class the method is in, not of the instance
+        assert_eq!(
+            run("class A { m() { return \"A\"; } }
+                 class B < A { m() { return \"B\"; } test() { return super.m(); } }
+                 class C < B {}
+                 print C().test();"),
+            "A\n"
+        );
+    }
+
+    #[test]
+    fn getters_and_setters_run_on_property_access() {
+        assert_eq!(
+            run("class Circle {
+                   area { return 3 * this.r * this.r; }
+                   set radius(value) { print \"set\"; this.r = value; }
+                   radius { return this.r; }
+                 }
+                 var c = Circle();
+                 print c.radius = 2;
+                 print c.area; print c.radius;
+                 // Taken off the instance like a field, not called later
+                 var area = c.area; c.radius = 1; print area;"),
+            "set\n2\n12\n2\nset\n12\n"
+        );
+
+        // Accessors are inherited and reachable through `super`
+        assert_eq!(
+            run("class A { name { return \"A\"; } }
+                 class B < A { name { return super.name + \"B\"; } }
+                 class C < B {}
+                 print C().name;"),
+            "AB\n"
+        );
+
+        assert!(matches!(
+            run_err("class A { x { return 1; } } A().x = 2;"),
+            IError::ReadOnlyProperty { .. }
+        ));
+        // Errors in accessors have a frame like calls
+        assert_eq!(
+            run_errors("class A { x { return undefined; } }\nA().x;").to_string(),
+            "Undefined variable 'undefined' at line 1.\n  in x(), called from script at line 2"
+        );
+    }
+
+    #[test]
+    fn operators_on_instances_call_methods() {
+        assert_eq!(
+            run("class Vec {
+                   plus(other) { var v = Vec(); v.x = this.x + other.x; return v; }
+                   times(other) { return this.x * other.x; }
+                   equals(other) { return this.x == other.x; }
+                   compare(other) { return this.x - other.x; }
+                 }
+                 var a = Vec(); a.x = 1;
+                 var b = Vec(); b.x = 2;
+                 print (a + b).x; print a * b;
+                 print a == b; print a != b; print a + a == b;
+                 print a < b; print a >= b; print b > a;"),
+            "3\n2\nfalse\ntrue\ntrue\ntrue\nfalse\ntrue\n"
+        );
+
+        // Without the method, instances behave like before
+        assert_eq!(
+            run("class A {} var a = A(); print a == a; print a == A();"),
+            "true\nfalse\n"
+        );
+        assert!(matches!(
+            run_err("class A {} A() - A();"),
+            IError::BinaryOpError { .. }
+        ));
+
+        assert!(matches!(
+            run_err("class A { compare(other) { return nil; } } A() < A();"),
+            IError::InvalidComparison {
+                type_name: "Nil",
+                ..
+            }
+        ));
+        assert!(matches!(
+            run_err("class A { plus() { return 1; } } A() + A();"),
+            IError::WrongArity { expected: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn inheritance_errors() {
+        assert!(matches!(
+            run_err("var A = 1; class B < A {}"),
+            IError::SuperclassNotClass { .. }
+        ));
+        assert!(matches!(
+            run_err("class B < A {}"),
+            IError::UndefinedVariable { .. }
+        ));
+        assert!(matches!(
+            run_err("class A {} class B < A { m() { super.missing(); } } B().m();"),
+            IError::UndefinedProperty { .. }
+        ));
+    }
+
+    #[test]
+    fn property_errors() {
+        assert!(matches!(
+            run_err("class A {} A().missing;"),
+            IError::UndefinedProperty { .. }
+        ));
+        assert!(matches!(
+            run_err("var a = 1; a.x = 2;"),
+            IError::NotAnInstance { .. }
+        ));
+        assert!(matches!(
+            run_err("\"str\".len;"),
+            IError::NotAnInstance { .. }
+        ));
+        assert!(matches!(
+            run_err("class A {} A(1);"),
+            IError::WrongArity { expected: 0, .. }
+        ));
+        assert!(matches!(
+            run_err("var a = 1; a is a;"),
+            IError::UnknownType { .. }
+        ));
+    }
+
+    #[test]
+    fn return_leaves_loops_inside_the_function() {
+        assert_eq!(
+            run_at(
+                LanguageLevel::Extended,
+                "fun first_over(limit) {
+                     var i = 0;
+                     loop { i = i + 1; if (i * i > limit) { return i; } }
+                 }
+                 print first_over(50);"
+            ),
+            "8\n"
+        );
+    }
+
+    fn run_err(source: &str) -> IError {
+        run_errors(source).errors()[0].clone()
+    }
+
+    fn run_errors(source: &str) -> RuntimeErrors {
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Source should scan");
+        let stmts = Parser::new(tokens).parse().expect("Source should parse");
+
+        let interpreter = Interpreter::new()
+            .with_error_policy(ErrorPolicy::Abort)
+            .with_output(Output::default());
+        Resolver::new(&interpreter)
+            .resolve(&stmts)
+            .expect("Source should resolve");
+        interpreter.interpret(&stmts).unwrap_err()
+    }
+
+    #[test]
+    fn closures_keep_the_variable_they_were_resolved_to() {
+        assert_eq!(
+            run("var a = \"global\";
+                 {
+                   fun show() { print a; }
+                   show();
+                   var a = \"block\";
+                   show();
+                   a = \"changed\";
+                   show();
+                 }"),
+            "global\nglobal\nglobal\n"
+        );
+
+        // Assignments also go to the resolved variable
+        assert_eq!(
+            run("var a = 1;
+                 {
+                   fun set() { a = 2; }
+                   var a = 10;
+                   set();
+                   print a;
+                 }
+                 print a;"),
+            "10\n2\n"
+        );
+
+        // Globals are looked up when the function runs, so they can be declared later
+        assert_eq!(
+            run("fun f() { return later; } var later = \"ok\"; print f();"),
+            "ok\n"
+        );
+    }
+
+    #[test]
+    fn parameters_with_defaults_can_be_left_out() {
+        assert_eq!(
+            run(
+                "fun greet(name, punctuation = \"!\") { print name + punctuation; }
+                 greet(\"hi\"); greet(\"hi\", \"?\");"
+            ),
+            "hi!\nhi?\n"
+        );
+
+        // Defaults are evaluated on every call that needs them, and see the parameters
+        // before them
+        assert_eq!(
+            run("var calls = 0;
+                 fun next() { calls = calls + 1; return calls; }
+                 fun f(a, b = a + next()) { return b; }
+                 print f(10); print f(10, 0); print f(10);
+                 class A { m(x = this.x) { return x; } }
+                 var a = A(); a.x = 3; print a.m(); print a.m(4);"),
+            "11\n0\n12\n3\n4\n"
+        );
+
+        let err = run_err("fun f(a, b = 1, c = 2) {} f();");
+        assert!(matches!(
+            err,
+            IError::WrongArity {
+                required: 1,
+                expected: 3,
+                got: 0,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Expected 1 to 3 arguments but got 0 at line 1."
+        );
+        assert!(matches!(
+            run_err("fun f(a = 1) {} f(1, 2);"),
+            IError::WrongArity { got: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn rest_parameters_get_the_extra_arguments() {
+        assert_eq!(
+            run("fun log(level, ...args) { print level; print args; }
+                 log(\"info\"); log(\"warn\", 1, \"a\");
+                 fun f(a, b = 2, ...rest) { print a + b; return rest; }
+                 print f(1); print f(1, 3, 4)[0];"),
+            "info\n{}\nwarn\n{0: 1, 1: \"a\"}\n3\n{}\n4\n4\n"
+        );
+
+        let err = run_err("fun f(a, ...rest) {} f();");
+        assert_eq!(
+            err.to_string(),
+            "Expected at least 1 arguments but got 0 at line 1."
+        );
+    }
+
+    #[test]
+    fn calls_are_checked() {
+        assert!(matches!(
+            run_err("fun f(a, b) {} f(1);"),
+            IError::WrongArity {
+                expected: 2,
+                got: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            run_err("\"not a function\"();"),
+            IError::NotCallable { .. }
+        ));
+        // Locals of the caller stay out of reach
+        assert!(matches!(
+            run_err("fun f() { print a; } { var a = 1; f(); }"),
+            IError::UndefinedVariable { .. }
+        ));
+    }
+
+    #[test]
+    fn caller_scopes_are_restored_after_a_call() {
+        assert_eq!(
+            run("fun f(a) { var b = a; }
+                 { var a = 1; { var b = 2; f(3); print a + b; } }"),
+            "3\n"
+        );
+
+        // Also when the call fails
+        let tokens = Scanner::new("var a = 1; fun f() { undefined; } f(); print a;".as_bytes())
+            .scan_tokens()
+            .unwrap();
+        let output = Output::default();
+        let errors = Interpreter::new()
+            .with_output(output.clone())
+            .interpret(&Parser::new(tokens).parse().unwrap())
+            .unwrap_err();
+        assert_eq!(errors.errors().len(), 1);
+        assert_eq!(*output.0.borrow(), b"1\n");
+    }
+
+    #[test]
+    fn deeply_nested_expressions_are_an_error() {
+        let mut expr = Expr::Literal(Literal::Number(1.0));
+        for _ in 0..MAX_EVAL_DEPTH * 2 {
+            expr = Expr::Grouping(expr.into());
+        }
+
+        let result = Interpreter::new().visit_expr(&expr);
+
+        assert!(matches!(result, Err(IError::NestingTooDeep { .. })));
+    }
+
+    #[test]
+    fn functions_return_their_last_expression_at_the_extended_level() {
+        assert_eq!(
+            run_at(
+                LanguageLevel::Extended,
+                "fun r(n) { if (n == 0) { 0 } else { r(n - 1) + 1 } } print r(1000);
+                 fun twice(x) { var y = x * 2; y }
+                 fun early(x) { if (x) { return \"early\"; } \"late\" }
+                 fun statement() { twice(1); }
+                 fun empty() {}
+                 class A {
+                   init() { this.x = 1; 2 }
+                   get() { this.x }
+                 }
+                 print twice(3); print early(true); print early(false);
+                 print statement(); print empty(); print A().get();"
+            ),
+            "1000\n6\nearly\nlate\n2\nnil\n1\n"
+        );
+        // The book's functions only return what `return` gives them.
+        assert_eq!(run("fun f() { 1; } print f();"), "nil\n");
+    }
+
+    #[test]
+    fn deep_recursion() {
+        assert_eq!(
+            run("fun r(n) { return n == 0 ? 0 : r(n - 1) + 1; } print r(5000);"),
+            "5000\n"
+        );
+
+        let errors = run_errors("fun r(n) { return r(n + 1); } r(0);");
+        assert!(matches!(
+            errors.errors()[0],
+            IError::StackOverflow {
+                limit: MAX_CALL_DEPTH,
+                ..
+            }
+        ));
+    }
+
+    fn binary(left: f64, token_type: TokenType, lexeme: &str, right: f64) -> Expr {
+        Expr::Binary(
+            Expr::Literal(Literal::Number(left)).into(),
+            Token::new(token_type, lexeme.to_owned(), None, 1),
+            Expr::Literal(Literal::Number(right)).into(),
+        )
+    }
+
+    #[test]
+    fn numeric_binary_operations() {
+        let interpreter = Interpreter::new();
+        let cases = [
+            (binary(6.0, TokenType::Minus, "-", 4.0), Value::Number(2.0)),
+            (binary(6.0, TokenType::Slash, "/", 4.0), Value::Number(1.5)),
+            (binary(6.0, TokenType::Star, "*", 4.0), Value::Number(24.0)),
+            (
+                binary(6.0, TokenType::Percent, "%", 4.0),
+                Value::Number(2.0),
+            ),
+            (
+                binary(-7.0, TokenType::Percent, "%", 3.0),
+                Value::Number(-1.0),
+            ),
+            (
+                binary(2.0, TokenType::StarStar, "**", 0.5),
+                Value::Number(2f64.sqrt()),
+            ),
+            (binary(6.0, TokenType::Plus, "+", 4.0), Value::Number(10.0)),
+            (binary(6.0, TokenType::Greater, ">", 4.0), Value::Bool(true)),
+            (
+                binary(6.0, TokenType::GreaterEqual, ">=", 6.0),
+                Value::Bool(true),
+            ),
+            (binary(6.0, TokenType::Less, "<", 4.0), Value::Bool(false)),
+            (
+                binary(6.0, TokenType::LessEqual, "<=", 4.0),
+                Value::Bool(false),
+            ),
+            (
+                binary(6.0, TokenType::BangEqual, "!=", 4.0),
+                Value::Bool(true),
+            ),
+            (
+                binary(6.0, TokenType::EqualEqual, "==", 4.0),
+                Value::Bool(false),
+            ),
+        ];
+
+        for (expr, expected) in cases {
+            assert_eq!(interpreter.visit_expr(&expr).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn ints_are_exact() {
+        assert_eq!(
+            run("print 9007199254740992 + 1;
+                 print 2 ** 62;
+                 print 7 % 3 * 5;"),
+            "9007199254740993\n4611686018427387904\n5\n"
+        );
+
+        // Mixed with floats, and divided, they are floats
+        assert_eq!(
+            run("print 7 / 2; print 1 + 0.5; print 4 ** 0.5 is Float; print 1 == 1.0; print 2 > 1.5;"),
+            "3.5\n1.5\ntrue\ntrue\ntrue\n"
+        );
+        assert_eq!(
+            run("print 3 is Int; print 3 is Float; print 3.5 is Float; print 3 is Number;"),
+            "true\nfalse\ntrue\ntrue\n"
+        );
+        // Floats print all their digits, and nothing more
+        assert_eq!(
+            run("print 0.25 * 2.5; print 3.14159; print 2.0; print 1 / 3; print 0.1 + 0.2;"),
+            "0.625\n3.14159\n2\n0.3333333333333333\n0.30000000000000004\n"
+        );
+        // Equal numbers are the same key
+        assert_eq!(
+            run("var m = {1: \"a\"}; m[1.0] = \"b\"; print m;"),
+            "{1: \"b\"}\n"
+        );
+
+        let overflow = run_err("print 9223372036854775807 + 1;");
+        assert!(matches!(
+            overflow,
+            IError::BinaryOpError {
+                source: VError::Overflow { .. },
+                ..
+            }
+        ));
+        assert!(matches!(
+            run_err("print 1 % 0;"),
+            IError::BinaryOpError {
+                source: VError::DivisionByZero { .. },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn strings_and_numbers_concatenate() {
+        assert_eq!(
+            run("print \"count: \" + 3; print 1.5 + \" left\"; print \"\" + 2 + 2; print 2 + 2 + \"\";"),
+            "count: 3\n1.5 left\n22\n4\n"
+        );
+        // Floats keep all their digits in the string
+        assert_eq!(
+            run("print \"pi: \" + 3.14159; print 0.625 + \"\";"),
+            "pi: 3.14159\n0.625\n"
+        );
+
+        assert!(matches!(
+            run_err("print \"a\" + true;"),
+            IError::BinaryOpError { .. }
+        ));
+        assert!(matches!(
+            run_err("print nil + \"a\";"),
+            IError::BinaryOpError { .. }
+        ));
+    }
+
+    #[test]
+    fn strings_compare_by_code_point() {
+        assert_eq!(
+            run("print \"a\" < \"b\"; print \"ab\" > \"a\"; print \"B\" < \"a\"; print \"x\" >= \"x\";
+                 print 1 < 1.5;"),
+            "true\ntrue\ntrue\ntrue\ntrue\n"
+        );
+
+        let err = run_err("print \"1\" < 2;");
+        assert_eq!(
+            err.to_string(),
+            "Binary op error: Cannot compare String with Int at line 1"
+        );
+        assert!(matches!(
+            run_err("print nil <= nil;"),
+            IError::BinaryOpError { .. }
+        ));
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_eq!(
+            run("var x = 1; print -(-3); print !!x; print !nil; print -2 ** 2; print -1.5;"),
+            "3\ntrue\ntrue\n-4\n-1.5\n"
+        );
+        assert!(matches!(
+            run_err("print -\"a\";"),
+            IError::UnaryOpError { .. }
+        ));
+    }
+
+    #[test]
+    fn for_in_goes_over_map_keys_and_characters() {
+        assert_eq!(
+            run("var m = {\"a\": 1, 2: \"b\"};
+                 for (key in m) print key;
+                 for (c in \"hi\") print c;
+                 // Keys added in the loop aren't visited
+                 for (key in m) { m[key + \"!\"] = 0; }
+                 print m;"),
+            "a\n2\nh\ni\n{\"a\": 1, 2: \"b\", \"a!\": 0, \"2!\": 0}\n"
+        );
+
+        // `break` and `continue` work, and closures see the element of their iteration
+        assert_eq!(
+            run("var fns = {};
+                 for (c in \"abcd\") {
+                   switch (c) { case \"b\": continue; case \"d\": break; }
+                   fun f() { return c; }
+                   fns[c] = f;
+                 }
+                 for (c in fns) print fns[c]();"),
+            "a\nc\n"
+        );
+
+        assert!(matches!(
+            run_err("for (x in 1) print x;"),
+            IError::NotIterable {
+                type_name: "Int",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn labeled_break_and_continue_reach_outer_loops() {
+        assert_eq!(
+            run("outer: for (a in \"abc\") {
+                   for (b in \"xyz\") {
+                     switch (b) { case \"y\": continue outer; }
+                     switch (a) { case \"c\": break outer; }
+                     print a + b;
+                   }
+                 }
+                 print \"done\";"),
+            "ax\nbx\ndone\n"
+        );
+
+        // Also for `loop`, where an unlabeled break only leaves the inner one
+        assert_eq!(
+            run_at(
+                LanguageLevel::Extended,
+                "var n = 0;
+                 outer: loop {
+                   loop { n = n + 1; if (n == 2) { break; } continue outer; }
+                   inner: loop { n = n * 10; if (n > 100) { break outer; } continue inner; }
+                 }
+                 print n;"
+            ),
+            "200\n"
+        );
+        // Also as the first thing in a block expression
+        assert_eq!(
+            run_at(
+                LanguageLevel::Extended,
+                "print { inner: loop { break inner; } 1 };"
+            ),
+            "1\n"
+        );
+    }
+
+    #[test]
+    fn comma_gives_the_right_operand() {
+        assert_eq!(run("var a = 0; print (a = 1, a + 1); print a;"), "2\n1\n");
+    }
+
+    #[test]
+    fn ternaries_nest_to_the_right() {
+        assert_eq!(
+            run("print true ? 1 : true ? 2 : 3; print false ? 1 : false ? 2 : 3;"),
+            "1\n3\n"
+        );
+    }
+
+    #[test]
+    fn modulo_and_exponent_need_numbers() {
+        assert_eq!(run("print 7 % 4 + 2 ** 3 ** 2;"), "515\n");
+        assert!(matches!(
+            run_err("print \"a\" % 2;"),
+            IError::BinaryOpError { .. }
+        ));
+        assert!(matches!(
+            run_err("print 2 ** nil;"),
+            IError::BinaryOpError { .. }
+        ));
+    }
+
+    #[test]
+    fn is_checks_primitive_types() {
+        let interpreter = Interpreter::new();
+        let is = |literal: Literal, type_name: &str| {
+            interpreter.visit_expr(&Expr::Is(
+                Expr::Literal(literal).into(),
+                Token::new(TokenType::Identifier, type_name.to_owned(), None, 1),
+                Resolution::default(),
+            ))
+        };
+
+        assert_eq!(
+            is(Literal::Number(1.0), "Number").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            is(Literal::Number(1.0), "String").unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            is(Literal::String("a".to_owned()), "String").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(is(Literal::True, "Bool").unwrap(), Value::Bool(true));
+        assert_eq!(is(Literal::Nil, "Nil").unwrap(), Value::Bool(true));
+        assert!(matches!(
+            is(Literal::Nil, "Unknown"),
+            Err(IError::UnknownType { .. })
+        ));
+    }
+
+    #[test]
+    fn memory_limit_stops_string_growth() {
+        let s = Token::new(TokenType::Identifier, "s".to_owned(), None, 1);
+        let plus = Token::new(TokenType::Plus, "+".to_owned(), None, 1);
+        let interpreter = Interpreter::new().with_memory_limit(1024);
+
+        let double = Stmt::Expression(Expr::Assign(
+            s.clone(),
+            Expr::Binary(
+                Expr::Variable(s.clone(), Resolution::default()).into(),
+                plus,
+                Expr::Variable(s.clone(), Resolution::default()).into(),
+            )
+            .into(),
+            Resolution::default(),
+        ));
+
+        let init = Stmt::Var(
+            s.clone(),
+            Some(Expr::Literal(Literal::String("ab".to_owned()))),
+        );
+        assert!(interpreter.visit_stmt(&init).is_ok());
+
+        let result = (0..20).try_for_each(|_| interpreter.visit_stmt(&double));
+        assert!(matches!(result, Err(IError::OutOfMemory { limit: _, .. })));
+    }
+
+    #[test]
+    fn out_of_memory_can_be_caught() {
+        let tokens = Scanner::new(
+            "var s = \"ab\";
+             try { loop { s = s + s; } } catch (e) { s = nil; print \"caught\"; }"
+                .as_bytes(),
+        )
+        .scan_tokens()
+        .expect("Source should scan");
+        let stmts = Parser::new(tokens)
+            .with_language_level(LanguageLevel::Extended)
+            .parse()
+            .expect("Source should parse");
+
+        let output = Output::default();
+        let interpreter = Interpreter::new()
+            .with_memory_limit(1024)
+            .with_output(output.clone());
+        Resolver::new(&interpreter)
+            .resolve(&stmts)
+            .expect("Source should resolve");
+
+        assert!(interpreter.interpret(&stmts).is_ok());
+        assert_eq!(String::from_utf8_lossy(&output.0.borrow()), "caught\n");
+    }
+
+    #[test]
+    fn memory_limit_counts_maps_and_instances() {
+        let run_limited = |source: &str| {
+            let tokens = Scanner::new(source.as_bytes())
+                .scan_tokens()
+                .expect("Source should scan");
+            let stmts = Parser::new(tokens).parse().expect("Source should parse");
+
+            let interpreter = Interpreter::new()
+                .with_memory_limit(64 * 1024)
+                .with_error_policy(ErrorPolicy::Abort)
+                .with_output(Output::default());
+            Resolver::new(&interpreter)
+                .resolve(&stmts)
+                .expect("Source should resolve");
+            let result = interpreter.interpret(&stmts);
+            (result, interpreter.heap.bytes())
+        };
+
+        let (result, _) =
+            run_limited("var m = {}; var i = 0; while (true) { m[i] = i; i = i + 1; }");
+        assert!(matches!(
+            result.unwrap_err().errors()[0],
+            IError::OutOfMemory { .. }
+        ));
+
+        let (result, _) = run_limited(
+            "class Node {} var head = nil;
+             while (true) { var node = Node(); node.next = head; head = node; }",
+        );
+        assert!(matches!(
+            result.unwrap_err().errors()[0],
+            IError::OutOfMemory { .. }
+        ));
+
+        // Values that are no longer used are given back.
+        let (result, bytes) =
+            run_limited("var i = 0; while (i < 10000) { var m = {\"i\": i}; i = i + 1; }");
+        assert!(result.is_ok());
+        assert!(bytes < 64 * 1024);
+    }
+
+    fn policy_program() -> (Token, Vec<Stmt>) {
+        let a = Token::new(TokenType::Identifier, "a".to_owned(), None, 1);
+        let undefined = Token::new(TokenType::Identifier, "undefined".to_owned(), None, 1);
+        let stmts = vec![
+            Stmt::Var(a.clone(), Some(Expr::Literal(Literal::Number(1.0)))),
+            Stmt::Expression(Expr::Variable(undefined.clone(), Resolution::default())),
+            Stmt::Expression(Expr::Variable(undefined, Resolution::default())),
+            Stmt::Expression(Expr::Assign(
+                a.clone(),
+                Expr::Literal(Literal::Number(2.0)).into(),
+                Resolution::default(),
+            )),
+        ];
+
+        (a, stmts)
+    }
+
+    #[test]
+    fn abort_policy_stops_at_first_error() {
+        let (a, stmts) = policy_program();
+        let interpreter = Interpreter::new().with_error_policy(ErrorPolicy::Abort);
+
+        let errors = interpreter.interpret(&stmts).unwrap_err();
+        assert_eq!(errors.errors().len(), 1);
+        assert!(matches!(
+            errors.errors()[0],
+            IError::UndefinedVariable { .. }
+        ));
+        assert_eq!(
+            interpreter
+                .visit_expr(&Expr::Variable(a, Resolution::default()))
+                .unwrap(),
+            Value::Number(1.0)
+        );
+    }
+
+    #[test]
+    fn continue_policy_runs_every_statement() {
+        let (a, stmts) = policy_program();
+        let interpreter = Interpreter::new().with_error_policy(ErrorPolicy::Continue);
+
+        let errors = interpreter.interpret(&stmts).unwrap_err();
+        assert_eq!(errors.errors().len(), 2);
+        assert_eq!(
+            errors.to_string(),
+            "Undefined variable 'undefined' at line 1.\n\
+             Undefined variable 'undefined' at line 1.\n\
+             2 of 4 statements failed."
+        );
+        assert_eq!(
+            interpreter
+                .visit_expr(&Expr::Variable(a, Resolution::default()))
+                .unwrap(),
+            Value::Number(2.0)
+        );
+    }
+
+    #[test]
+    fn variables_are_restored_after_block() {
+        let name = Token::new(TokenType::Identifier, "a".to_owned(), None, 1);
+        let interpreter = Interpreter::new();
+
+        let stmts = vec![
+            Stmt::Var(name.clone(), Some(Expr::Literal(Literal::Number(1.0)))),
+            Stmt::Block(vec![
+                Stmt::Var(name.clone(), Some(Expr::Literal(Literal::Number(2.0)))),
+                Stmt::Expression(Expr::Assign(
+                    name.clone(),
+                    Expr::Literal(Literal::Number(3.0)).into(),
+                    Resolution::default(),
+                )),
+            ]),
+        ];
+        assert!(interpreter.interpret(&stmts).is_ok());
+
+        assert_eq!(
+            interpreter
+                .visit_expr(&Expr::Variable(name, Resolution::default()))
+                .unwrap(),
+            Value::Number(1.0)
+        );
+    }
 }
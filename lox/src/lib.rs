@@ -0,0 +1,13 @@
+pub mod cache;
+pub mod environment;
+pub mod expr;
+pub mod heap;
+pub mod interpreter;
+pub mod lox_string;
+pub mod natives;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod server;
+pub mod timers;
+pub mod token;
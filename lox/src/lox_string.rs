@@ -0,0 +1,166 @@
+use std::{
+    cell::{Ref, RefCell},
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+use crate::heap::{Allocation, Heap};
+
+// The bytes are counted against a Heap once the buffer is tracked, see LoxString::track.
+struct Buffer(String, Option<Allocation>);
+
+impl Buffer {
+    fn new(value: String) -> Self {
+        Self(value, None)
+    }
+
+    fn push_str(&mut self, value: &str) {
+        let before = self.0.capacity();
+        self.0.push_str(value);
+        if let Some(allocation) = &self.1 {
+            allocation.grow(self.0.capacity() - before);
+        }
+    }
+}
+
+// Runtime string value.
+//
+// Lox strings are immutable, so `s = s + part;` in a loop would copy the whole string on every
+// iteration with a plain String. Instead, a LoxString is a view of the first `len` bytes of a
+// shared buffer. Concatenating onto a value that ends where the buffer ends appends in place and
+// hands back a longer view of the same buffer, which makes building up a string amortized O(n).
+// Bytes before the end of a buffer are never changed, so every existing view stays the same.
+#[derive(Clone)]
+pub struct LoxString {
+    buf: Rc<RefCell<Buffer>>,
+    len: usize,
+}
+
+impl LoxString {
+    pub fn new(value: String) -> Self {
+        Self {
+            len: value.len(),
+            buf: Rc::new(RefCell::new(Buffer::new(value))),
+        }
+    }
+
+    pub fn as_str(&self) -> Ref<'_, str> {
+        Ref::map(self.buf.borrow(), |buf| &buf.0[..self.len])
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Counts the buffer against `heap` from now on, also when it grows. Buffers that are
+    // already tracked stay with their heap.
+    pub fn track(&self, heap: &Heap) {
+        let mut buf = self.buf.borrow_mut();
+        if buf.1.is_none() {
+            buf.1 = Some(heap.allocate(buf.0.capacity()));
+        }
+    }
+
+    pub fn concat(&self, other: &str) -> Self {
+        // Someone else already appended to the buffer (or `other` borrows from it),
+        // so the buffer can't be grown in place. Fall back to copying.
+        if let Ok(mut buf) = self.buf.try_borrow_mut() {
+            if buf.0.len() == self.len {
+                buf.push_str(other);
+                return Self {
+                    buf: self.buf.clone(),
+                    len: buf.0.len(),
+                };
+            }
+        }
+
+        let mut value = String::with_capacity(self.len + other.len());
+        value.push_str(&self.as_str());
+        value.push_str(other);
+        Self::new(value)
+    }
+}
+
+impl From<&str> for LoxString {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_owned())
+    }
+}
+
+impl PartialEq for LoxString {
+    fn eq(&self, other: &Self) -> bool {
+        *self.as_str() == *other.as_str()
+    }
+}
+
+impl Display for LoxString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", &*self.as_str())
+    }
+}
+
+impl Debug for LoxString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", &*self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concat_appends_in_place() {
+        let empty = LoxString::from("");
+        let a = empty.concat("a");
+        let ab = a.concat("b");
+
+        assert!(Rc::ptr_eq(&empty.buf, &ab.buf));
+        assert_eq!(&*empty.as_str(), "");
+        assert_eq!(&*a.as_str(), "a");
+        assert_eq!(&*ab.as_str(), "ab");
+    }
+
+    #[test]
+    fn concat_on_a_shared_prefix_copies() {
+        let a = LoxString::from("a");
+        let ab = a.concat("b");
+        let ac = a.concat("c");
+
+        assert!(!Rc::ptr_eq(&ab.buf, &ac.buf));
+        assert_eq!(&*a.as_str(), "a");
+        assert_eq!(&*ab.as_str(), "ab");
+        assert_eq!(&*ac.as_str(), "ac");
+    }
+
+    #[test]
+    fn tracked_buffers_count_against_the_heap() {
+        let heap = Heap::new();
+
+        let a = LoxString::new(String::with_capacity(16));
+        assert_eq!(heap.bytes(), 0);
+        a.track(&heap);
+        assert_eq!(heap.bytes(), 16);
+
+        // Appending in place grows the same buffer
+        let ab = a.concat(&"b".repeat(32));
+        assert!(heap.bytes() >= 32);
+
+        drop(a);
+        drop(ab);
+        assert_eq!(heap.bytes(), 0);
+    }
+
+    #[test]
+    fn concat_with_itself() {
+        let a = LoxString::from("ab");
+        let aa = a.concat(&a.as_str());
+
+        assert_eq!(&*aa.as_str(), "abab");
+        assert_eq!(&*a.as_str(), "ab");
+    }
+}
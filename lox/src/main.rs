@@ -1,64 +1,265 @@
-mod expr;
-mod interpreter;
-mod parser;
-mod scanner;
-mod token;
-
 use std::{
     env, fs,
     io::{self, Write},
+    net::TcpListener,
+    path::Path,
     process,
 };
 
-use expr::AstPrinter;
-use interpreter::Interpreter;
-use parser::Parser;
-use scanner::Scanner;
+use lox::{
+    cache::{self, Cache},
+    expr::{AstPrinter, Stmt},
+    interpreter::{self, ErrorPolicy, Interpreter},
+    parser::{self, LanguageLevel, Parser},
+    resolver::{self, Resolver},
+    scanner::{self, Scanner},
+    server::Server,
+    token::Token,
+};
+use rayon::prelude::*;
 use thiserror::Error;
-use token::Token;
 
 #[derive(Error, Debug)]
 enum Error {
     #[error("io error: {0}")]
     Io(#[from] io::Error),
-    #[error("scanner errors: {0:?}")]
-    ScannerError(#[from] scanner::Errors),
-    #[error("runtime error: {0:?}")]
-    RuntimeError(#[from] interpreter::IError),
+    // The errors with the lines they are on, see scanner::Errors::report.
+    #[error("scanner errors:\n{0}")]
+    ScannerError(String),
+    #[error("parser error: {0}")]
+    ParserError(#[from] parser::Error),
+    #[error("resolver error: {0}")]
+    ResolverError(#[from] resolver::Error),
+    #[error("{0}")]
+    RuntimeError(#[from] interpreter::RuntimeErrors),
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().skip(1).collect();
 
-    if args.len() > 2 {
-        println!("Usage: jlox [script]");
-        process::exit(64)
-    } else if args.len() == 2 {
-        if let Err(err) = run_file(&args[1]) {
+    // `--extended` turns on the language extensions, see LanguageLevel.
+    // `--no-cache` always scans files from scratch, see Cache.
+    // `--strict-globals` rejects assignments to undeclared globals, see Interpreter::with_strict_globals.
+    // `--warn-shadowing` reports variables hiding others when running files or in the REPL, see Resolver.
+    // `--memory-limit=N` stops scripts from holding more than N bytes, see Interpreter::with_memory_limit.
+    // `--deterministic` makes `clock()` and `random()` give the same results every run, see
+    // Interpreter::with_deterministic.
+    let mut level = LanguageLevel::Standard;
+    let mut cache = Some(Cache::new(cache::DEFAULT_DIR));
+    let mut strict_globals = false;
+    let mut warn_shadowing = false;
+    let mut memory_limit = None;
+    let mut deterministic = false;
+    args.retain(|arg| match arg.as_str() {
+        "--extended" => {
+            level = LanguageLevel::Extended;
+            false
+        }
+        "--no-cache" => {
+            cache = None;
+            false
+        }
+        "--strict-globals" => {
+            strict_globals = true;
+            false
+        }
+        "--warn-shadowing" => {
+            warn_shadowing = true;
+            false
+        }
+        "--deterministic" => {
+            deterministic = true;
+            false
+        }
+        _ => match arg.strip_prefix("--memory-limit=") {
+            Some(bytes) => {
+                let Ok(bytes) = bytes.parse::<usize>() else {
+                    eprintln!("Usage: lox --memory-limit=N");
+                    process::exit(64)
+                };
+                memory_limit = Some(bytes);
+                false
+            }
+            None => true,
+        },
+    });
+
+    if args.first().map(String::as_str) == Some("serve") {
+        if let Err(err) = serve(
+            &args[1..],
+            level,
+            strict_globals,
+            memory_limit,
+            deterministic,
+        ) {
+            eprintln!("{err}");
+            process::exit(74)
+        }
+    } else if !args.is_empty() {
+        if let Err(err) = run_files(
+            &args,
+            level,
+            strict_globals,
+            warn_shadowing,
+            memory_limit,
+            deterministic,
+            cache.as_ref(),
+        ) {
             match err {
-                Error::RuntimeError(_) => process::exit(70),
+                Error::RuntimeError(err) => {
+                    eprintln!("{err}");
+                    process::exit(70)
+                }
                 _ => process::exit(65),
             }
         }
     } else {
-        run_prompt()
+        run_prompt(
+            level,
+            strict_globals,
+            warn_shadowing,
+            memory_limit,
+            deterministic,
+        )
     }
 }
 
-fn run_file(path: &String) -> Result<(), Error> {
-    let bytes: Vec<u8> = fs::read(path)?;
+fn with_memory_limit(interpreter: Interpreter, memory_limit: Option<usize>) -> Interpreter {
+    match memory_limit {
+        Some(bytes) => interpreter.with_memory_limit(bytes),
+        None => interpreter,
+    }
+}
+
+// Every file is run in order in the same interpreter, so later files see the globals of earlier ones.
+fn run_files(
+    paths: &[String],
+    level: LanguageLevel,
+    strict_globals: bool,
+    warn_shadowing: bool,
+    memory_limit: Option<usize>,
+    deterministic: bool,
+    cache: Option<&Cache>,
+) -> Result<(), Error> {
+    // Scanning and parsing are pure, so the files are handled in parallel on the thread pool.
+    // `collect` keeps the input order, which keeps the diagnostics deterministic.
+    let programs: Vec<Result<Vec<Stmt>, Error>> = paths
+        .par_iter()
+        .map(|path| {
+            let bytes: Vec<u8> = fs::read(path)?;
+            parse(&bytes, level, cache)
+        })
+        .collect();
+
+    let mut parsed = vec![];
+    let mut first_error = None;
+    for (path, program) in paths.iter().zip(programs) {
+        match program {
+            Ok(stmts) => parsed.push(stmts),
+            Err(err) => {
+                eprintln!("{path}: {err}");
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+
+    if let Some(err) = first_error {
+        return Err(err);
+    }
+
+    // All files are resolved before any of them runs, like they are all parsed first.
+    let interpreter = Interpreter::new()
+        .with_error_policy(ErrorPolicy::Abort)
+        .with_language_level(level)
+        .with_strict_globals(strict_globals)
+        .with_deterministic(deterministic);
+    let interpreter = with_memory_limit(interpreter, memory_limit);
+    let resolver = Resolver::new(&interpreter).with_shadowing_warnings(warn_shadowing);
+    for (path, stmts) in paths.iter().zip(&parsed) {
+        let result = resolver.resolve(stmts);
+        for warning in resolver.take_warnings() {
+            eprintln!("{path}: warning: {warning}");
+        }
+        if let Err(err) = result {
+            eprintln!("{path}: {err}");
+            return Err(err.into());
+        }
+    }
+
+    for (path, stmts) in paths.iter().zip(parsed) {
+        println!("{}", AstPrinter::new().print(&stmts));
+        // Imports are relative to the file they are in.
+        interpreter.set_import_dir(Path::new(path).parent().unwrap_or(Path::new("")));
+        interpreter.interpret(&stmts)?;
+    }
 
-    run(&bytes)?;
     Ok(())
 }
 
-fn run_prompt() {
+// `lox serve --port N`, see Server for the protocol.
+fn serve(
+    args: &[String],
+    level: LanguageLevel,
+    strict_globals: bool,
+    memory_limit: Option<usize>,
+    deterministic: bool,
+) -> io::Result<()> {
+    let port = match args {
+        [flag, port] if flag == "--port" => port.parse::<u16>().ok(),
+        _ => None,
+    };
+
+    let Some(port) = port else {
+        eprintln!("Usage: lox serve --port N");
+        process::exit(64)
+    };
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("Listening on {}", listener.local_addr()?);
+
+    let server = Server::new(level)
+        .with_strict_globals(strict_globals)
+        .with_deterministic(deterministic);
+    match memory_limit {
+        Some(bytes) => server.with_memory_limit(bytes),
+        None => server,
+    }
+    .serve(listener)
+}
+
+fn run_prompt(
+    level: LanguageLevel,
+    strict_globals: bool,
+    warn_shadowing: bool,
+    memory_limit: Option<usize>,
+    deterministic: bool,
+) {
     let _ = io::stdout().flush();
 
-    let _ = inner_prompt_runner();
+    let _ = inner_prompt_runner(
+        level,
+        strict_globals,
+        warn_shadowing,
+        memory_limit,
+        deterministic,
+    );
 }
 
-fn inner_prompt_runner() -> Result<(), Error> {
+fn inner_prompt_runner(
+    level: LanguageLevel,
+    strict_globals: bool,
+    warn_shadowing: bool,
+    memory_limit: Option<usize>,
+    deterministic: bool,
+) -> Result<(), Error> {
+    // Shared by every line, so definitions stick around like they do in a file.
+    let interpreter = Interpreter::new()
+        .with_error_policy(ErrorPolicy::Continue)
+        .with_language_level(level)
+        .with_strict_globals(strict_globals)
+        .with_deterministic(deterministic);
+    let interpreter = with_memory_limit(interpreter, memory_limit);
+
     let mut buf = String::new();
     loop {
         print!("> ");
@@ -71,7 +272,7 @@ fn inner_prompt_runner() -> Result<(), Error> {
             break;
         }
 
-        if let Err(err) = run(buf.as_bytes()) {
+        if let Err(err) = run(buf.as_bytes(), &interpreter, level, warn_shadowing) {
             eprintln!("{err}")
         }
     }
@@ -79,24 +280,45 @@ fn inner_prompt_runner() -> Result<(), Error> {
     Ok(())
 }
 
-fn run(bytes: &[u8]) -> Result<(), Error> {
-    let mut scanner = Scanner::new(bytes);
-
-    let tokens: Vec<Token> = scanner.scan_tokens()?;
-    let mut parser = Parser::new(tokens);
+fn parse(bytes: &[u8], level: LanguageLevel, cache: Option<&Cache>) -> Result<Vec<Stmt>, Error> {
+    let tokens: Vec<Token> = match cache {
+        Some(cache) => cache.scan(bytes),
+        None => Scanner::new(bytes).scan_tokens(),
+    }
+    .map_err(|errors| scanner_error(bytes, errors))?;
+    let mut parser = Parser::new(tokens).with_language_level(level);
 
-    let stmts = parser.parse();
+    Ok(parser.parse()?)
+}
 
-    if let Err(err) = stmts.clone() {
-        eprintln!("{}", err);
-        return Ok(());
-    }
+fn run(
+    bytes: &[u8],
+    interpreter: &Interpreter,
+    level: LanguageLevel,
+    warn_shadowing: bool,
+) -> Result<(), Error> {
+    let mut scanner = Scanner::new(bytes);
 
-    println!("{}", AstPrinter::new().print(&stmts.clone().unwrap()));
+    let tokens: Vec<Token> = scanner
+        .scan_tokens()
+        .map_err(|errors| scanner_error(bytes, errors))?;
+    let stmts = Parser::new(tokens)
+        .with_language_level(level)
+        .parse_repl()?;
 
-    let interpreter = Interpreter::new();
+    println!("{}", AstPrinter::new().print(&stmts));
 
-    interpreter.interpret(&stmts.unwrap());
+    let resolver = Resolver::new(interpreter).with_shadowing_warnings(warn_shadowing);
+    let result = resolver.resolve(&stmts);
+    for warning in resolver.take_warnings() {
+        eprintln!("warning: {warning}");
+    }
+    result?;
+    interpreter.interpret(&stmts)?;
 
     Ok(())
 }
+
+fn scanner_error(bytes: &[u8], errors: scanner::Errors) -> Error {
+    Error::ScannerError(errors.report(&String::from_utf8_lossy(bytes)))
+}
@@ -0,0 +1,288 @@
+use std::{
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    interpreter::{
+        IError, Interpreter, LoxCallable, LoxMap, MapKey, Memoized, NativeError, NativeFunction,
+        Value,
+    },
+    token::Token,
+};
+
+// Defined as globals in every interpreter. Add new natives here.
+pub const NATIVES: &[NativeFunction] = &[
+    NativeFunction {
+        name: "clock",
+        arity: 0,
+        function: clock,
+    },
+    NativeFunction {
+        name: "delete",
+        arity: 2,
+        function: delete,
+    },
+    NativeFunction {
+        name: "next_key",
+        arity: 2,
+        function: next_key,
+    },
+    NativeFunction {
+        name: "random",
+        arity: 0,
+        function: random,
+    },
+    NativeFunction {
+        name: "memoize",
+        arity: 1,
+        function: memoize,
+    },
+    NativeFunction {
+        name: "name",
+        arity: 1,
+        function: name,
+    },
+    NativeFunction {
+        name: "arity",
+        arity: 1,
+        function: arity,
+    },
+    NativeFunction {
+        name: "setTimeout",
+        arity: 2,
+        function: set_timeout,
+    },
+    NativeFunction {
+        name: "setInterval",
+        arity: 2,
+        function: set_interval,
+    },
+    NativeFunction {
+        name: "clearTimer",
+        arity: 1,
+        function: clear_timer,
+    },
+    NativeFunction {
+        name: "runEventLoop",
+        arity: 0,
+        function: run_event_loop,
+    },
+];
+
+// Seconds since the Unix epoch, with sub-second precision. Meant for timing code.
+// Counts up by one per call instead when the interpreter is deterministic.
+fn clock(interpreter: &Interpreter, _: &Token, _: &[Value]) -> Result<Value, NativeError> {
+    if let Some(tick) = interpreter.tick() {
+        return Ok(Value::Number(tick as f64));
+    }
+
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Ok(Value::Number(elapsed.as_secs_f64()))
+}
+
+// A number in [0, 1). Not good enough for anything secret.
+fn random(interpreter: &Interpreter, _: &Token, _: &[Value]) -> Result<Value, NativeError> {
+    // xorshift64*, the state is never 0.
+    let state = interpreter.random_state();
+    let mut x = state.get();
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    state.set(x);
+
+    // The top 53 bits, as many as a float holds.
+    let bits = x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+    Ok(Value::Number(bits as f64 / (1u64 << 53) as f64))
+}
+
+// Where `random()` starts when the interpreter isn't deterministic.
+pub(crate) fn seed() -> u64 {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    (elapsed.as_nanos() as u64) | 1
+}
+
+// `delete(map, key)` removes the key, and returns the value it had or nil.
+fn delete(_: &Interpreter, _: &Token, arguments: &[Value]) -> Result<Value, NativeError> {
+    let map = map_argument(&arguments[0])?;
+    let key = key_argument(&arguments[1])?;
+
+    Ok(map.remove(&key).unwrap_or(Value::Nil))
+}
+
+// `next_key(map, key)` is the key after `key`, in the order they were added. Starts with the
+// first key for nil, and is nil after the last one:
+//
+//     var key = next_key(map, nil);
+//     loop { if (key == nil) { break; } print key; key = next_key(map, key); }
+fn next_key(_: &Interpreter, _: &Token, arguments: &[Value]) -> Result<Value, NativeError> {
+    let map = map_argument(&arguments[0])?;
+    let key = match &arguments[1] {
+        Value::Nil => None,
+        key => Some(key_argument(key)?),
+    };
+
+    if let Some(key) = &key {
+        if map.get(key).is_none() {
+            return Err(format!("Key {} is not in the map", arguments[1]).into());
+        }
+    }
+
+    Ok(map
+        .key_after(key.as_ref())
+        .map_or(Value::Nil, |key| (&key).into()))
+}
+
+// `memoize(f)` is a function that calls `f` the first time it gets some arguments, and gives
+// back the same result for them after that. Only for functions that return the same for the
+// same arguments and do nothing else:
+//
+//     fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+//     fib = memoize(fib);
+fn memoize(
+    interpreter: &Interpreter,
+    _: &Token,
+    arguments: &[Value],
+) -> Result<Value, NativeError> {
+    let function = function_argument(&arguments[0])?;
+
+    Ok(Value::Callable(LoxCallable::Memoized(
+        Memoized::new(function.clone(), interpreter.heap()).into(),
+    )))
+}
+
+// `name(f)` is the name a function or class was declared with.
+fn name(_: &Interpreter, _: &Token, arguments: &[Value]) -> Result<Value, NativeError> {
+    let function = function_argument(&arguments[0])?;
+
+    Ok(Value::String(function.name().into()))
+}
+
+// `arity(f)` is how many arguments a call to `f` needs. Parameters with a default and
+// `...rest` aren't counted, a class needs the arguments of its `init`.
+fn arity(_: &Interpreter, _: &Token, arguments: &[Value]) -> Result<Value, NativeError> {
+    let function = function_argument(&arguments[0])?;
+
+    Ok(Value::Int(*function.arity().start() as i64))
+}
+
+// `setTimeout(callback, ms)` has `runEventLoop()` call `callback` once, `ms` milliseconds
+// from now. Returns an id for clearTimer.
+fn set_timeout(
+    interpreter: &Interpreter,
+    paren: &Token,
+    arguments: &[Value],
+) -> Result<Value, NativeError> {
+    set_timer(interpreter, paren, arguments, false)
+}
+
+// `setInterval(callback, ms)` is like setTimeout, but calls `callback` every `ms`
+// milliseconds until it is cleared.
+fn set_interval(
+    interpreter: &Interpreter,
+    paren: &Token,
+    arguments: &[Value],
+) -> Result<Value, NativeError> {
+    set_timer(interpreter, paren, arguments, true)
+}
+
+fn set_timer(
+    interpreter: &Interpreter,
+    paren: &Token,
+    arguments: &[Value],
+    interval: bool,
+) -> Result<Value, NativeError> {
+    let callback = function_argument(&arguments[0])?;
+    let delay = match arguments[1].number() {
+        Some(ms) if ms >= 0.0 => ms as u64,
+        _ => {
+            return Err(format!(
+                "Expected a delay of 0 or more milliseconds but got {}",
+                arguments[1]
+            )
+            .into())
+        }
+    };
+
+    let id =
+        interpreter
+            .timers()
+            .borrow_mut()
+            .set(callback.clone(), delay, interval, paren.clone());
+    Ok(Value::Int(id))
+}
+
+// `clearTimer(id)` stops a timer from running (again). True if it would have.
+fn clear_timer(
+    interpreter: &Interpreter,
+    _: &Token,
+    arguments: &[Value],
+) -> Result<Value, NativeError> {
+    let Value::Int(id) = arguments[0] else {
+        return Err(format!("Expected a timer id but got {}", arguments[0]).into());
+    };
+
+    Ok(Value::Bool(interpreter.timers().borrow_mut().clear(id)))
+}
+
+// `runEventLoop()` runs the timers in the order they are due, waiting for each, until there
+// are none left. Callbacks can set more. An error in a callback stops the loop, the timers
+// that didn't run yet stay.
+fn run_event_loop(interpreter: &Interpreter, _: &Token, _: &[Value]) -> Result<Value, NativeError> {
+    let timers = interpreter.timers();
+    if timers.borrow().running {
+        return Err("The event loop is already running".to_owned().into());
+    }
+
+    timers.borrow_mut().running = true;
+    let result = run_timers(interpreter);
+    timers.borrow_mut().running = false;
+
+    result?;
+    Ok(Value::Nil)
+}
+
+fn run_timers(interpreter: &Interpreter) -> Result<(), IError> {
+    let timers = interpreter.timers();
+    // Timers are due on the loop's clock, which is turned into the real time to wait for.
+    let (started, start) = (Instant::now(), timers.borrow().now());
+
+    loop {
+        // Not borrowed while the callback runs, it can set timers.
+        let Some(timer) = timers.borrow_mut().take_due() else {
+            return Ok(());
+        };
+
+        if !interpreter.is_deterministic() {
+            let due = started + Duration::from_millis(timer.due - start);
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+        }
+
+        interpreter.invoke(&timer.callback, &timer.paren, vec![])?;
+    }
+}
+
+fn function_argument(value: &Value) -> Result<&LoxCallable, String> {
+    match value {
+        Value::Callable(function) => Ok(function),
+        _ => Err(format!("Expected a function but got {value}")),
+    }
+}
+
+fn map_argument(value: &Value) -> Result<&Rc<LoxMap>, String> {
+    match value {
+        Value::Map(map) => Ok(map),
+        _ => Err(format!("Expected a map but got {value}")),
+    }
+}
+
+fn key_argument(value: &Value) -> Result<MapKey, String> {
+    MapKey::from_value(value).ok_or_else(|| format!("{value} can't be a map key"))
+}
@@ -1,17 +1,69 @@
+use std::{path::Path, sync::Arc};
+
 use thiserror::Error;
 
 use crate::{
-    expr::{Expr, Stmt},
-    token::{Token, TokenType},
+    expr::{Expr, Function, FunctionKind, Resolution, Stmt},
+    scanner::Scanner,
+    token::{Literal, Token, TokenType},
 };
 
+// How deeply expressions and statements may nest before parsing is aborted.
+// Pathological inputs like `((((...))))` are rejected with an error instead of exhausting memory.
+pub const MAX_NESTING_DEPTH: usize = 1024;
+
+// Recursion grows the stack on the heap when less than RED_ZONE bytes are left,
+// so hitting MAX_NESTING_DEPTH never overflows the native stack.
+const RED_ZONE: usize = 64 * 1024;
+const STACK_GROWTH: usize = 1024 * 1024;
+
+// Which dialect of Lox is accepted. Standard is the language from the book,
+// Extended adds our own features on top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LanguageLevel {
+    #[default]
+    Standard,
+    // Blocks, `if` and `loop` are expressions.
+    Extended,
+}
+
+// Same limit as the book, which makes sure calls stay cheap.
+pub const MAX_ARGUMENTS: usize = 255;
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: i32,
+    depth: usize,
+    level: LanguageLevel,
+    // How many loops we are in, to reject `break` outside of one.
+    loop_depth: usize,
+    // The labels of the loops we are in, innermost last.
+    labels: Vec<String>,
+    // Same for functions and `return`.
+    function_depth: usize,
+    // The innermost class we are in, for `this` and `super`.
+    class: ClassKind,
+}
+
+// What `parameters` parsed, see Function for what they mean.
+#[derive(Default)]
+struct Parameters {
+    names: Vec<Token>,
+    defaults: Vec<Expr>,
+    rest: Option<Token>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum ClassKind {
+    #[default]
+    None,
+    Class,
+    // Only a subclass has a `super`.
+    Subclass,
 }
 
 #[derive(Error, Debug, Clone)]
-pub(crate) enum Error {
+pub enum Error {
     #[error("Out of bounds for index {0} in tokens list")]
     OutOfBounds(i32),
     #[error("Empty literal in token {0:?}")]
@@ -27,6 +79,50 @@ pub(crate) enum Error {
     },
     #[error("Unable to find boundary (keyword or semicolon) when synchronizing parser state")]
     SyncBoundaryNotFound,
+    #[error("Invalid assignment target in line {0}.")]
+    InvalidAssignmentTarget(i32),
+    #[error("Nesting too deep in line {line}: exceeded the limit of {limit}.")]
+    NestingTooDeep { line: i32, limit: usize },
+    #[error("Comparisons can't be chained in line {line}: '{expr}' compares the result of the first comparison, a boolean. Write it as '{suggestion}' instead.")]
+    ChainedComparison {
+        line: i32,
+        expr: String,
+        suggestion: String,
+    },
+    #[error("Can't have more than {MAX_ARGUMENTS} {what} in line {line}.")]
+    TooManyArguments { line: i32, what: &'static str },
+    #[error("'break' outside of a loop in line {0}.")]
+    BreakOutsideLoop(i32),
+    #[error("'continue' outside of a loop in line {0}.")]
+    ContinueOutsideLoop(i32),
+    #[error("Duplicate case '{case}' in line {line}.")]
+    DuplicateCase { line: i32, case: String },
+    #[error("No loop around line {line} is labeled '{label}'.")]
+    UnknownLabel { line: i32, label: String },
+    #[error("Only loops can have a label, in line {0}.")]
+    LabelWithoutLoop(i32),
+    #[error("'try' needs a 'catch' or 'finally' in line {0}.")]
+    TryWithoutHandler(i32),
+    #[error("Can't return from top-level code in line {0}.")]
+    ReturnOutsideFunction(i32),
+    #[error("Can't use 'this' outside of a class in line {0}.")]
+    ThisOutsideClass(i32),
+    #[error("Can't use 'super' outside of a class in line {0}.")]
+    SuperOutsideClass(i32),
+    #[error("Can't use 'super' in a class with no superclass in line {0}.")]
+    SuperWithoutSuperclass(i32),
+    #[error(
+        "Parameter '{name}' needs a default in line {line}, it comes after one that has a default."
+    )]
+    RequiredAfterDefault { line: i32, name: String },
+    #[error("A setter takes exactly one parameter, without a default, in line {0}.")]
+    SetterArity(i32),
+    #[error("A class can't inherit from itself in line {0}.")]
+    InheritsFromItself(i32),
+    #[error("{feature} in line {line} needs the extended language level.")]
+    ExtendedOnly { line: i32, feature: &'static str },
+    #[error("Can't import '{path}' in line {line}: the module is named after the file, so its name has to be a valid identifier.")]
+    InvalidModuleName { line: i32, path: String },
 }
 
 type PResult<T> = Result<T, Error>;
@@ -34,7 +130,21 @@ type PResult<T> = Result<T, Error>;
 // Recursive descent parser
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            depth: 0,
+            level: LanguageLevel::default(),
+            loop_depth: 0,
+            labels: vec![],
+            function_depth: 0,
+            class: ClassKind::None,
+        }
+    }
+
+    pub fn with_language_level(mut self, level: LanguageLevel) -> Self {
+        self.level = level;
+        self
     }
 
     // grammar: -> declaration* EOF
@@ -48,22 +158,365 @@ impl Parser {
         Ok(statements)
     }
 
-    // grammar: -> "var" IDENTIFIER ( "=" expression )? ";"
+    // Entry point for the REPL. A single expression without a trailing ';' is accepted and
+    // wrapped in a print statement, so typing `1 + 2` echoes the result.
+    // Anything else is parsed exactly like a file.
+    pub fn parse_repl(&mut self) -> PResult<Vec<Stmt>> {
+        let start = self.current;
+
+        if let Ok(expr) = self.expression() {
+            if self.is_at_end() {
+                return Ok(vec![Stmt::Print(expr)]);
+            }
+        }
+
+        self.current = start;
+        self.depth = 0;
+        self.loop_depth = 0;
+        self.labels.clear();
+        self.function_depth = 0;
+        self.class = ClassKind::None;
+        self.parse()
+    }
+
+    // grammar: -> classDecl | funDecl | varDecl | statement
     fn declaration(&mut self) -> PResult<Stmt> {
-        todo!();
+        if self.match_type(&TokenType::Class) {
+            return self.class_declaration();
+        }
+
+        if self.match_type(&TokenType::Fun) {
+            return Ok(Stmt::Function(self.function("function")?.into()));
+        }
+
+        if self.match_type(&TokenType::Var) {
+            return self.var_declaration();
+        }
+
+        if self.match_type(&TokenType::Const) {
+            return self.const_declaration();
+        }
+
+        if self.match_type(&TokenType::Import) {
+            return self.import_declaration();
+        }
+
+        self.statement()
     }
 
+    // grammar: -> "import" STRING ";"
+    fn import_declaration(&mut self) -> PResult<Stmt> {
+        let keyword = self.previous()?.to_owned();
+        let path = self.consume(
+            TokenType::String,
+            "Expect module path after 'import'.".to_owned(),
+        )?;
+        let Some(Literal::String(path)) = path.literal() else {
+            return Err(Error::EmptyLiteral(path));
+        };
+
+        // `import "lib/math.lox";` defines `math`.
+        let line = *keyword.line();
+        let stem = Path::new(&path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let is_identifier = matches!(
+            Scanner::new(stem.as_bytes()).scan_tokens().as_deref(),
+            Ok([name, _]) if *name.token_type() == TokenType::Identifier
+        );
+        if !is_identifier {
+            return Err(Error::InvalidModuleName { line, path });
+        }
+        let name = Token::new(TokenType::Identifier, stem.to_owned(), None, line);
+
+        self.consume(TokenType::Semicolon, "Expect ';' after import.".to_owned())?;
+        Ok(Stmt::Import(keyword, path, name))
+    }
+
+    // grammar: -> "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}"
+    fn class_declaration(&mut self) -> PResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.".to_owned())?;
+
+        let mut superclass = None;
+        if self.match_type(&TokenType::Less) {
+            let token =
+                self.consume(TokenType::Identifier, "Expect superclass name.".to_owned())?;
+            if token.lexeme() == name.lexeme() {
+                return Err(Error::InheritsFromItself(*token.line()));
+            }
+            superclass = Some(Expr::Variable(token, Resolution::default()));
+        }
+
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before class body.".to_owned(),
+        )?;
+
+        let kind = match superclass {
+            Some(_) => ClassKind::Subclass,
+            None => ClassKind::Class,
+        };
+        let enclosing = std::mem::replace(&mut self.class, kind);
+        let methods = self.class_body();
+        self.class = enclosing;
+
+        Ok(Stmt::Class(name, superclass, methods?))
+    }
+
+    fn class_body(&mut self) -> PResult<Vec<Arc<Function>>> {
+        let mut methods = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.method()?.into());
+        }
+
+        self.consume(
+            TokenType::RightBrace,
+            "Expect '}' after class body.".to_owned(),
+        )?;
+        Ok(methods)
+    }
+
+    // grammar: method -> "set" IDENTIFIER "(" IDENTIFIER ")" block
+    //                    | IDENTIFIER block
+    //                    | function
+    // A getter has no parameter list and runs when the property is read, a setter runs when
+    // it is assigned. `set` is only special here, so `set(a, b) { ... }` is still a method.
+    fn method(&mut self) -> PResult<Function> {
+        let is_setter = self.peek()?.lexeme() == "set"
+            && self.check(&TokenType::Identifier)
+            && self.check_next(&TokenType::Identifier);
+
+        if is_setter {
+            self.advance();
+            let name = self.consume(TokenType::Identifier, "Expect setter name.".to_owned())?;
+            let params = self.parameters("setter")?;
+            if params.names.len() != 1 || !params.defaults.is_empty() || params.rest.is_some() {
+                return Err(Error::SetterArity(*name.line()));
+            }
+            return self.function_body("setter", name, params, FunctionKind::Setter);
+        }
+
+        let name = self.consume(TokenType::Identifier, "Expect method name.".to_owned())?;
+        if self.check(&TokenType::LeftBrace) {
+            return self.function_body("getter", name, Parameters::default(), FunctionKind::Getter);
+        }
+
+        let params = self.parameters("method")?;
+        self.function_body("method", name, params, FunctionKind::Function)
+    }
+
+    // grammar: funDecl -> "fun" function
+    //   function -> IDENTIFIER "(" parameters? ")" block
+    //   parameters -> parameter ( "," parameter )* ( "," "..." IDENTIFIER )? | "..." IDENTIFIER
+    //   parameter -> IDENTIFIER ( "=" assignment )?
+    // `kind` is "function" or "method", for the error messages.
+    fn function(&mut self, kind: &str) -> PResult<Function> {
+        let name = self.consume(TokenType::Identifier, format!("Expect {kind} name."))?;
+        let params = self.parameters(kind)?;
+        self.function_body(kind, name, params, FunctionKind::Function)
+    }
+
+    // The parenthesized parameter list after the name of a function.
+    fn parameters(&mut self, kind: &str) -> PResult<Parameters> {
+        self.consume(
+            TokenType::LeftParen,
+            format!("Expect '(' after {kind} name."),
+        )?;
+
+        let mut params = vec![];
+        let mut defaults = vec![];
+        let mut rest = None;
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if self.match_type(&TokenType::DotDotDot) {
+                    rest = Some(self.consume(
+                        TokenType::Identifier,
+                        "Expect parameter name after '...'.".to_owned(),
+                    )?);
+                    break;
+                }
+
+                if params.len() >= MAX_ARGUMENTS {
+                    return Err(Error::TooManyArguments {
+                        line: *self.peek()?.line(),
+                        what: "parameters",
+                    });
+                }
+
+                let param =
+                    self.consume(TokenType::Identifier, "Expect parameter name.".to_owned())?;
+
+                // Arguments fill the parameters from the left, so the optional ones come last.
+                if self.match_type(&TokenType::Equal) {
+                    defaults.push(self.nested(|parser| parser.assignment())?);
+                } else if !defaults.is_empty() {
+                    return Err(Error::RequiredAfterDefault {
+                        line: *param.line(),
+                        name: param.lexeme().to_owned(),
+                    });
+                }
+                params.push(param);
+
+                if !self.match_type(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after parameters, a rest parameter has to be the last one.".to_owned(),
+        )?;
+
+        Ok(Parameters {
+            names: params,
+            defaults,
+            rest,
+        })
+    }
+
+    fn function_body(
+        &mut self,
+        kind: &str,
+        name: Token,
+        params: Parameters,
+        function_kind: FunctionKind,
+    ) -> PResult<Function> {
+        self.consume(
+            TokenType::LeftBrace,
+            format!("Expect '{{' before {kind} body."),
+        )?;
+
+        // A `break` in the body can't reach a loop around the declaration.
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let labels = std::mem::take(&mut self.labels);
+        self.function_depth += 1;
+        let body = if self.level == LanguageLevel::Extended {
+            // The body is read like a block expression, so it can end in an expression without a
+            // ';', which is what the function returns, see Interpreter::call_function.
+            self.block_expression().map(|block| match block {
+                Expr::Block(mut statements, value) => {
+                    statements.extend(value.map(|value| Stmt::Expression(*value)));
+                    statements
+                }
+                _ => unreachable!("block_expression always returns a block"),
+            })
+        } else {
+            self.nested(|parser| parser.block())
+        };
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        self.labels = labels;
+
+        Ok(Function {
+            name,
+            params: params.names,
+            defaults: params.defaults,
+            rest: params.rest,
+            body: body?,
+            kind: function_kind,
+        })
+    }
+
+    // grammar: -> "var" IDENTIFIER ( "=" expression )? ";"
     fn var_declaration(&mut self) -> PResult<Stmt> {
-        todo!()
+        let name = self.consume(TokenType::Identifier, "Expect variable name.".to_owned())?;
+
+        let mut initializer = None;
+        if self.match_type(&TokenType::Equal) {
+            initializer = Some(self.expression()?);
+        }
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.".to_owned(),
+        )?;
+        Ok(Stmt::Var(name, initializer))
+    }
+
+    // grammar: -> "const" IDENTIFIER "=" expression ";"
+    fn const_declaration(&mut self) -> PResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect constant name.".to_owned())?;
+        self.consume(
+            TokenType::Equal,
+            "Expect '=' after constant name, constants need a value.".to_owned(),
+        )?;
+        let value = self.expression()?;
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after constant declaration.".to_owned(),
+        )?;
+        Ok(Stmt::Const(name, value))
     }
 
-    // grammar: -> exprStmt | printStmt
+    // grammar: -> exprStmt | printStmt | ifStmt | whileStmt | forStmt | breakStmt | continueStmt
+    //            | returnStmt | tryStmt | throwStmt | switchStmt | block
     fn statement(&mut self) -> PResult<Stmt> {
-        if self.match_type(&TokenType::Print) {
-            return self.print_statement();
+        self.nested(|parser| {
+            if parser.check(&TokenType::If) && !parser.is_if_expression() {
+                parser.advance();
+                return parser.if_statement();
+            }
+
+            if parser.match_type(&TokenType::While) {
+                return parser.while_statement(None);
+            }
+
+            if parser.match_type(&TokenType::Print) {
+                return parser.print_statement();
+            }
+
+            if parser.match_type(&TokenType::Break) {
+                return parser.break_statement();
+            }
+
+            if parser.match_type(&TokenType::Continue) {
+                return parser.continue_statement();
+            }
+
+            if parser.match_type(&TokenType::Return) {
+                return parser.return_statement();
+            }
+
+            if parser.match_type(&TokenType::Try) {
+                return parser.try_statement();
+            }
+
+            if parser.match_type(&TokenType::Throw) {
+                return parser.throw_statement();
+            }
+
+            if parser.match_type(&TokenType::Switch) {
+                return parser.switch_statement();
+            }
+
+            if parser.match_type(&TokenType::For) {
+                return parser.for_statement(None);
+            }
+
+            if parser.check(&TokenType::Identifier) && parser.check_next(&TokenType::Colon) {
+                return parser.labeled_statement();
+            }
+
+            if parser.match_type(&TokenType::LeftBrace) {
+                return Ok(Stmt::Block(parser.block()?));
+            }
+
+            parser.express_statement()
+        })
+    }
+
+    // grammar: -> "{" declaration* "}"
+    fn block(&mut self) -> PResult<Vec<Stmt>> {
+        let mut statements: Vec<Stmt> = vec![];
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
         }
 
-        self.express_statement()
+        self.consume(TokenType::RightBrace, "Expect '}' after block.".to_owned())?;
+        Ok(statements)
     }
 
     // grammar: -> "print" expression ";"
@@ -73,48 +526,443 @@ impl Parser {
         Ok(Stmt::Print(value))
     }
 
-    // grammar: -> expression ";"
+    // grammar: -> "break" IDENTIFIER? expression? ";"
+    // The identifier is the label of the loop to break out of. It is only taken as a label
+    // when a loop around it has that label, so `break a;` still breaks with the value of `a`
+    // otherwise.
+    fn break_statement(&mut self) -> PResult<Stmt> {
+        let keyword = self.previous()?.to_owned();
+        if self.loop_depth == 0 {
+            return Err(Error::BreakOutsideLoop(*keyword.line()));
+        }
+
+        let mut label = None;
+        if self.check(&TokenType::Identifier) && self.is_label(self.peek()?.lexeme()) {
+            label = Some(self.advance().to_owned());
+        }
+
+        let mut value = None;
+        if !self.check(&TokenType::Semicolon) {
+            value = Some(self.expression()?);
+        }
+
+        self.consume(TokenType::Semicolon, "Expect ';' after break.".to_owned())?;
+        Ok(Stmt::Break(keyword, label, value))
+    }
+
+    // grammar: -> "continue" IDENTIFIER? ";"
+    fn continue_statement(&mut self) -> PResult<Stmt> {
+        let keyword = self.previous()?.to_owned();
+        if self.loop_depth == 0 {
+            return Err(Error::ContinueOutsideLoop(*keyword.line()));
+        }
+
+        let mut label = None;
+        if self.match_type(&TokenType::Identifier) {
+            let name = self.previous()?.to_owned();
+            if !self.is_label(name.lexeme()) {
+                return Err(Error::UnknownLabel {
+                    line: *name.line(),
+                    label: name.lexeme().to_owned(),
+                });
+            }
+            label = Some(name);
+        }
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after continue.".to_owned(),
+        )?;
+        Ok(Stmt::Continue(keyword, label))
+    }
+
+    // grammar: -> IDENTIFIER ":" ( whileStmt | forStmt | loopExpr )
+    // `break label;` and `continue label;` in the loop, also in loops nested in it, refer to it.
+    fn labeled_statement(&mut self) -> PResult<Stmt> {
+        let label = self.advance().to_owned();
+        self.advance();
+
+        self.labels.push(label.lexeme().to_owned());
+        let stmt = if self.match_type(&TokenType::While) {
+            self.while_statement(Some(label))
+        } else if self.match_type(&TokenType::For) {
+            self.for_statement(Some(label))
+        } else if self.match_type(&TokenType::Loop) {
+            self.require_extended("'loop' expression")
+                .and_then(|()| self.loop_expression(Some(label)))
+                .map(Stmt::Expression)
+        } else {
+            Err(Error::LabelWithoutLoop(*label.line()))
+        };
+        self.labels.pop();
+
+        stmt
+    }
+
+    // grammar: -> "return" expression? ";"
+    fn return_statement(&mut self) -> PResult<Stmt> {
+        let keyword = self.previous()?.to_owned();
+        if self.function_depth == 0 {
+            return Err(Error::ReturnOutsideFunction(*keyword.line()));
+        }
+
+        let mut value = None;
+        if !self.check(&TokenType::Semicolon) {
+            value = Some(self.expression()?);
+        }
+
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after return value.".to_owned(),
+        )?;
+        Ok(Stmt::Return(keyword, value))
+    }
+
+    // grammar: -> "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )?
+    fn try_statement(&mut self) -> PResult<Stmt> {
+        let keyword = self.previous()?.to_owned();
+
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.".to_owned())?;
+        let body = self.block()?;
+
+        let mut catch = None;
+        if self.match_type(&TokenType::Catch) {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.".to_owned())?;
+            let name = self.consume(
+                TokenType::Identifier,
+                "Expect exception variable name.".to_owned(),
+            )?;
+            self.consume(
+                TokenType::RightParen,
+                "Expect ')' after exception variable.".to_owned(),
+            )?;
+            self.consume(TokenType::LeftBrace, "Expect '{' after catch.".to_owned())?;
+            catch = Some((name, self.block()?));
+        }
+
+        let mut finally = None;
+        if self.match_type(&TokenType::Finally) {
+            self.consume(
+                TokenType::LeftBrace,
+                "Expect '{' after 'finally'.".to_owned(),
+            )?;
+            finally = Some(self.block()?);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(Error::TryWithoutHandler(*keyword.line()));
+        }
+
+        Ok(Stmt::Try(body, catch, finally))
+    }
+
+    // grammar: -> "switch" "(" expression ")" "{" ( "case" literal ":" declaration* )*
+    //             ( "default" ":" declaration* )? "}"
+    fn switch_statement(&mut self) -> PResult<Stmt> {
+        self.consume(
+            TokenType::LeftParen,
+            "Expect '(' after 'switch'.".to_owned(),
+        )?;
+        let value = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after switch value.".to_owned(),
+        )?;
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before switch cases.".to_owned(),
+        )?;
+
+        let mut cases: Vec<(Literal, Vec<Stmt>)> = vec![];
+        let mut seen = vec![];
+        while self.match_type(&TokenType::Case) {
+            let token = self.peek()?.to_owned();
+            let literal = self.case_literal()?;
+
+            // Numbers compare by value, so `1` and `1.0` are the same case.
+            let case = (*token.token_type(), literal.to_string());
+            if seen.contains(&case) {
+                return Err(Error::DuplicateCase {
+                    line: *token.line(),
+                    case: token.lexeme().to_owned(),
+                });
+            }
+            seen.push(case);
+
+            self.consume(TokenType::Colon, "Expect ':' after case.".to_owned())?;
+            cases.push((literal, self.case_body()?));
+        }
+
+        let mut default = None;
+        if self.match_type(&TokenType::Default) {
+            self.consume(TokenType::Colon, "Expect ':' after 'default'.".to_owned())?;
+            default = Some(self.case_body()?);
+        }
+
+        self.consume(
+            TokenType::RightBrace,
+            "Expect '}' after switch cases, the default case has to be the last one.".to_owned(),
+        )?;
+        Ok(Stmt::Switch(value, cases, default))
+    }
+
+    // grammar: -> "if" "(" expression ")" statement ( "else" statement )?
+    // An `else` belongs to the nearest `if`.
+    fn if_statement(&mut self) -> PResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.".to_owned())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after if condition.".to_owned(),
+        )?;
+
+        let then_branch = self.statement()?;
+        let mut else_branch = None;
+        if self.match_type(&TokenType::Else) {
+            else_branch = Some(self.statement()?.into());
+        }
+
+        Ok(Stmt::If(condition, then_branch.into(), else_branch))
+    }
+
+    // grammar: -> "while" "(" expression ")" statement
+    fn while_statement(&mut self, label: Option<Token>) -> PResult<Stmt> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.".to_owned())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after condition.".to_owned(),
+        )?;
+
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+
+        Ok(Stmt::While(label, condition, body?.into(), None))
+    }
+
+    // grammar: -> "for" "(" ( IDENTIFIER "in" expression
+    //                       | ( varDecl | exprStmt | ";" ) expression? ";" expression? ) ")"
+    //             statement
+    // The second form is a while loop, in a block with the initializer when there is one.
+    fn for_statement(&mut self, label: Option<Token>) -> PResult<Stmt> {
+        let keyword = self.previous()?.to_owned();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.".to_owned())?;
+
+        // `in` is only special here, it can still be used as a name.
+        let is_in = self.check(&TokenType::Identifier)
+            && self
+                .tokens
+                .get(self.current as usize + 1)
+                .is_some_and(|next| {
+                    next.token_type() == &TokenType::Identifier && next.lexeme() == "in"
+                });
+        if is_in {
+            return self.for_in_statement(keyword, label);
+        }
+
+        let initializer = if self.match_type(&TokenType::Semicolon) {
+            None
+        } else if self.match_type(&TokenType::Var) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.express_statement()?)
+        };
+
+        let mut condition = Expr::Literal(Literal::True);
+        if !self.check(&TokenType::Semicolon) {
+            condition = self.expression()?;
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after loop condition.".to_owned(),
+        )?;
+
+        let mut increment = None;
+        if !self.check(&TokenType::RightParen) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for clauses.".to_owned(),
+        )?;
+
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+
+        let mut stmt = Stmt::While(label, condition, body?.into(), increment);
+        if let Some(initializer) = initializer {
+            stmt = Stmt::Block(vec![initializer, stmt]);
+        }
+        Ok(stmt)
+    }
+
+    // The rest of `for (name in collection) body`.
+    fn for_in_statement(&mut self, keyword: Token, label: Option<Token>) -> PResult<Stmt> {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.".to_owned())?;
+        // The `in`, for_statement checked it is there.
+        self.advance();
+
+        let collection = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after for loop collection.".to_owned(),
+        )?;
+
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+
+        Ok(Stmt::ForIn(keyword, label, name, collection, body?.into()))
+    }
+
+    // Cases can only be compared against literals.
+    fn case_literal(&mut self) -> PResult<Literal> {
+        if self.match_types(vec![TokenType::False, TokenType::True, TokenType::Nil]) {
+            return Ok(self.previous()?.token_type().into());
+        }
+
+        if self.match_types(vec![TokenType::Number, TokenType::String]) {
+            let token = self.previous()?;
+            return token.literal().ok_or(Error::EmptyLiteral(token.to_owned()));
+        }
+
+        Err(Error::UnexpectedToken(
+            self.peek()?.to_owned(),
+            self.current,
+        ))
+    }
+
+    // The statements up to the next case, like a block without braces.
+    fn case_body(&mut self) -> PResult<Vec<Stmt>> {
+        let mut statements = vec![];
+        while !self.check(&TokenType::Case)
+            && !self.check(&TokenType::Default)
+            && !self.check(&TokenType::RightBrace)
+            && !self.is_at_end()
+        {
+            statements.push(self.declaration()?);
+        }
+        Ok(statements)
+    }
+
+    // grammar: -> "throw" expression ";"
+    fn throw_statement(&mut self) -> PResult<Stmt> {
+        let keyword = self.previous()?.to_owned();
+        let value = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after thrown value.".to_owned(),
+        )?;
+        Ok(Stmt::Throw(keyword, value))
+    }
+
+    // grammar: -> expression ";" | blockLikeExpr ";"?
     fn express_statement(&mut self) -> PResult<Stmt> {
         let value = self.expression()?;
-        self.consume(TokenType::Semicolon, "Expect ';' after value.".to_owned())?;
+        // `if (a) { ... }` reads like a statement, so don't make people write a ';' after it.
+        if !value.is_block_like() || self.check(&TokenType::Semicolon) {
+            self.consume(TokenType::Semicolon, "Expect ';' after value.".to_owned())?;
+        }
         Ok(Stmt::Expression(value))
     }
 
     // grammar: -> comma
     fn expression(&mut self) -> PResult<Expr> {
-        self.comma()
+        self.nested(|parser| parser.comma())
     }
 
-    // grammar: -> ternary ( ( "," ) ternary )*
+    // grammar: -> assignment ( ( "," ) assignment )*
+    // Evaluates both sides and gives the right one, like in C.
     fn comma(&mut self) -> PResult<Expr> {
-        let mut expr = self.ternary()?;
+        let mut expr = self.assignment()?;
 
         while self.match_type(&TokenType::Comma) {
             let comma_operator = self.previous()?.to_owned();
-            let right = self.ternary()?;
+            let right = self.assignment()?;
             expr = Expr::Binary(expr.into(), comma_operator, right.into())
         }
 
         return Ok(expr);
     }
 
-    // grammar: -> equality ( ( "?" ) equality ( ":" ) equality )*
+    // grammar: -> ( call "." )? IDENTIFIER "=" assignment | ternary
+    fn assignment(&mut self) -> PResult<Expr> {
+        let expr = self.ternary()?;
+
+        if self.match_type(&TokenType::Equal) {
+            let equals = self.previous()?.to_owned();
+            // Assignment is right-associative, so recurse instead of looping.
+            let value = self.nested(|parser| parser.assignment())?;
+
+            if let Expr::Variable(name, _) = expr {
+                return Ok(Expr::Assign(name, value.into(), Resolution::default()));
+            }
+
+            if let Expr::Get(object, name) = expr {
+                return Ok(Expr::Set(object, name, value.into()));
+            }
+
+            if let Expr::Index(object, bracket, key) = expr {
+                return Ok(Expr::SetIndex(object, bracket, key, value.into()));
+            }
+
+            return Err(Error::InvalidAssignmentTarget(*equals.line()));
+        }
+
+        return Ok(expr);
+    }
+
+    // grammar: -> logic_or ( "?" expression ":" ternary )?
+    // Groups to the right like in C, so `a ? b : c ? d : e` is `a ? b : (c ? d : e)`. The
+    // middle is delimited by the ':', so it can be any expression.
     fn ternary(&mut self) -> PResult<Expr> {
-        let mut expr = self.equality()?;
+        let expr = self.or()?;
 
-        while self.match_types(vec![TokenType::QuestionMark]) {
-            let inner_true = self.equality()?;
+        if self.match_type(&TokenType::QuestionMark) {
+            let inner_true = self.nested(|parser| parser.expression())?;
 
             self.consume(TokenType::Colon, "Expect ':' after expression".to_owned())?;
 
-            let inner_false = self.equality()?;
+            let inner_false = self.nested(|parser| parser.ternary())?;
 
-            expr = Expr::Condition(expr.into(), inner_true.into(), inner_false.into())
+            return Ok(Expr::Condition(
+                expr.into(),
+                inner_true.into(),
+                inner_false.into(),
+            ));
         }
 
         return Ok(expr);
     }
 
+    // grammar: -> logic_and ( "or" logic_and )* ;
+    fn or(&mut self) -> PResult<Expr> {
+        let mut expr = self.and()?;
+
+        while self.match_type(&TokenType::Or) {
+            let operator = self.previous()?.to_owned();
+            let right = self.and()?;
+            expr = Expr::Logical(expr.into(), operator, right.into());
+        }
+
+        Ok(expr)
+    }
+
+    // grammar: -> equality ( "and" equality )* ;
+    fn and(&mut self) -> PResult<Expr> {
+        let mut expr = self.equality()?;
+
+        while self.match_type(&TokenType::And) {
+            let operator = self.previous()?.to_owned();
+            let right = self.equality()?;
+            expr = Expr::Logical(expr.into(), operator, right.into());
+        }
+
+        Ok(expr)
+    }
+
     // grammar: -> comparison ( ( "!=" | "==") comparison )* ;
     fn equality(&mut self) -> PResult<Expr> {
         let mut _expr = self.comparison()?;
@@ -130,25 +978,70 @@ impl Parser {
         return Ok(_expr);
     }
 
-    // grammar: -> term ( ( ">" | ">=" | "<" | "<=") term )* ;
+    // grammar: -> term ( ( ">" | ">=" | "<" | "<=") term | "is" IDENTIFIER )* ;
     fn comparison(&mut self) -> PResult<Expr> {
+        let start = self.current;
         let mut _expr = self.term()?;
+        // Where the right operand of the previous comparison starts, if the last thing parsed
+        // was a comparison.
+        let mut middle_start = None;
 
-        while self.match_types(vec![
-            TokenType::LessEqual,
-            TokenType::Less,
-            TokenType::Greater,
-            TokenType::GreaterEqual,
-        ]) {
-            let operator = self.previous()?.to_owned();
-            let right = self.term()?;
+        loop {
+            if self.match_types(vec![
+                TokenType::LessEqual,
+                TokenType::Less,
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+            ]) {
+                let operator = self.previous()?.to_owned();
+                let right_start = self.current;
+                let right = self.term()?;
 
-            _expr = Expr::Binary(_expr.into(), operator, right.into());
+                // `a < b < c` would parse as `(a < b) < c` and compare a bool with a number.
+                if let Some(middle_start) = middle_start {
+                    return Err(self.chained_comparison(start, middle_start, right_start - 1));
+                }
+                middle_start = Some(right_start);
+
+                _expr = Expr::Binary(_expr.into(), operator, right.into());
+            } else if self.match_type(&TokenType::Is) {
+                let type_name = self.consume(
+                    TokenType::Identifier,
+                    "Expect type name after 'is'.".to_owned(),
+                )?;
+                middle_start = None;
+
+                _expr = Expr::Is(_expr.into(), type_name, Resolution::default());
+            } else {
+                break;
+            }
         }
 
         return Ok(_expr);
     }
 
+    // Builds the error for `left < middle < right` from the tokens, so the message shows what
+    // was written. `second` is the index of the second comparison operator.
+    fn chained_comparison(&self, start: i32, middle_start: i32, second: i32) -> Error {
+        let source = |from: i32, to: i32| {
+            self.tokens[from as usize..to as usize]
+                .iter()
+                .map(|token| token.lexeme())
+                .collect::<Vec<&str>>()
+                .join(" ")
+        };
+
+        let first = source(start, middle_start);
+        let middle = source(middle_start, second);
+        let rest = source(second, self.current);
+
+        Error::ChainedComparison {
+            line: *self.tokens[second as usize].line(),
+            expr: format!("{first} {middle} {rest}"),
+            suggestion: format!("{first} {middle} and {middle} {rest}"),
+        }
+    }
+
     // grammar: -> factor ( ( "-" | "+") factor )* ;
     fn term(&mut self) -> PResult<Expr> {
         let mut _expr = self.factor()?;
@@ -163,11 +1056,11 @@ impl Parser {
         return Ok(_expr);
     }
 
-    // grammar: -> unary ( ( "/" | "*") unary )* ;
+    // grammar: -> unary ( ( "/" | "*" | "%" ) unary )* ;
     fn factor(&mut self) -> PResult<Expr> {
         let mut _expr = self.unary()?;
 
-        while self.match_types(vec![TokenType::Slash, TokenType::Star]) {
+        while self.match_types(vec![TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let operator = self.previous()?.to_owned();
 
             let right = self.unary()?;
@@ -177,14 +1070,90 @@ impl Parser {
         return Ok(_expr);
     }
 
-    // grammar: -> ("!" | "-") unary | primary ;
+    // grammar: -> ("!" | "-") unary | power ;
     fn unary(&mut self) -> PResult<Expr> {
-        if self.match_types(vec![TokenType::Bang, TokenType::Minus]) {}
+        if self.match_types(vec![TokenType::Bang, TokenType::Minus]) {
+            let operator = self.previous()?.to_owned();
+            let right = self.nested(|parser| parser.unary())?;
+            return Ok(Expr::Unary(operator, right.into()));
+        }
 
-        return self.primary();
+        return self.power();
     }
 
-    // grammar: -> NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")" | IDENTIFIER
+    // grammar: -> call ( "**" unary )? ;
+    // Binds tighter than unary on its left, and groups to the right: `-2 ** 2` is -4 and
+    // `2 ** 3 ** 2` is 2 ** 9, like in math.
+    fn power(&mut self) -> PResult<Expr> {
+        let expr = self.call()?;
+
+        if self.match_type(&TokenType::StarStar) {
+            let operator = self.previous()?.to_owned();
+            let right = self.nested(|parser| parser.unary())?;
+            return Ok(Expr::Binary(expr.into(), operator, right.into()));
+        }
+
+        Ok(expr)
+    }
+
+    // grammar: -> primary ( "(" arguments? ")" | "." IDENTIFIER | "[" expression "]" )*
+    fn call(&mut self) -> PResult<Expr> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_type(&TokenType::LeftParen) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_type(&TokenType::Dot) {
+                let name = self.consume(
+                    TokenType::Identifier,
+                    "Expect property name after '.'.".to_owned(),
+                )?;
+                expr = Expr::Get(expr.into(), name);
+            } else if self.match_type(&TokenType::LeftBracket) {
+                let key = self.nested(|parser| parser.expression())?;
+                let bracket =
+                    self.consume(TokenType::RightBracket, "Expect ']' after key.".to_owned())?;
+                expr = Expr::Index(expr.into(), bracket, key.into());
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    // grammar: arguments -> assignment ( "," assignment )*
+    // Arguments skip the comma operator, or `f(a, b)` would be a call with the single argument `b`.
+    fn finish_call(&mut self, callee: Expr) -> PResult<Expr> {
+        let mut arguments = vec![];
+
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    return Err(Error::TooManyArguments {
+                        line: *self.peek()?.line(),
+                        what: "arguments",
+                    });
+                }
+
+                arguments.push(self.nested(|parser| parser.assignment())?);
+
+                if !self.match_type(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(
+            TokenType::RightParen,
+            "Expect ')' after arguments.".to_owned(),
+        )?;
+
+        Ok(Expr::Call(callee.into(), paren, arguments))
+    }
+
+    // grammar: -> NUMBER | STRING | "true" | "false" | "nil" | "this" | "(" expression ")"
+    //            | "super" "." IDENTIFIER | IDENTIFIER | mapExpr | ifExpr | blockExpr | loopExpr
     fn primary(&mut self) -> PResult<Expr> {
         if self.match_types(vec![TokenType::False, TokenType::True, TokenType::Nil]) {
             let literal = self.previous()?;
@@ -200,6 +1169,37 @@ impl Parser {
             return Ok(Expr::Literal(literal));
         }
 
+        if self.match_type(&TokenType::This) {
+            let keyword = self.previous()?.to_owned();
+            if self.class == ClassKind::None {
+                return Err(Error::ThisOutsideClass(*keyword.line()));
+            }
+            return Ok(Expr::This(keyword, Resolution::default()));
+        }
+
+        if self.match_type(&TokenType::Super) {
+            let keyword = self.previous()?.to_owned();
+            match self.class {
+                ClassKind::None => return Err(Error::SuperOutsideClass(*keyword.line())),
+                ClassKind::Class => return Err(Error::SuperWithoutSuperclass(*keyword.line())),
+                ClassKind::Subclass => {}
+            }
+
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.".to_owned())?;
+            let method = self.consume(
+                TokenType::Identifier,
+                "Expect superclass method name.".to_owned(),
+            )?;
+            return Ok(Expr::Super(keyword, method, Resolution::default()));
+        }
+
+        if self.match_type(&TokenType::Identifier) {
+            return Ok(Expr::Variable(
+                self.previous()?.to_owned(),
+                Resolution::default(),
+            ));
+        }
+
         if self.match_types(vec![TokenType::LeftParen]) {
             let expr = self.expression()?;
             self.consume(
@@ -209,12 +1209,249 @@ impl Parser {
             return Ok(Expr::Grouping(expr.into()));
         }
 
+        // A '{' at the start of a statement never gets here, statement() makes it a block
+        // statement. So `{ 1 }` is only a value where an expression is expected,
+        // e.g. `var a = { 1 };` or `({ 1 });`.
+        if self.match_type(&TokenType::If) {
+            self.require_extended("'if' expression")?;
+            return self.if_expression();
+        }
+
+        if self.match_type(&TokenType::LeftBrace) {
+            if self.is_map_literal() {
+                return self.map_literal();
+            }
+
+            self.require_extended("Block expression")?;
+            return self.block_expression();
+        }
+
+        if self.match_type(&TokenType::Loop) {
+            self.require_extended("'loop' expression")?;
+            return self.loop_expression(None);
+        }
+
         return Err(Error::UnexpectedToken(
             self.peek()?.to_owned(),
             self.current,
         ));
     }
 
+    // grammar: -> "if" "(" expression ")" blockExpr ( "else" ( ifExpr | blockExpr ) )?
+    fn if_expression(&mut self) -> PResult<Expr> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.".to_owned())?;
+        let condition = self.expression()?;
+        self.consume(
+            TokenType::RightParen,
+            "Expect ')' after if condition.".to_owned(),
+        )?;
+
+        // Branches have to be blocks, which keeps `if (a) if (b) 1 else 2` from being ambiguous.
+        self.consume(
+            TokenType::LeftBrace,
+            "Expect '{' before if branch.".to_owned(),
+        )?;
+        let then_branch = self.block_expression()?;
+
+        let mut else_branch = None;
+        if self.match_type(&TokenType::Else) {
+            let branch = if self.match_type(&TokenType::If) {
+                self.nested(|parser| parser.if_expression())?
+            } else {
+                self.consume(
+                    TokenType::LeftBrace,
+                    "Expect '{' or 'if' after 'else'.".to_owned(),
+                )?;
+                self.block_expression()?
+            };
+            else_branch = Some(branch.into());
+        }
+
+        Ok(Expr::If(condition.into(), then_branch.into(), else_branch))
+    }
+
+    // grammar: -> "loop" blockExpr
+    fn loop_expression(&mut self, label: Option<Token>) -> PResult<Expr> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'loop'.".to_owned())?;
+
+        self.loop_depth += 1;
+        let body = self.block_expression();
+        self.loop_depth -= 1;
+
+        Ok(Expr::Loop(label, body?.into()))
+    }
+
+    // grammar: -> "{" ( declaration | expression ";" | blockLikeExpr )* expression? "}"
+    fn block_expression(&mut self) -> PResult<Expr> {
+        self.nested(|parser| {
+            let mut statements: Vec<Stmt> = vec![];
+            let mut value = None;
+
+            while !parser.check(&TokenType::RightBrace) && !parser.is_at_end() {
+                if parser.check(&TokenType::Var)
+                    || parser.check(&TokenType::Const)
+                    || parser.check(&TokenType::Class)
+                    || parser.check(&TokenType::Fun)
+                    || parser.check(&TokenType::Import)
+                    || parser.check(&TokenType::Print)
+                    || parser.check(&TokenType::Break)
+                    || parser.check(&TokenType::Continue)
+                    || parser.check(&TokenType::Try)
+                    || parser.check(&TokenType::Throw)
+                    || parser.check(&TokenType::Switch)
+                    || parser.check(&TokenType::For)
+                    || parser.check(&TokenType::While)
+                    || (parser.check(&TokenType::If) && !parser.is_if_expression())
+                    || parser.check(&TokenType::Return)
+                    || (parser.check(&TokenType::Identifier)
+                        && parser.check_next(&TokenType::Colon))
+                {
+                    statements.push(parser.declaration()?);
+                    continue;
+                }
+
+                // Inside a block expression a nested '{' is a block expression as well.
+                let expr = parser.expression()?;
+
+                if parser.check(&TokenType::RightBrace) {
+                    value = Some(expr.into());
+                    break;
+                }
+
+                if !expr.is_block_like() || parser.check(&TokenType::Semicolon) {
+                    parser.consume(TokenType::Semicolon, "Expect ';' after value.".to_owned())?;
+                }
+                statements.push(Stmt::Expression(expr));
+            }
+
+            parser.consume(TokenType::RightBrace, "Expect '}' after block.".to_owned())?;
+            Ok(Expr::Block(statements, value))
+        })
+    }
+
+    // grammar: mapExpr -> "{" ( entry ( "," entry )* ","? )? "}"
+    //   entry -> assignment ":" assignment
+    fn map_literal(&mut self) -> PResult<Expr> {
+        let brace = self.previous()?.to_owned();
+
+        self.nested(|parser| {
+            let mut entries = vec![];
+            while !parser.check(&TokenType::RightBrace) && !parser.is_at_end() {
+                let key = parser.assignment()?;
+                parser.consume(TokenType::Colon, "Expect ':' after map key.".to_owned())?;
+                let value = parser.assignment()?;
+                entries.push((key, value));
+
+                if !parser.match_type(&TokenType::Comma) {
+                    break;
+                }
+            }
+
+            parser.consume(
+                TokenType::RightBrace,
+                "Expect '}' after map entries.".to_owned(),
+            )?;
+            Ok(Expr::Map(brace, entries))
+        })
+    }
+
+    // Whether the '{' just matched starts a map rather than a block expression: `{}`, or a ':'
+    // on the level of the braces before the first ';' or '}' there. Colons closing a `?:` don't
+    // count, `{ a ? b : c }` is a block. Neither does the one after a label, `{ a: loop {} }` is a
+    // block with a labeled loop in it.
+    fn is_map_literal(&self) -> bool {
+        let mut depth = 0;
+        let mut ternaries = 0;
+
+        let tokens = &self.tokens[self.current as usize..];
+        if let [label, colon, keyword, ..] = tokens {
+            if label.token_type() == &TokenType::Identifier
+                && colon.token_type() == &TokenType::Colon
+                && matches!(keyword.token_type(), TokenType::Loop | TokenType::For)
+            {
+                return false;
+            }
+        }
+
+        for (i, token) in tokens.iter().enumerate() {
+            match token.token_type() {
+                TokenType::RightBrace if depth == 0 => return i == 0,
+                TokenType::Semicolon | TokenType::Eof if depth == 0 => return false,
+                TokenType::QuestionMark if depth == 0 => ternaries += 1,
+                TokenType::Colon if depth == 0 => {
+                    if ternaries == 0 {
+                        return true;
+                    }
+                    ternaries -= 1;
+                }
+                TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                    if depth == 0 {
+                        return false;
+                    }
+                    depth -= 1
+                }
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    // Whether the `if` at the current token is an expression: at the extended level, when its
+    // branch is a block. Those can give a value, other ones are statements.
+    fn is_if_expression(&self) -> bool {
+        if self.level != LanguageLevel::Extended {
+            return false;
+        }
+
+        let mut depth = 0;
+        let tokens = &self.tokens[self.current as usize + 1..];
+        for (i, token) in tokens.iter().enumerate() {
+            match token.token_type() {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen if depth == 1 => {
+                    return tokens
+                        .get(i + 1)
+                        .is_some_and(|next| next.token_type() == &TokenType::LeftBrace);
+                }
+                TokenType::RightParen => depth -= 1,
+                TokenType::Eof => return false,
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    fn require_extended(&self, feature: &'static str) -> PResult<()> {
+        if self.level == LanguageLevel::Extended {
+            return Ok(());
+        }
+
+        Err(Error::ExtendedOnly {
+            line: *self.previous()?.line(),
+            feature,
+        })
+    }
+
+    // Every production that can recurse into itself goes through here, which keeps
+    // track of how deep we are and makes sure the native stack never runs out.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> PResult<T>) -> PResult<T> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(Error::NestingTooDeep {
+                line: *self.peek()?.line(),
+                limit: MAX_NESTING_DEPTH,
+            });
+        }
+
+        self.depth += 1;
+        let result = stacker::maybe_grow(RED_ZONE, STACK_GROWTH, || f(self));
+        self.depth -= 1;
+
+        result
+    }
+
     // NOTE: If token type is matched, the token is consumed with the call to advance()
     fn match_types(&mut self, types: Vec<TokenType>) -> bool {
         for token_type in types {
@@ -235,6 +1472,18 @@ impl Parser {
         false
     }
 
+    // Whether a loop we are in has the label `name`.
+    fn is_label(&self, name: &str) -> bool {
+        self.labels.iter().any(|label| label == name)
+    }
+
+    // Like `check`, but for the token after the current one.
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens
+            .get(self.current as usize + 1)
+            .is_some_and(|next| next.token_type() == token_type)
+    }
+
     fn check(&self, token_type: &TokenType) -> bool {
         if self.is_at_end() || self.peek().is_err() {
             return false;
@@ -311,3 +1560,639 @@ impl Parser {
         Err(Error::SyncBoundaryNotFound)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{expr::AstPrinter, scanner::Scanner};
+
+    use super::*;
+
+    fn parse(source: &str) -> PResult<Vec<Stmt>> {
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Source should scan");
+        Parser::new(tokens).parse()
+    }
+
+    #[test]
+    fn nested_groupings_within_limit() {
+        let depth = MAX_NESTING_DEPTH / 2;
+        let source = format!("{}1{};", "(".repeat(depth), ")".repeat(depth));
+
+        assert!(parse(&source).is_ok());
+    }
+
+    #[test]
+    fn deeply_nested_groupings_are_an_error() {
+        let depth = MAX_NESTING_DEPTH * 2;
+        let source = format!("{}1{};", "(".repeat(depth), ")".repeat(depth));
+
+        assert!(matches!(
+            parse(&source),
+            Err(Error::NestingTooDeep { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn deeply_nested_blocks_are_an_error() {
+        let depth = MAX_NESTING_DEPTH * 2;
+        let source = format!("{}print 1;{}", "{".repeat(depth), "}".repeat(depth));
+
+        assert!(matches!(parse(&source), Err(Error::NestingTooDeep { .. })));
+    }
+
+    #[test]
+    fn is_binds_like_a_comparison() {
+        let stmts = parse("1 + 2 is Number == true;").unwrap();
+
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(== (is (+ 1 2) Number) true)"
+        );
+    }
+
+    fn parse_repl(source: &str) -> PResult<Vec<Stmt>> {
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Source should scan");
+        Parser::new(tokens).parse_repl()
+    }
+
+    #[test]
+    fn repl_echoes_bare_expressions() {
+        let stmts = parse_repl("1 + 2\n").unwrap();
+
+        assert_eq!(AstPrinter::new().print(&stmts), "(print (+ 1 2))");
+    }
+
+    #[test]
+    fn repl_parses_statements_like_files() {
+        let stmts = parse_repl("var a = 1; a + 2;\n").unwrap();
+
+        assert_eq!(AstPrinter::new().print(&stmts), "(var a 1)\n(+ a 2)");
+        assert!(parse_repl("var a = 1").is_err());
+    }
+
+    #[test]
+    fn files_still_require_semicolons() {
+        assert!(parse("1 + 2\n").is_err());
+    }
+
+    fn parse_extended(source: &str) -> PResult<Vec<Stmt>> {
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Source should scan");
+        Parser::new(tokens)
+            .with_language_level(LanguageLevel::Extended)
+            .parse()
+    }
+
+    #[test]
+    fn if_and_blocks_are_expressions() {
+        let stmts = parse_extended("var x = if (a) { 1 } else if (b) { 2 } else { 3 };").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(var x (if a (block 1) (if b (block 2) (block 3))))"
+        );
+
+        let stmts = parse_extended("var y = { var a = 1; a = a + 1; a };").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(var y (block (var a 1) (= a (+ a 1)) a))"
+        );
+    }
+
+    #[test]
+    fn block_like_statements_need_no_semicolon() {
+        let stmts = parse_extended("if (a) { print 1; } print 2;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(if a (block (print 1)))\n(print 2)"
+        );
+
+        // At the start of a statement '{' is still a plain block statement
+        let stmts = parse_extended("{ print 1; }").unwrap();
+        assert_eq!(AstPrinter::new().print(&stmts), "(block (print 1))");
+        assert!(parse_extended("{ 1 }").is_err());
+        assert!(parse_extended("({ 1 });").is_ok());
+    }
+
+    #[test]
+    fn loop_and_break() {
+        let stmts = parse_extended("var x = loop { break 1; };").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(var x (loop (block (break 1))))"
+        );
+
+        let stmts = parse_extended("loop { if (a) { break; } } print 1;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(loop (block (if a (block (break)))))\n(print 1)"
+        );
+
+        let stmts = parse_extended("loop { continue; if (a) { continue; } }").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(loop (block (continue) (if a (block (continue)))))"
+        );
+
+        assert!(matches!(
+            parse_extended("{ break; }"),
+            Err(Error::BreakOutsideLoop(1))
+        ));
+        assert!(matches!(
+            parse_extended("{ continue; }"),
+            Err(Error::ContinueOutsideLoop(1))
+        ));
+        assert!(matches!(
+            parse_extended("loop { fun f() { continue; } }"),
+            Err(Error::ContinueOutsideLoop(1))
+        ));
+        assert!(matches!(
+            parse_extended("loop { } break;"),
+            Err(Error::BreakOutsideLoop(1))
+        ));
+        assert!(matches!(
+            parse("loop { break; }"),
+            Err(Error::ExtendedOnly { .. })
+        ));
+    }
+
+    #[test]
+    fn labeled_loops() {
+        let stmts = parse_extended(
+            "outer: loop { inner: for (x in xs) { break outer 1; continue inner; break x; } }",
+        )
+        .unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(outer: loop (block (inner: for x in xs (block (break outer 1) (continue inner) (break x)))))"
+        );
+
+        // In a block expression a label isn't a map key
+        let stmts = parse_extended(
+            "var x = { outer: loop { break outer; } }; var y = { inner: loop { break inner; } 1 };",
+        )
+        .unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(var x (block (outer: loop (block (break outer)))))\n\
+             (var y (block (inner: loop (block (break inner))) 1))"
+        );
+
+        assert!(matches!(
+            parse("a: for (x in xs) { continue b; }"),
+            Err(Error::UnknownLabel { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("a: print 1;"),
+            Err(Error::LabelWithoutLoop(1))
+        ));
+        // Labels don't reach into functions, nor past their loop
+        assert!(matches!(
+            parse("a: for (x in xs) { fun f() { for (y in ys) { continue a; } } }"),
+            Err(Error::UnknownLabel { .. })
+        ));
+        assert!(matches!(
+            parse("a: for (x in xs) {} for (y in ys) { continue a; }"),
+            Err(Error::UnknownLabel { .. })
+        ));
+    }
+
+    #[test]
+    fn expression_blocks_need_the_extended_level() {
+        assert!(matches!(
+            parse("var x = if (a) { 1 } else { 2 };"),
+            Err(Error::ExtendedOnly { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("var x = { 1 };"),
+            Err(Error::ExtendedOnly { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn chained_comparisons_are_an_error() {
+        let err = parse("1 < x + 1 <= 3;").unwrap_err();
+        assert!(matches!(err, Error::ChainedComparison { line: 1, .. }));
+        assert_eq!(
+            err.to_string(),
+            "Comparisons can't be chained in line 1: '1 < x + 1 <= 3' compares the result of \
+             the first comparison, a boolean. Write it as '1 < x + 1 and x + 1 <= 3' instead."
+        );
+
+        // Comparing the result of a comparison on purpose still works
+        assert!(parse("(1 < 2) < 3;").is_ok());
+        assert!(parse("1 < 2 is Bool;").is_ok());
+        assert!(parse("1 < 2 == 2 < 3;").is_ok());
+    }
+
+    #[test]
+    fn control_flow_statements() {
+        let stmts = parse("if (a) print 1; else if (b) print 2; else { print 3; }").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(if a (print 1) (if b (print 2) (block (print 3))))"
+        );
+
+        // The else goes with the nearest if
+        let stmts = parse("if (a) if (b) print 1; else print 2;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(if a (if b (print 1) (print 2)))"
+        );
+
+        let stmts = parse("while (a < 3) a = a + 1;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(while (< a 3) (= a (+ a 1)))"
+        );
+
+        let stmts = parse("for (var i = 0; i < 3; i = i + 1) print i; for (;;) print 1;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(block (var i 0) (while (< i 3) (print i) (= i (+ i 1))))\n(while true (print 1))"
+        );
+
+        // `and` binds tighter than `or`, both looser than comparisons
+        let stmts = parse("print a or b and c == d;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(print (or a (and b (== c d))))"
+        );
+
+        // At the extended level an `if` with block branches is still an expression
+        let stmts = parse_extended("if (a) { 1 } else { 2 } if (a) return; else print 1;");
+        assert!(matches!(stmts, Err(Error::ReturnOutsideFunction(1))));
+        let stmts =
+            parse_extended("fun f(n) { if (n) { n } else { 0 } if (n) return n; }").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(fun f (n) (if n (block n) (block 0)) (if n (return n)))"
+        );
+    }
+
+    #[test]
+    fn functions_and_calls() {
+        let stmts = parse("fun add(a, b) { print a + b; } add(1, 2)(3)();").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(fun add (a b) (print (+ a b)))\n(call (call (call add 1 2) 3))"
+        );
+
+        // A comma operator inside an argument needs parentheses
+        let stmts = parse("f((1, 2));").unwrap();
+        assert_eq!(AstPrinter::new().print(&stmts), "(call f (group (, 1 2)))");
+
+        let params = (0..=MAX_ARGUMENTS)
+            .map(|i| format!("p{i}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        assert!(matches!(
+            parse(&format!("fun f({params}) {{}}")),
+            Err(Error::TooManyArguments {
+                what: "parameters",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse(&format!("f({});", vec!["1"; MAX_ARGUMENTS + 1].join(", "))),
+            Err(Error::TooManyArguments {
+                what: "arguments",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn classes_and_properties() {
+        let stmts = parse(
+            "class Point { init(x) { return x; } norm() { } }
+             p.x = p.y.z = 1;
+             p.norm().x;",
+        )
+        .unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(class Point (fun init (x) (return x)) (fun norm () ))\n\
+             (set p x (set (get p y) z 1))\n\
+             (get (call (get p norm)) x)"
+        );
+
+        assert!(matches!(
+            parse("class A { fun f() {} }"),
+            Err(Error::MismatchedToken { .. })
+        ));
+        assert!(matches!(
+            parse("f().x() = 1;"),
+            Err(Error::InvalidAssignmentTarget(1))
+        ));
+    }
+
+    #[test]
+    fn getters_and_setters() {
+        let stmts = parse("class A { x { return 1; } set x(value) {} set(a, b) {} }").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(class A (getter x () (return 1)) (setter x (value) ) (fun set (a b) ))"
+        );
+
+        assert!(matches!(
+            parse("class A { set x(a, b) {} }"),
+            Err(Error::SetterArity(1))
+        ));
+        assert!(matches!(
+            parse("class A { set x() {} }"),
+            Err(Error::SetterArity(1))
+        ));
+    }
+
+    #[test]
+    fn parameter_defaults() {
+        let stmts = parse("fun f(a, b = 1, c = a + b) {}").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(fun f (a (= b 1) (= c (+ a b))) )"
+        );
+
+        assert!(matches!(
+            parse("fun f(a = 1, b) {}"),
+            Err(Error::RequiredAfterDefault { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("class A { set x(a = 1) {} }"),
+            Err(Error::SetterArity(1))
+        ));
+    }
+
+    #[test]
+    fn rest_parameters() {
+        let stmts = parse("fun f(a, b = 1, ...rest) {} fun g(...all) {}").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(fun f (a (= b 1) ...rest) )\n(fun g (...all) )"
+        );
+
+        assert!(matches!(
+            parse("fun f(...rest, a) {}"),
+            Err(Error::MismatchedToken { .. })
+        ));
+        assert!(matches!(
+            parse("class A { set x(...rest) {} }"),
+            Err(Error::SetterArity(1))
+        ));
+    }
+
+    #[test]
+    fn this_only_in_classes() {
+        let stmts = parse("class A { m() { fun f() { return this.x; } this.y = 1; } }").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(class A (fun m () (fun f () (return (get this x))) (set this y 1)))"
+        );
+
+        assert!(matches!(
+            parse("print this;"),
+            Err(Error::ThisOutsideClass(1))
+        ));
+        assert!(matches!(
+            parse("fun f() { return this; }"),
+            Err(Error::ThisOutsideClass(1))
+        ));
+        assert!(matches!(
+            parse("class A {} this;"),
+            Err(Error::ThisOutsideClass(1))
+        ));
+    }
+
+    #[test]
+    fn inheritance_and_super() {
+        let stmts = parse("class B < A { m() { return super.m(); } }").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(class B < A (fun m () (return (call (super m)))))"
+        );
+
+        assert!(matches!(
+            parse("class A < A {}"),
+            Err(Error::InheritsFromItself(1))
+        ));
+        assert!(matches!(
+            parse("fun f() { super.m(); }"),
+            Err(Error::SuperOutsideClass(1))
+        ));
+        assert!(matches!(
+            parse("class A { m() { super.m(); } }"),
+            Err(Error::SuperWithoutSuperclass(1))
+        ));
+        // Only the innermost class counts
+        assert!(matches!(
+            parse("class B < A { m() { class C { n() { super.n(); } } } }"),
+            Err(Error::SuperWithoutSuperclass(1))
+        ));
+        assert!(matches!(
+            parse("class B < A { m() { super; } }"),
+            Err(Error::MismatchedToken { .. })
+        ));
+    }
+
+    #[test]
+    fn maps_and_subscripts() {
+        let stmts = parse("var m = {\"a\": 1, 2: {}, }; m[\"a\"] = m[2][x ? 1 : 2];").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(var m (map a 1 2 (map)))\n\
+             (set-index m a (index (index m 2) (cond x 1 2)))"
+        );
+
+        // Braces are a map when there is a ':' on their level that isn't part of a ternary
+        let stmts = parse_extended(
+            "print { a ? b : c };
+             print { (x): y ? 1 : 2 };
+             print { f(a, { b: 1 }) };
+             print {};",
+        )
+        .unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(print (block (cond a b c)))\n\
+             (print (map (group x) (cond y 1 2)))\n\
+             (print (block (call f a (map b 1))))\n\
+             (print (map))"
+        );
+
+        assert!(matches!(
+            parse("print { 1 };"),
+            Err(Error::ExtendedOnly { .. })
+        ));
+        assert!(matches!(
+            parse("print { 1: 2 3: 4 };"),
+            Err(Error::MismatchedToken { .. })
+        ));
+        assert!(matches!(parse("m[1;"), Err(Error::MismatchedToken { .. })));
+    }
+
+    #[test]
+    fn try_and_throw() {
+        let stmts = parse(
+            "try { throw 1; } catch (e) { print e; }
+             try { f(); } finally { g(); }
+             try { } catch (e) { } finally { }",
+        )
+        .unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(try (throw 1) (catch e (print e)))\n\
+             (try (call f) (finally (call g)))\n\
+             (try (catch e) (finally))"
+        );
+
+        assert!(matches!(parse("try { }"), Err(Error::TryWithoutHandler(1))));
+        assert!(matches!(
+            parse("try { } catch { }"),
+            Err(Error::MismatchedToken { .. })
+        ));
+        assert!(matches!(parse("throw;"), Err(Error::UnexpectedToken(..))));
+    }
+
+    #[test]
+    fn modulo_and_exponent_precedence() {
+        let stmts = parse("1 + 2 * 3 % 4; 2 ** 3 ** 2; a.b ** 2 * 3;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(+ 1 (% (* 2 3) 4))\n(** 2 (** 3 2))\n(* (** (get a b) 2) 3)"
+        );
+    }
+
+    #[test]
+    fn unary_operators_nest() {
+        let stmts = parse("-(-3); !!x; -2 ** 2; -a.b * 2;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(- (group (- 3)))\n(! (! x))\n(- (** 2 2))\n(* (- (get a b)) 2)"
+        );
+    }
+
+    #[test]
+    fn ternary_and_comma_precedence() {
+        let stmts = parse("a ? b : c ? d : e; a ? b = 1, c : d; a = 1, b = 2;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(cond a b (cond c d e))\n(cond a (, (= b 1) c) d)\n(, (= a 1) (= b 2))"
+        );
+    }
+
+    #[test]
+    fn for_in_loops() {
+        let stmts = parse("for (x in xs) print x; for (in in a.b) { break; } var in;").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(for x in xs (print x))\n(for in in (get a b) (block (break)))\n(var in)"
+        );
+
+        assert!(matches!(
+            parse("for (x of xs) print x;"),
+            Err(Error::MismatchedToken { .. })
+        ));
+        assert!(matches!(
+            parse("for (x in xs) print x; break;"),
+            Err(Error::BreakOutsideLoop(1))
+        ));
+    }
+
+    #[test]
+    fn constants_need_a_value() {
+        let stmts = parse("const a = 1; var b;").unwrap();
+        assert_eq!(AstPrinter::new().print(&stmts), "(const a 1)\n(var b)");
+
+        assert!(matches!(
+            parse("const a;"),
+            Err(Error::MismatchedToken { .. })
+        ));
+    }
+
+    #[test]
+    fn switch_statements() {
+        let stmts = parse(
+            "switch (x) { case 1: print 1; case \"a\": case nil: default: print 2; print 3; }
+             switch (x) {}",
+        )
+        .unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(switch x (case 1 (print 1)) (case a) (case nil) (default (print 2) (print 3)))\n\
+             (switch x)"
+        );
+
+        assert!(matches!(
+            parse("switch (x) { case 1: case 1.0: }"),
+            Err(Error::DuplicateCase { line: 1, .. })
+        ));
+        // Different types are different cases
+        assert!(parse("switch (x) { case 1: case \"1\": }").is_ok());
+        assert!(matches!(
+            parse("switch (x) { case y: }"),
+            Err(Error::UnexpectedToken(..))
+        ));
+        assert!(matches!(
+            parse("switch (x) { default: case 1: }"),
+            Err(Error::MismatchedToken { .. })
+        ));
+    }
+
+    #[test]
+    fn imports_are_named_after_the_file() {
+        let stmts = parse("import \"lib/math.lox\"; { import \"util\"; }").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(import \"lib/math.lox\" math)\n(block (import \"util\" util))"
+        );
+
+        assert!(matches!(
+            parse("import \"my-lib.lox\";"),
+            Err(Error::InvalidModuleName { .. })
+        ));
+        assert!(matches!(
+            parse("import \"class.lox\";"),
+            Err(Error::InvalidModuleName { .. })
+        ));
+        assert!(matches!(
+            parse("import math;"),
+            Err(Error::MismatchedToken { .. })
+        ));
+    }
+
+    #[test]
+    fn return_only_in_functions() {
+        let stmts = parse("fun f(a) { { return a; } return; }").unwrap();
+        assert_eq!(
+            AstPrinter::new().print(&stmts),
+            "(fun f (a) (block (return a)) (return))"
+        );
+
+        assert!(matches!(
+            parse("return 1;"),
+            Err(Error::ReturnOutsideFunction(1))
+        ));
+        assert!(matches!(
+            parse("fun f() {} { return; }"),
+            Err(Error::ReturnOutsideFunction(1))
+        ));
+    }
+
+    #[test]
+    fn break_does_not_cross_functions() {
+        assert!(matches!(
+            parse_extended("loop { fun f() { break; } }"),
+            Err(Error::BreakOutsideLoop(1))
+        ));
+    }
+
+    #[test]
+    fn long_assignment_chains_are_an_error() {
+        let source = format!("{}1;", "a = ".repeat(MAX_NESTING_DEPTH * 2));
+
+        assert!(matches!(parse(&source), Err(Error::NestingTooDeep { .. })));
+    }
+}
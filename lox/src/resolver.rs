@@ -0,0 +1,614 @@
+use std::{cell::RefCell, collections::HashMap, sync::Arc};
+
+use thiserror::Error;
+
+use crate::{
+    expr::{Expr, Function, Resolution, Stmt, Visitor},
+    interpreter::Interpreter,
+    token::Token,
+};
+
+// Static pass between the parser and the interpreter.
+//
+// Works out for every variable which scope it refers to, counted from where it is used, and
+// writes that into the tree for the interpreter, see expr::Resolution. At runtime a variable then always ends up at the declaration
+// it had when the code was written, also when a closure runs after the scope around it got
+// a new variable with the same name:
+//
+//     var a = "global";
+//     {
+//         fun show() { print a; }
+//         show();
+//         var a = "block";
+//         show(); // still "global"
+//     }
+//
+// Globals aren't tracked: anything not found in a local scope is looked up in the globals at
+// runtime, so functions can use globals declared after them. With strict globals (see
+// Interpreter::with_strict_globals) assigning to one is only allowed when some top level code
+// declares it, here or in code that ran before.
+//
+// With shadowing warnings on, a declaration that hides a variable of an enclosing scope, also a
+// global or a parameter, is reported in `warnings`. It is still allowed.
+pub struct Resolver<'a> {
+    interpreter: &'a Interpreter,
+    // The local scopes we are in, innermost last.
+    scopes: RefCell<Vec<HashMap<String, Local>>>,
+    // The globals declared by the programs resolved so far, anywhere at their top level, and
+    // the line they are declared in.
+    globals: RefCell<HashMap<String, i32>>,
+    shadowing_warnings: bool,
+    warnings: RefCell<Vec<Warning>>,
+}
+
+struct Local {
+    // Whether the variable is done with its initializer, and can be read.
+    defined: bool,
+    // None for `this` and `super`, which the code doesn't declare itself.
+    line: Option<i32>,
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum Error {
+    #[error("Can't read local variable '{name}' in its own initializer in line {line}.")]
+    ReadInOwnInitializer { line: i32, name: String },
+    #[error("Already a variable named '{name}' in this scope in line {line}.")]
+    AlreadyDeclared { line: i32, name: String },
+    #[error("Can't assign to undeclared variable '{name}' in line {line}.")]
+    UndeclaredGlobal { line: i32, name: String },
+}
+
+#[derive(Error, Debug, Clone)]
+pub enum Warning {
+    #[error("Variable '{name}' in line {line} shadows the one declared in line {shadowed}.")]
+    Shadows {
+        line: i32,
+        shadowed: i32,
+        name: String,
+    },
+}
+
+type RResult<T> = Result<T, Error>;
+
+// The parser limits how deeply the AST nests, the same stack growth as there keeps walking it safe.
+const RED_ZONE: usize = 64 * 1024;
+const STACK_GROWTH: usize = 1024 * 1024;
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a Interpreter) -> Self {
+        Self {
+            interpreter,
+            scopes: RefCell::new(vec![]),
+            globals: RefCell::new(HashMap::new()),
+            shadowing_warnings: false,
+            warnings: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn with_shadowing_warnings(mut self, shadowing_warnings: bool) -> Self {
+        self.shadowing_warnings = shadowing_warnings;
+        self
+    }
+
+    // The warnings of everything resolved since the last call, in the order of the code.
+    pub fn take_warnings(&self) -> Vec<Warning> {
+        self.warnings.take()
+    }
+
+    pub fn resolve(&self, stmts: &[Stmt]) -> RResult<()> {
+        // A program's globals are known before any of it is resolved, functions can assign to
+        // ones declared after them.
+        if self.scopes.borrow().is_empty() {
+            self.globals.borrow_mut().extend(
+                stmts
+                    .iter()
+                    .filter_map(declared_name)
+                    .map(|name| (name.lexeme().to_owned(), *name.line())),
+            );
+        }
+
+        stmts.iter().try_for_each(|stmt| self.visit_stmt(stmt))
+    }
+
+    // Runs `f` in a new local scope.
+    fn in_scope(&self, f: impl FnOnce() -> RResult<()>) -> RResult<()> {
+        self.scopes.borrow_mut().push(HashMap::new());
+        let result = f();
+        self.scopes.borrow_mut().pop();
+        result
+    }
+
+    // Adds `name` to the innermost scope, but it can't be read until it is defined.
+    fn declare(&self, name: &Token) -> RResult<()> {
+        let mut scopes = self.scopes.borrow_mut();
+        let Some(scope) = scopes.last_mut() else {
+            return Ok(());
+        };
+
+        if scope.contains_key(name.lexeme()) {
+            return Err(Error::AlreadyDeclared {
+                line: *name.line(),
+                name: name.lexeme().to_owned(),
+            });
+        }
+
+        scope.insert(
+            name.lexeme().to_owned(),
+            Local {
+                defined: false,
+                line: Some(*name.line()),
+            },
+        );
+
+        if self.shadowing_warnings {
+            self.check_shadowing(&scopes, name);
+        }
+        Ok(())
+    }
+
+    // Warns when `name`, just declared in the innermost scope, hides a variable further out.
+    fn check_shadowing(&self, scopes: &[HashMap<String, Local>], name: &Token) {
+        let enclosing = scopes
+            .iter()
+            .rev()
+            .skip(1)
+            .find_map(|scope| scope.get(name.lexeme()))
+            .map(|local| local.line);
+        let shadowed = match enclosing {
+            Some(line) => line,
+            None => self.globals.borrow().get(name.lexeme()).copied(),
+        };
+
+        if let Some(shadowed) = shadowed {
+            self.warnings.borrow_mut().push(Warning::Shadows {
+                line: *name.line(),
+                shadowed,
+                name: name.lexeme().to_owned(),
+            });
+        }
+    }
+
+    fn define(&self, name: &str) {
+        if let Some(scope) = self.scopes.borrow_mut().last_mut() {
+            scope
+                .entry(name.to_owned())
+                .or_insert(Local {
+                    defined: false,
+                    line: None,
+                })
+                .defined = true;
+        }
+    }
+
+    // Records how many scopes up from where it is used the variable `name` lives, None for
+    // a global.
+    fn resolve_local(&self, resolution: &Resolution, name: &Token) -> Option<usize> {
+        let depth = self
+            .scopes
+            .borrow()
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name.lexeme()));
+
+        resolution.set(depth);
+        depth
+    }
+
+    fn check_global_assignment(&self, name: &Token) -> RResult<()> {
+        let declared = self.globals.borrow().contains_key(name.lexeme())
+            || self.interpreter.is_global(name.lexeme());
+
+        if self.interpreter.strict_globals() && !declared {
+            return Err(Error::UndeclaredGlobal {
+                line: *name.line(),
+                name: name.lexeme().to_owned(),
+            });
+        }
+
+        Ok(())
+    }
+
+    // Parameters and the body share one scope, like they do when the function is called.
+    fn resolve_function(&self, function: &Function) -> RResult<()> {
+        self.in_scope(|| {
+            // Defaults are evaluated in the scope too, and can use the parameters before them.
+            let required = function.required_params();
+            for (i, param) in function.params.iter().enumerate() {
+                self.declare(param)?;
+                if let Some(default) = i.checked_sub(required) {
+                    self.visit_expr(&function.defaults[default])?;
+                }
+                self.define(param.lexeme());
+            }
+            if let Some(rest) = &function.rest {
+                self.declare(rest)?;
+                self.define(rest.lexeme());
+            }
+
+            self.resolve(&function.body)
+        })
+    }
+
+    // Methods see `this`, and in a subclass also `super`, each in a scope of its own.
+    // The interpreter creates the same scopes in LoxFunction::bind and execute_class.
+    fn resolve_class(
+        &self,
+        name: &Token,
+        superclass: Option<&Expr>,
+        methods: &[Arc<Function>],
+    ) -> RResult<()> {
+        self.declare(name)?;
+        self.define(name.lexeme());
+
+        let methods = || {
+            self.in_scope(|| {
+                self.define("this");
+                methods
+                    .iter()
+                    .try_for_each(|method| self.resolve_function(method))
+            })
+        };
+
+        match superclass {
+            Some(superclass) => {
+                self.visit_expr(superclass)?;
+                self.in_scope(|| {
+                    self.define("super");
+                    methods()
+                })
+            }
+            None => methods(),
+        }
+    }
+}
+
+impl Visitor<()> for Resolver<'_> {
+    type ExprOutput = RResult<()>;
+    type StmtOutput = RResult<()>;
+
+    fn visit_expr(&self, expr: &Expr) -> Self::ExprOutput {
+        stacker::maybe_grow(RED_ZONE, STACK_GROWTH, || match expr {
+            Expr::Variable(name, resolution) => {
+                let in_initializer = self
+                    .scopes
+                    .borrow()
+                    .last()
+                    .and_then(|scope| scope.get(name.lexeme()))
+                    .is_some_and(|local| !local.defined);
+                if in_initializer {
+                    return Err(Error::ReadInOwnInitializer {
+                        line: *name.line(),
+                        name: name.lexeme().to_owned(),
+                    });
+                }
+
+                self.resolve_local(resolution, name);
+                Ok(())
+            }
+            Expr::Assign(name, value, resolution) => {
+                self.visit_expr(value)?;
+                match self.resolve_local(resolution, name) {
+                    Some(_) => Ok(()),
+                    None => self.check_global_assignment(name),
+                }
+            }
+            // The type name is a class when it isn't one of the built in types.
+            Expr::Is(value, type_name, resolution) => {
+                self.visit_expr(value)?;
+                self.resolve_local(resolution, type_name);
+                Ok(())
+            }
+            Expr::This(keyword, resolution) => {
+                self.resolve_local(resolution, keyword);
+                Ok(())
+            }
+            Expr::Super(keyword, _, resolution) => {
+                self.resolve_local(resolution, keyword);
+                Ok(())
+            }
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+                self.visit_expr(left)?;
+                self.visit_expr(right)
+            }
+            Expr::Grouping(inner) | Expr::Unary(_, inner) | Expr::Loop(_, inner) => {
+                self.visit_expr(inner)
+            }
+            Expr::Get(object, _) => self.visit_expr(object),
+            Expr::Map(_, entries) => entries.iter().try_for_each(|(key, value)| {
+                self.visit_expr(key)?;
+                self.visit_expr(value)
+            }),
+            Expr::Index(object, _, key) => {
+                self.visit_expr(object)?;
+                self.visit_expr(key)
+            }
+            Expr::SetIndex(object, _, key, value) => {
+                self.visit_expr(object)?;
+                self.visit_expr(key)?;
+                self.visit_expr(value)
+            }
+            Expr::Literal(_) => Ok(()),
+            Expr::Condition(condition, inner_true, inner_false) => {
+                self.visit_expr(condition)?;
+                self.visit_expr(inner_true)?;
+                self.visit_expr(inner_false)
+            }
+            Expr::Block(stmts, value) => self.in_scope(|| {
+                self.resolve(stmts)?;
+                value
+                    .as_deref()
+                    .map_or(Ok(()), |value| self.visit_expr(value))
+            }),
+            Expr::If(condition, then_branch, else_branch) => {
+                self.visit_expr(condition)?;
+                self.visit_expr(then_branch)?;
+                else_branch
+                    .as_deref()
+                    .map_or(Ok(()), |else_branch| self.visit_expr(else_branch))
+            }
+            Expr::Call(callee, _, arguments) => {
+                self.visit_expr(callee)?;
+                arguments
+                    .iter()
+                    .try_for_each(|argument| self.visit_expr(argument))
+            }
+            Expr::Set(object, _, value) => {
+                self.visit_expr(object)?;
+                self.visit_expr(value)
+            }
+        })
+    }
+
+    fn visit_stmt(&self, stmt: &Stmt) -> Self::StmtOutput {
+        stacker::maybe_grow(RED_ZONE, STACK_GROWTH, || match stmt {
+            Stmt::Expression(expr) | Stmt::Print(expr) => self.visit_expr(expr),
+            Stmt::Var(name, initializer) => {
+                self.declare(name)?;
+                if let Some(initializer) = initializer {
+                    self.visit_expr(initializer)?;
+                }
+                self.define(name.lexeme());
+                Ok(())
+            }
+            Stmt::Const(name, value) => {
+                self.declare(name)?;
+                self.visit_expr(value)?;
+                self.define(name.lexeme());
+                Ok(())
+            }
+            Stmt::Block(stmts) => self.in_scope(|| self.resolve(stmts)),
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.visit_expr(condition)?;
+                self.visit_stmt(then_branch)?;
+                else_branch
+                    .as_deref()
+                    .map_or(Ok(()), |else_branch| self.visit_stmt(else_branch))
+            }
+            Stmt::While(_, condition, body, increment) => {
+                self.visit_expr(condition)?;
+                self.visit_stmt(body)?;
+                increment
+                    .as_ref()
+                    .map_or(Ok(()), |increment| self.visit_expr(increment))
+            }
+            Stmt::Continue(..) => Ok(()),
+            Stmt::Throw(_, value) => self.visit_expr(value),
+            Stmt::Switch(value, cases, default) => {
+                self.visit_expr(value)?;
+                cases
+                    .iter()
+                    .map(|(_, body)| body)
+                    .chain(default)
+                    .try_for_each(|body| self.in_scope(|| self.resolve(body)))
+            }
+            // The caught value gets a scope of its own, shared with the handler.
+            Stmt::Try(body, catch, finally) => {
+                self.in_scope(|| self.resolve(body))?;
+                if let Some((name, handler)) = catch {
+                    self.in_scope(|| {
+                        self.declare(name)?;
+                        self.define(name.lexeme());
+                        self.resolve(handler)
+                    })?;
+                }
+                match finally {
+                    Some(finally) => self.in_scope(|| self.resolve(finally)),
+                    None => Ok(()),
+                }
+            }
+            Stmt::Break(_, _, value) | Stmt::Return(_, value) => value
+                .as_ref()
+                .map_or(Ok(()), |value| self.visit_expr(value)),
+            // Declared before the body, so the function can call itself.
+            Stmt::Function(function) => {
+                self.declare(&function.name)?;
+                self.define(function.name.lexeme());
+                self.resolve_function(function)
+            }
+            Stmt::Class(name, superclass, methods) => {
+                self.resolve_class(name, superclass.as_ref(), methods)
+            }
+            // Like in the interpreter, every iteration has a scope with the variable in it.
+            Stmt::ForIn(_, _, name, collection, body) => {
+                self.visit_expr(collection)?;
+                self.in_scope(|| {
+                    self.declare(name)?;
+                    self.define(name.lexeme());
+                    self.visit_stmt(body)
+                })
+            }
+            // The module itself is resolved when it is imported, it doesn't see our scopes.
+            Stmt::Import(_, _, name) => {
+                self.declare(name)?;
+                self.define(name.lexeme());
+                Ok(())
+            }
+        })
+    }
+}
+
+// The variable `stmt` declares, when it is run at the top level.
+fn declared_name(stmt: &Stmt) -> Option<&Token> {
+    match stmt {
+        Stmt::Var(name, _)
+        | Stmt::Const(name, _)
+        | Stmt::Class(name, _, _)
+        | Stmt::Import(_, _, name) => Some(name),
+        Stmt::Function(function) => Some(&function.name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    fn resolve(source: &str) -> RResult<()> {
+        resolve_with(&Interpreter::new(), source)
+    }
+
+    fn resolve_with(interpreter: &Interpreter, source: &str) -> RResult<()> {
+        let tokens = Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Source should scan");
+        let stmts = Parser::new(tokens).parse().expect("Source should parse");
+
+        Resolver::new(interpreter).resolve(&stmts)
+    }
+
+    #[test]
+    fn reading_a_local_in_its_own_initializer_is_an_error() {
+        assert!(matches!(
+            resolve("{ var a = a; }"),
+            Err(Error::ReadInOwnInitializer { line: 1, .. })
+        ));
+        assert!(matches!(
+            resolve("var a = 1; { var a = a + 1; }"),
+            Err(Error::ReadInOwnInitializer { .. })
+        ));
+        // Globals are looked up at runtime
+        assert!(resolve("var a = a;").is_ok());
+    }
+
+    #[test]
+    fn resolutions_are_written_into_the_tree() {
+        let tokens = Scanner::new("var a; { var b; { a; b; } }".as_bytes())
+            .scan_tokens()
+            .unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+
+        let Stmt::Block(outer) = &stmts[1] else {
+            panic!("Expected a block");
+        };
+        let Stmt::Block(inner) = &outer[1] else {
+            panic!("Expected a block");
+        };
+        let resolutions = inner
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Expression(Expr::Variable(_, resolution)) => resolution,
+                _ => panic!("Expected a variable"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(resolutions[0].get(), None);
+        Resolver::new(&Interpreter::new()).resolve(&stmts).unwrap();
+        // `a` is a global, `b` is one scope up
+        assert_eq!(resolutions[0].get(), Some(None));
+        assert_eq!(resolutions[1].get(), Some(Some(1)));
+    }
+
+    #[test]
+    fn redeclaring_a_local_is_an_error() {
+        assert!(matches!(
+            resolve("{ var a = 1; var a = 2; }"),
+            Err(Error::AlreadyDeclared { line: 1, .. })
+        ));
+        assert!(matches!(
+            resolve("fun f(a, a) {}"),
+            Err(Error::AlreadyDeclared { .. })
+        ));
+        assert!(matches!(
+            resolve("fun f(a) { var a = 1; }"),
+            Err(Error::AlreadyDeclared { .. })
+        ));
+        // Only within the same scope, and not for globals
+        assert!(resolve("var a = 1; var a = 2; { var a = 3; { var a = 4; } }").is_ok());
+    }
+
+    #[test]
+    fn strict_globals_must_be_declared_to_be_assigned() {
+        let strict = Interpreter::new().with_strict_globals(true);
+
+        assert!(matches!(
+            resolve_with(&strict, "a = 1;"),
+            Err(Error::UndeclaredGlobal { line: 1, .. })
+        ));
+        assert!(matches!(
+            resolve_with(&strict, "{ var a; } fun f() { a = 1; }"),
+            Err(Error::UndeclaredGlobal { .. })
+        ));
+        // Declared anywhere at the top level, also after the function assigning it, or as a
+        // native
+        assert!(resolve_with(&strict, "fun f() { a = 1; } var a; clock = 1;").is_ok());
+        assert!(resolve_with(&strict, "fun f(a) { a = 1; { var b; b = 2; } }").is_ok());
+        // Or by code that ran before, like an earlier line in the REPL
+        let tokens = Scanner::new("var b;".as_bytes()).scan_tokens().unwrap();
+        let earlier = Parser::new(tokens).parse().unwrap();
+        Resolver::new(&strict).resolve(&earlier).unwrap();
+        strict.interpret(&earlier).unwrap();
+        assert!(resolve_with(&strict, "b = 1;").is_ok());
+        // Without strict globals it is left to the runtime
+        assert!(resolve("a = 1;").is_ok());
+    }
+
+    #[test]
+    fn shadowing_is_warned_about_when_asked() {
+        let warnings = |source: &str| {
+            let tokens = Scanner::new(source.as_bytes()).scan_tokens().unwrap();
+            let stmts = Parser::new(tokens).parse().unwrap();
+            let interpreter = Interpreter::new();
+            let resolver = Resolver::new(&interpreter).with_shadowing_warnings(true);
+            resolver.resolve(&stmts).unwrap();
+            resolver
+                .take_warnings()
+                .iter()
+                .map(Warning::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            warnings(
+                "var a = 1;
+                 fun f(b) {
+                   { var a = 2; var b = 3; }
+                 }"
+            ),
+            vec![
+                "Variable 'a' in line 3 shadows the one declared in line 1.",
+                "Variable 'b' in line 3 shadows the one declared in line 2.",
+            ]
+        );
+        // The innermost one is reported, globals declared later count too
+        assert_eq!(
+            warnings("{ var c;\n { var c;\n { var c; } } }\n var c;"),
+            vec![
+                "Variable 'c' in line 1 shadows the one declared in line 4.",
+                "Variable 'c' in line 2 shadows the one declared in line 1.",
+                "Variable 'c' in line 3 shadows the one declared in line 2.",
+            ]
+        );
+        assert!(warnings("{ var a; } { var a; } fun f(a) {}").is_empty());
+
+        // Off by default
+        let tokens = Scanner::new("var a; { var a; }".as_bytes())
+            .scan_tokens()
+            .unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let interpreter = Interpreter::new();
+        let resolver = Resolver::new(&interpreter);
+        resolver.resolve(&stmts).unwrap();
+        assert!(resolver.take_warnings().is_empty());
+    }
+}
@@ -5,26 +5,53 @@ use thiserror::Error;
 
 use crate::token::{Literal, Token, TokenType};
 
+// Columns count chars from 1, like lines.
 #[derive(Error, Debug, Clone)]
 pub(crate) enum Error {
-    #[error("invalid char: {0}")]
-    UnexceptedChar(char),
-    #[error("unterminated string at line: {0}")]
-    UnterminatedString(i32),
-    #[error("unable to parse to float: {0}")]
-    ParseError(#[from] std::num::ParseFloatError),
+    #[error("unexpected character '{char}' at line {line}, column {column}")]
+    UnexpectedChar {
+        line: i32,
+        column: usize,
+        char: char,
+    },
+    // Where the string starts.
+    #[error("unterminated string at line {line}, column {column}")]
+    UnterminatedString { line: i32, column: usize },
+    #[error("invalid escape sequence '{escape}' at line {line}, column {column}")]
+    InvalidEscape {
+        line: i32,
+        column: usize,
+        escape: String,
+    },
+    #[error("unable to parse number at line {line}, column {column}: {source}")]
+    ParseError {
+        line: i32,
+        column: usize,
+        source: std::num::ParseFloatError,
+    },
+}
+
+impl Error {
+    fn location(&self) -> (i32, usize) {
+        match self {
+            Error::UnexpectedChar { line, column, .. }
+            | Error::UnterminatedString { line, column }
+            | Error::InvalidEscape { line, column, .. }
+            | Error::ParseError { line, column, .. } => (*line, *column),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Error)]
 pub struct Errors(Vec<Error>);
 
+// One error per line.
 impl Display for Errors {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let output = self
             .0
-            .clone()
-            .into_iter()
-            .map(|err| format!("{err:?}"))
+            .iter()
+            .map(|err| err.to_string())
             .collect::<Vec<String>>()
             .join("\n");
 
@@ -33,6 +60,33 @@ impl Display for Errors {
 }
 
 impl Errors {
+    // Every error followed by the line of `source` it is on, with a caret under the column:
+    //
+    //     unexpected character '@' at line 1, column 7
+    //      1 | print @;
+    //        |       ^
+    //
+    // `source` has to be what was scanned.
+    pub fn report(&self, source: &str) -> String {
+        self.0
+            .iter()
+            .map(|err| {
+                let (line, column) = err.location();
+                let text = source.lines().nth(line as usize - 1).unwrap_or_default();
+                // Tabs stay tabs, so the caret lines up however wide they are shown.
+                let indent = text
+                    .chars()
+                    .take(column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect::<String>();
+                let gutter = " ".repeat(line.to_string().len());
+
+                format!("{err}\n {line} | {text}\n {gutter} | {indent}^")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
     fn new() -> Self {
         Self { 0: Vec::new() }
     }
@@ -46,31 +100,57 @@ impl Errors {
     }
 }
 
+// A change to the source, in byte indices. The chars `start..old_end` of the old source were
+// replaced by the ones at `start..new_end` in the new source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edit {
+    pub start: usize,
+    pub old_end: usize,
+    pub new_end: usize,
+}
+
 static KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "and" => TokenType::And,
+    "break" => TokenType::Break,
+    "case" => TokenType::Case,
+    "catch" => TokenType::Catch,
     "class" => TokenType::Class,
+    "const" => TokenType::Const,
+    "continue" => TokenType::Continue,
+    "default" => TokenType::Default,
     "else" => TokenType::Else,
     "false" => TokenType::False,
+    "finally" => TokenType::Finally,
     "for" => TokenType::For,
     "fun" => TokenType::Fun,
     "if" => TokenType::If,
+    "import" => TokenType::Import,
+    "is" => TokenType::Is,
+    "loop" => TokenType::Loop,
     "nil" => TokenType::Nil,
     "or" => TokenType::Or,
     "print" => TokenType::Print,
     "return" => TokenType::Return,
     "super" => TokenType::Super,
+    "switch" => TokenType::Switch,
     "this" => TokenType::This,
+    "throw" => TokenType::Throw,
     "true" => TokenType::True,
+    "try" => TokenType::Try,
     "var" => TokenType::Var,
     "while" => TokenType::While
 };
 
+// `start` and `current` are byte indices into `source`, always on a char boundary. Reading the
+// char at one is constant time, so scanning is linear in the length of the source.
 pub struct Scanner {
     source: String,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: i32,
+    // Where `line` starts, for the columns in errors.
+    line_start: usize,
     errors: Errors,
 }
 
@@ -82,6 +162,7 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
             errors: Errors::new(),
         }
     }
@@ -106,20 +187,107 @@ impl Scanner {
         Ok(self.tokens.clone())
     }
 
+    // Scans the source after an edit, reusing the tokens of the source before it.
+    // `previous` has to be the result of scanning the old source. Only the region around the edit
+    // is scanned again, so an editor or the REPL can call this on every keystroke.
+    pub fn rescan(&mut self, previous: &[Token], edit: Edit) -> Result<Vec<Token>, Errors> {
+        let previous = match previous.split_last() {
+            Some((eof, rest)) if eof.token_type() == &TokenType::Eof => rest,
+            _ => previous,
+        };
+
+        // Tokens ending before the edit stay the same. One ending right at it could grow into it.
+        let kept = previous
+            .iter()
+            .take_while(|token| token.end() < edit.start)
+            .count();
+        self.tokens = previous[..kept].to_vec();
+        if let Some(last) = self.tokens.last() {
+            self.current = last.end();
+            self.line = *last.line();
+            self.line_start = self.source[..self.current]
+                .rfind('\n')
+                .map_or(0, |newline| newline + 1);
+        }
+
+        let shift = edit.new_end as isize - edit.old_end as isize;
+        let mut old = previous[kept..].iter().enumerate().peekable();
+
+        while !self.is_at_end() {
+            self.start = self.current;
+
+            let scanned = self.tokens.len();
+            if let Err(err) = self.scan_token() {
+                self.errors.push(err.clone());
+            }
+
+            let Some(token) = self.tokens.get(scanned) else {
+                continue;
+            };
+            if token.offset() < edit.new_end {
+                continue;
+            }
+
+            // Past the edit the text is the same as before. As soon as a token starts where an old
+            // one did, everything after it is the same too, apart from where it is.
+            while old
+                .next_if(|(_, old)| (old.offset() as isize + shift) < token.offset() as isize)
+                .is_some()
+            {}
+
+            if let Some((index, old_token)) = old.peek() {
+                if old_token.offset() as isize + shift == token.offset() as isize {
+                    let lines = token.line() - old_token.line();
+                    let rest = &previous[kept + index + 1..];
+
+                    self.tokens
+                        .extend(rest.iter().map(|token| token.shifted(shift, lines)));
+                    break;
+                }
+            }
+        }
+
+        self.tokens
+            .push(Token::new(TokenType::Eof, String::new(), None, 0));
+
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
+        }
+
+        Ok(self.tokens.clone())
+    }
+
     fn scan_token(&mut self) -> Result<(), Error> {
         match self.advance() {
             '(' => self.get_and_add_token(TokenType::LeftParen),
             ')' => self.get_and_add_token(TokenType::RightParen),
             '{' => self.get_and_add_token(TokenType::LeftBrace),
             '}' => self.get_and_add_token(TokenType::RightBrace),
+            '[' => self.get_and_add_token(TokenType::LeftBracket),
+            ']' => self.get_and_add_token(TokenType::RightBracket),
             ',' => self.get_and_add_token(TokenType::Comma),
-            '.' => self.get_and_add_token(TokenType::Dot),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.advance();
+                    self.advance();
+                    self.get_and_add_token(TokenType::DotDotDot)
+                } else {
+                    self.get_and_add_token(TokenType::Dot)
+                }
+            }
             '-' => self.get_and_add_token(TokenType::Minus),
             '+' => self.get_and_add_token(TokenType::Plus),
             ';' => self.get_and_add_token(TokenType::Semicolon),
             ':' => self.get_and_add_token(TokenType::Colon),
             '?' => self.get_and_add_token(TokenType::QuestionMark),
-            '*' => self.get_and_add_token(TokenType::Star),
+            '%' => self.get_and_add_token(TokenType::Percent),
+            '*' => {
+                if self.match_char('*') {
+                    self.get_and_add_token(TokenType::StarStar)
+                } else {
+                    self.get_and_add_token(TokenType::Star)
+                }
+            }
             '!' => {
                 if self.match_char('=') {
                     self.get_and_add_token(TokenType::BangEqual)
@@ -160,10 +328,8 @@ impl Scanner {
                     self.get_and_add_token(TokenType::Slash)
                 }
             }
-            ' ' | '\r' | '\t' => {}
-            '\n' => {
-                self.line = self.line + 1;
-            }
+            // `advance` counts the lines.
+            ' ' | '\r' | '\t' | '\n' => {}
             '"' => {
                 let token = self.string()?;
                 self.add_token(token);
@@ -178,7 +344,12 @@ impl Scanner {
                     self.add_token(token);
                     return Ok(());
                 }
-                return Err(Error::UnexceptedChar(unknown));
+                let (line, column) = self.start_location();
+                return Err(Error::UnexpectedChar {
+                    line,
+                    column,
+                    char: unknown,
+                });
             }
         };
 
@@ -187,7 +358,8 @@ impl Scanner {
 
     fn consume_block_comment(&mut self) {
         let mut count = 1;
-        loop {
+        // An unterminated comment goes until the end of the source.
+        while !self.is_at_end() {
             match self.peek() {
                 '/' => {
                     self.advance();
@@ -206,10 +378,6 @@ impl Scanner {
                         }
                     }
                 }
-                '\n' => {
-                    self.line = self.line + 1;
-                    self.advance();
-                }
                 _ => {
                     self.advance();
                 }
@@ -233,12 +401,14 @@ impl Scanner {
         return self.get_token(TokenType::Identifier, None);
     }
 
+    // Identifiers follow the Unicode rules (XID), like Rust's, so `café` or `变量` work too.
+    // Only ASCII digits make numbers.
     fn is_alphanumeric(&self, c: char) -> bool {
-        return self.is_alpha(c) || self.is_digit(c);
+        return unicode_ident::is_xid_continue(c);
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        return c.is_ascii_alphabetic() || c == '_';
+        return unicode_ident::is_xid_start(c) || c == '_';
     }
 
     fn number(&mut self) -> Result<Token, Error> {
@@ -254,35 +424,31 @@ impl Scanner {
             }
         }
 
-        let value = self.source[self.start..self.current]
-            .parse::<f64>()
-            .map_err(|err| Error::ParseError(err))?;
+        let lexeme = &self.source[self.start..self.current];
+
+        // Integers too big for an Int are still numbers, just not exact ones.
+        if let Ok(value) = lexeme.parse::<i64>() {
+            return Ok(self.get_token(TokenType::Number, Some(Literal::Int(value))));
+        }
+
+        let value = lexeme.parse::<f64>().map_err(|source| {
+            let (line, column) = self.start_location();
+            Error::ParseError {
+                line,
+                column,
+                source,
+            }
+        })?;
 
         Ok(self.get_token(TokenType::Number, Some(Literal::Number(value))))
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-
-        return self
-            .source
-            .chars()
-            .nth(self.current + 1)
-            .expect("Could not get char from string");
+        return self.source[self.current..].chars().nth(1).unwrap_or('\0');
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-
-        return self
-            .source
-            .chars()
-            .nth(self.current)
-            .expect("Could not get char from string");
+        return self.source[self.current..].chars().next().unwrap_or('\0');
     }
 
     fn is_digit(&self, c: char) -> bool {
@@ -290,28 +456,95 @@ impl Scanner {
     }
 
     fn string(&mut self) -> Result<Token, Error> {
+        let (line, column) = self.start_location();
+
         // Consume chars until we hit the '"' that ends the string.
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() != '\n' {
-                self.line = self.line + 1;
+            // An escaped quote doesn't end the string.
+            if self.advance() == '\\' && !self.is_at_end() {
+                self.advance();
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            return Err(Error::UnterminatedString(self.line.clone()));
+            return Err(Error::UnterminatedString { line, column });
         }
 
+        // The closing ".
         self.advance();
 
-        // NOTE: If Lox supported escape sequences like \n, we'd unescape those here.
-        let value = self.source[self.start..self.current].to_owned();
+        // Trim the surrounding quotes, the lexeme keeps them.
+        let raw = &self.source[self.start + 1..self.current - 1];
+        let value = Self::unescape(raw, line, column + 1)?;
         Ok(self.get_token(TokenType::String, Some(Literal::String(value))))
     }
 
+    // Turns the raw text between the quotes, which starts at `line` and `column`, into the
+    // string's value. Knows `\n`, `\r`, `\t`, `\"`, `\\` and `\u{...}` with up to six hex digits.
+    fn unescape(raw: &str, mut line: i32, mut column: usize) -> Result<String, Error> {
+        let mut value = String::with_capacity(raw.len());
+        let mut chars = raw.chars();
+
+        while let Some(c) = chars.next() {
+            // Where `c` is, `line` and `column` move on to the next char.
+            let (at_line, at_column) = (line, column);
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+
+            let invalid = |escape: &str| Error::InvalidEscape {
+                line: at_line,
+                column: at_column,
+                escape: format!("\\{escape}"),
+            };
+
+            // An escape never spans lines, a newline after the '\' is invalid.
+            column += 1;
+
+            match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('u') => {
+                    let rest = chars.as_str();
+                    let escape = match rest.find('}') {
+                        Some(end) if rest.starts_with('{') => &rest[..=end],
+                        _ => return Err(invalid("u")),
+                    };
+                    let digits = &escape[1..escape.len() - 1];
+                    let valid = (1..=6).contains(&digits.len())
+                        && digits.chars().all(|c| c.is_ascii_hexdigit());
+                    let c = u32::from_str_radix(digits, 16)
+                        .ok()
+                        .filter(|_| valid)
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| invalid(&format!("u{escape}")))?;
+
+                    value.push(c);
+                    column += escape.chars().count();
+                    chars = rest[escape.len()..].chars();
+                }
+                Some(other) => return Err(invalid(&other.to_string())),
+                None => return Err(invalid("")),
+            }
+        }
+
+        Ok(value)
+    }
+
     fn get_token(&self, token_type: TokenType, literal: Option<Literal>) -> Token {
         let lexeme = self.source[self.start..self.current].to_owned();
-        return Token::new(token_type, lexeme, literal, self.line);
+        return Token::new(token_type, lexeme, literal, self.line).with_offset(self.start);
     }
 
     fn get_and_add_token(&mut self, token_type: TokenType) {
@@ -323,17 +556,24 @@ impl Scanner {
         self.tokens.push(token);
     }
 
+    // Also keeps track of the line we are on.
     fn advance(&mut self) -> char {
-        let curr_index = self.current;
-        let source = self
-            .source
-            .chars()
-            .nth(curr_index)
-            .expect("Could not get char from string");
-        self.current = self.current + 1;
+        let source = self.peek();
+        self.current = self.current + source.len_utf8();
+
+        if source == '\n' {
+            self.line = self.line + 1;
+            self.line_start = self.current;
+        }
         return source;
     }
 
+    // The line and column where the current token starts. Columns are in chars, not bytes.
+    fn start_location(&self) -> (i32, usize) {
+        let column = self.source[self.line_start..self.start].chars().count() + 1;
+        (self.line, column)
+    }
+
     fn is_at_end(&self) -> bool {
         return self.current >= self.source.len();
     }
@@ -343,18 +583,286 @@ impl Scanner {
             return false;
         }
 
-        let curr_index = self.current;
-        let source = self
-            .source
-            .chars()
-            .nth(curr_index)
-            .expect("Could not get char from string");
-
-        if source != expected {
+        if self.peek() != expected {
             return false;
         }
 
-        self.current = self.current + 1;
+        self.current = self.current + expected.len_utf8();
         return true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(source: &str) -> Vec<Token> {
+        Scanner::new(source.as_bytes())
+            .scan_tokens()
+            .expect("Source should scan")
+    }
+
+    #[test]
+    fn string_literal_excludes_quotes() {
+        let tokens = scan("\"hello\" \"\"");
+
+        assert_eq!(tokens[0].lexeme(), "\"hello\"");
+        assert!(matches!(
+            tokens[0].literal(),
+            Some(Literal::String(value)) if value == "hello"
+        ));
+        assert!(matches!(
+            tokens[1].literal(),
+            Some(Literal::String(value)) if value.is_empty()
+        ));
+    }
+
+    #[test]
+    fn escape_sequences_in_strings() {
+        let tokens = scan(r#""a\tb\n" "say \"hi\"" "back\\slash" "\u{48}\u{1F600}""#);
+        let values: Vec<String> = tokens[..4]
+            .iter()
+            .map(|token| match token.literal() {
+                Some(Literal::String(value)) => value,
+                other => panic!("Expected a string, got {other:?}"),
+            })
+            .collect();
+
+        assert_eq!(
+            values,
+            ["a\tb\n", "say \"hi\"", "back\\slash", "H\u{1F600}"]
+        );
+        assert_eq!(tokens[1].lexeme(), r#""say \"hi\"""#);
+    }
+
+    #[test]
+    fn invalid_escapes_are_reported_where_they_are() {
+        let invalid = |source: &str| match Scanner::new(source.as_bytes()).scan_tokens() {
+            Err(Errors(errors)) => match errors.as_slice() {
+                [Error::InvalidEscape {
+                    line,
+                    column,
+                    escape,
+                }] => (*line, *column, escape.clone()),
+                errors => panic!("Expected one invalid escape, got {errors:?}"),
+            },
+            Ok(_) => panic!("Expected {source:?} to fail"),
+        };
+
+        assert_eq!(invalid(r#""\q""#), (1, 2, r"\q".to_owned()));
+        assert_eq!(invalid("\"one\ntwo \\x\""), (2, 5, r"\x".to_owned()));
+        assert_eq!(invalid(r#""\u{110000}""#), (1, 2, r"\u{110000}".to_owned()));
+        assert_eq!(invalid(r#""\u{}""#), (1, 2, r"\u{}".to_owned()));
+        assert_eq!(invalid(r#""\u41""#), (1, 2, r"\u".to_owned()));
+        // Earlier escapes are counted with their full length
+        assert_eq!(invalid(r#"x = "\t\u{41}\q";"#), (1, 14, r"\q".to_owned()));
+    }
+
+    #[test]
+    fn errors_have_line_and_column() {
+        let errors = |source: &str| match Scanner::new(source.as_bytes()).scan_tokens() {
+            Err(Errors(errors)) => errors.iter().map(Error::location).collect::<Vec<_>>(),
+            Ok(_) => panic!("Expected {source:?} to fail"),
+        };
+
+        assert_eq!(errors("print @;"), vec![(1, 7)]);
+        assert_eq!(errors("var a;\n  a = #1 /* \n */ $"), vec![(2, 7), (3, 5)]);
+        assert_eq!(errors("print \"one\n\n  two"), vec![(1, 7)]);
+    }
+
+    #[test]
+    fn report_points_at_the_error() {
+        let source = "var a = 1;\n\tprint a @ 2;";
+        let errors = Scanner::new(source.as_bytes()).scan_tokens().unwrap_err();
+
+        assert_eq!(
+            errors.report(source),
+            "unexpected character '@' at line 2, column 10\n 2 | \tprint a @ 2;\n   | \t        ^"
+        );
+    }
+
+    #[test]
+    fn strings_only_count_their_newlines() {
+        let tokens = scan("\"long string\"\n\"two\nlines\" after");
+
+        assert_eq!(*tokens[0].line(), 1);
+        assert_eq!(*tokens[2].line(), 3);
+    }
+
+    #[test]
+    fn offsets_are_in_bytes() {
+        let tokens = scan("\"é\" a /* unterminated");
+
+        assert_eq!(tokens[0].end(), 4);
+        assert_eq!(tokens[1].offset(), 5);
+        assert_eq!(tokens[2].token_type(), &TokenType::Eof);
+    }
+
+    #[test]
+    fn non_ascii_identifiers_and_strings() {
+        let tokens = scan("var café = \"naïve 🦀\\u{e9}\";\nvar 变量 = _ü1;");
+
+        assert_eq!(tokens[1].lexeme(), "café");
+        assert!(matches!(
+            tokens[3].literal(),
+            Some(Literal::String(value)) if value == "naïve 🦀é"
+        ));
+        assert_eq!(tokens[6].lexeme(), "变量");
+        assert_eq!(*tokens[6].line(), 2);
+        assert_eq!(tokens[8].lexeme(), "_ü1");
+        // Symbols and digits don't start one
+        assert!(Scanner::new("€".as_bytes()).scan_tokens().is_err());
+        assert_eq!(scan("1x")[0].token_type(), &TokenType::Number);
+    }
+
+    #[test]
+    fn columns_count_chars_not_bytes() {
+        let source = "var ü = \"é\";\n\"🦀\" € \"\\q\"";
+        let errors = Scanner::new(source.as_bytes()).scan_tokens().unwrap_err();
+
+        assert_eq!(
+            errors.0.iter().map(Error::location).collect::<Vec<_>>(),
+            vec![(2, 5), (2, 8)]
+        );
+        assert_eq!(
+            errors.report(source).lines().collect::<Vec<_>>(),
+            vec![
+                "unexpected character '€' at line 2, column 5",
+                " 2 | \"🦀\" € \"\\q\"",
+                "   |     ^",
+                "invalid escape sequence '\\q' at line 2, column 8",
+                " 2 | \"🦀\" € \"\\q\"",
+                "   |        ^",
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_without_a_fraction_are_ints() {
+        let tokens = scan("12 1.5 99999999999999999999");
+
+        assert!(matches!(tokens[0].literal(), Some(Literal::Int(12))));
+        assert!(matches!(tokens[1].literal(), Some(Literal::Number(n)) if n == 1.5));
+        // Too big for an Int
+        assert!(matches!(tokens[2].literal(), Some(Literal::Number(n)) if n == 1e20));
+    }
+
+    #[test]
+    fn three_dots_are_one_token() {
+        let types = scan("(...args) .. .")
+            .iter()
+            .map(|token| token.token_type().clone())
+            .collect::<Vec<TokenType>>();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::LeftParen,
+                TokenType::DotDotDot,
+                TokenType::Identifier,
+                TokenType::RightParen,
+                TokenType::Dot,
+                TokenType::Dot,
+                TokenType::Dot,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    // Applies the edit to `source`, and checks that rescanning gives the same tokens as
+    // scanning the result from scratch.
+    fn check_rescan(source: &str, start: usize, old_end: usize, text: &str) {
+        let previous = scan(source);
+        let edited = format!("{}{text}{}", &source[..start], &source[old_end..]);
+        let edit = Edit {
+            start,
+            old_end,
+            new_end: start + text.len(),
+        };
+
+        // Also the errors have to be the same, when the edit broke the source.
+        let rescanned = Scanner::new(edited.as_bytes()).rescan(&previous, edit);
+        let scanned = Scanner::new(edited.as_bytes()).scan_tokens();
+
+        assert_eq!(
+            format!("{rescanned:?}"),
+            format!("{scanned:?}"),
+            "editing {source:?} into {edited:?}"
+        );
+    }
+
+    #[test]
+    fn rescan_matches_full_scan() {
+        let source = "var a = 1;\nvar bc = \"two\";\n// note\nprint a + bc;\na = a * 3;\n";
+
+        // Every single char deletion, and insertions that merge or split tokens
+        for start in 0..source.len() {
+            check_rescan(source, start, start + 1, "");
+            check_rescan(source, start, start, "x");
+            check_rescan(source, start, start, " ");
+            check_rescan(source, start, start, "\n");
+        }
+
+        check_rescan(source, 0, 0, "var z = 0;\n");
+        check_rescan(source, 4, 5, "longer_name");
+        check_rescan(source, 0, source.len(), "print 1;");
+        check_rescan(source, source.len(), source.len(), "print 2;");
+        // Turning code into a comment and back
+        check_rescan(source, 11, 11, "//");
+        check_rescan(&format!("/* x */{source}"), 0, 2, "");
+        check_rescan(source, 19, 26, "\"a\nb\"");
+
+        // Multi-byte chars, the offsets are in bytes
+        let source = "var ä = \"ö\";\nprint ä;";
+        check_rescan(source, 4, 6, "ää");
+        check_rescan(source, 10, 12, "");
+        check_rescan(source, 12, 12, "🦀");
+        check_rescan(source, 15, 15, "\"ü");
+    }
+
+    #[test]
+    fn rescan_reports_errors_in_the_edit() {
+        let previous = scan("var a = 1;");
+        let edit = Edit {
+            start: 8,
+            old_end: 9,
+            new_end: 9,
+        };
+
+        assert!(Scanner::new("var a = @;".as_bytes())
+            .rescan(&previous, edit)
+            .is_err());
+    }
+
+    #[test]
+    fn rescan_reuses_tokens_after_the_edit() {
+        let source = "a; b; c;";
+        let previous = scan(source);
+        let edit = Edit {
+            start: 0,
+            old_end: 1,
+            new_end: 3,
+        };
+
+        let tokens = Scanner::new("abc; b; c;".as_bytes())
+            .rescan(&previous, edit)
+            .unwrap();
+
+        let lexemes: Vec<(&str, usize)> = tokens
+            .iter()
+            .map(|token| (token.lexeme(), token.offset()))
+            .collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                ("abc", 0),
+                (";", 3),
+                ("b", 5),
+                (";", 6),
+                ("c", 8),
+                (";", 9),
+                ("", 0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    rc::Rc,
+};
+
+use crate::{
+    interpreter::{ErrorPolicy, Interpreter},
+    parser::{LanguageLevel, Parser},
+    resolver::Resolver,
+    scanner::Scanner,
+};
+
+// Eval server for editor integrations and notebook kernels, started with `lox serve --port N`.
+//
+// The protocol is line based. Every line a client sends is evaluated like a line typed into the
+// REPL, and answered with one line per thing that happened, ending with `done`:
+//
+//     > var a = 1; print a + 1;
+//     < out 2
+//     < done
+//     > print b;
+//     < err Undefined variable 'b' at line 1.
+//     < done
+//
+// All requests, also from different connections, run against the same interpreter, so
+// definitions stick around until the server is stopped.
+pub struct Server {
+    interpreter: Interpreter,
+    output: Output,
+    level: LanguageLevel,
+}
+
+// Collects what the interpreter prints, so it can be sent back with the response.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Server {
+    pub fn new(level: LanguageLevel) -> Self {
+        let output = Output::default();
+
+        Self {
+            interpreter: Interpreter::new()
+                .with_error_policy(ErrorPolicy::Continue)
+                .with_language_level(level)
+                .with_output(output.clone()),
+            output,
+            level,
+        }
+    }
+
+    pub fn with_strict_globals(mut self, strict_globals: bool) -> Self {
+        self.interpreter = self.interpreter.with_strict_globals(strict_globals);
+        self
+    }
+
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.interpreter = self.interpreter.with_deterministic(deterministic);
+        self
+    }
+
+    // The limit is for everything the clients define together, since they share the interpreter.
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.interpreter = self.interpreter.with_memory_limit(bytes);
+        self
+    }
+
+    // Handles one connection at a time. The interpreter isn't thread safe, and sharing it
+    // between clients is the point.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            // A client going away halfway through shouldn't take the server down with it.
+            if let Err(err) = self.handle(stream?) {
+                eprintln!("connection error: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+
+        for line in BufReader::new(stream).lines() {
+            writer.write_all(self.eval(&line?).as_bytes())?;
+            writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // Evaluates one request and returns the full response, including the final `done`.
+    pub fn eval(&self, source: &str) -> String {
+        let mut response = vec![];
+
+        match Scanner::new(source.as_bytes()).scan_tokens() {
+            // One line per error, the protocol is line based
+            Err(errors) => {
+                for line in errors.to_string().lines() {
+                    response.push(format!("err {line}"));
+                }
+            }
+            Ok(tokens) => match Parser::new(tokens)
+                .with_language_level(self.level)
+                .parse_repl()
+            {
+                Err(err) => response.push(format!("err {err}")),
+                Ok(stmts) => match Resolver::new(&self.interpreter).resolve(&stmts) {
+                    Err(err) => response.push(format!("err {err}")),
+                    Ok(()) => {
+                        let result = self.interpreter.interpret(&stmts);
+
+                        let printed = self.output.0.take();
+                        for line in String::from_utf8_lossy(&printed).lines() {
+                            response.push(format!("out {line}"));
+                        }
+
+                        if let Err(errors) = result {
+                            // One line per trace entry, the protocol is line based
+                            for report in errors.reports() {
+                                for line in report.lines() {
+                                    response.push(format!("err {line}"));
+                                }
+                            }
+                        }
+                    }
+                },
+            },
+        }
+
+        response.push("done".to_owned());
+        response.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    use super::*;
+
+    #[test]
+    fn eval_keeps_state_between_requests() {
+        let server = Server::new(LanguageLevel::Standard);
+
+        assert_eq!(server.eval("var a = 1;"), "done\n");
+        assert_eq!(server.eval("print a + 1; print a;"), "out 2\nout 1\ndone\n");
+        // Bare expressions are echoed like in the REPL
+        assert_eq!(server.eval("a + 2"), "out 3\ndone\n");
+    }
+
+    #[test]
+    fn eval_reports_errors() {
+        let server = Server::new(LanguageLevel::Standard);
+
+        assert_eq!(
+            server.eval("print 1; print b; print 2;"),
+            "out 1\nout 2\nerr Undefined variable 'b' at line 1.\ndone\n"
+        );
+        assert!(server.eval("print (1;").starts_with("err "));
+        assert!(server.eval("print @;").starts_with("err "));
+    }
+
+    #[test]
+    fn serves_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // The server has to live on the thread that created it, so it is built in there.
+        thread::spawn(move || Server::new(LanguageLevel::Standard).serve(listener));
+
+        let request = |stream: &mut TcpStream, line: &str| {
+            stream.write_all(format!("{line}\n").as_bytes()).unwrap();
+
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut response = vec![];
+            loop {
+                let mut buf = String::new();
+                reader.read_line(&mut buf).unwrap();
+                if buf == "done\n" {
+                    break response;
+                }
+                response.push(buf.trim_end().to_owned());
+            }
+        };
+
+        let mut first = TcpStream::connect(address).unwrap();
+        assert_eq!(request(&mut first, "var a = \"hi\";"), Vec::<String>::new());
+        assert_eq!(request(&mut first, "print a;"), vec!["out hi"]);
+        drop(first);
+
+        // A new connection sees what the previous one defined
+        let mut second = TcpStream::connect(address).unwrap();
+        assert_eq!(request(&mut second, "a"), vec!["out hi"]);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{interpreter::LoxCallable, token::Token};
+
+// The callbacks waiting for `runEventLoop()`, see natives::set_timeout and natives::run_event_loop.
+//
+// Time is counted in milliseconds since the interpreter started, on a clock that only moves
+// when the loop runs a timer. Timers due at the same time run in the order they were set.
+#[derive(Debug, Default)]
+pub struct Timers {
+    queue: Vec<Timer>,
+    next_id: i64,
+    now: u64,
+    // So a callback can't start the loop again while it runs.
+    pub(crate) running: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Timer {
+    pub id: i64,
+    pub due: u64,
+    pub callback: LoxCallable,
+    // Set for `setInterval`, which runs again this many milliseconds later.
+    pub interval: Option<u64>,
+    // The call that set the timer, errors in the callback are reported there.
+    pub paren: Token,
+}
+
+impl Timers {
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn set(&mut self, callback: LoxCallable, delay: u64, interval: bool, paren: Token) -> i64 {
+        self.next_id += 1;
+        self.queue.push(Timer {
+            id: self.next_id,
+            due: self.now + delay,
+            callback,
+            interval: interval.then_some(delay),
+            paren,
+        });
+        self.next_id
+    }
+
+    // Whether there was a timer with `id` that hadn't run yet, or was an interval.
+    pub fn clear(&mut self, id: i64) -> bool {
+        let before = self.queue.len();
+        self.queue.retain(|timer| timer.id != id);
+        self.queue.len() != before
+    }
+
+    // Takes the timer that is due first and moves the clock to it. An interval is put back
+    // right away for its next run, so its callback can clear it.
+    pub fn take_due(&mut self) -> Option<Timer> {
+        let (index, _) = self
+            .queue
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, timer)| timer.due)?;
+        let timer = self.queue.remove(index);
+        self.now = timer.due;
+
+        if let Some(interval) = timer.interval {
+            self.queue.push(Timer {
+                due: timer.due + interval,
+                ..timer.clone()
+            });
+        }
+
+        Some(timer)
+    }
+}
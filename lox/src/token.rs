@@ -1,8 +1,13 @@
 use std::fmt::Display;
 
+use strum::{EnumString, IntoStaticStr};
+
 #[derive(Debug, Clone)]
 pub enum Literal {
+    // A number with a fraction, `1.5`.
     Number(f64),
+    // A number without one, `2`.
+    Int(i64),
     String(String),
     True,
     False,
@@ -30,6 +35,7 @@ impl Display for Literal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Literal::Number(val) => write!(f, "{}", val),
+            Literal::Int(val) => write!(f, "{}", val),
             Literal::String(val) => write!(f, "{}", val),
             Literal::True => write!(f, "{}", true),
             Literal::False => write!(f, "{}", false),
@@ -44,6 +50,8 @@ pub struct Token {
     lexeme: String,
     literal: Option<Literal>,
     line: i32,
+    // Byte index of the first char of the lexeme in the source.
+    offset: usize,
 }
 
 impl Token {
@@ -53,9 +61,15 @@ impl Token {
             lexeme,
             literal,
             line,
+            offset: 0,
         }
     }
 
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
     pub fn lexeme(&self) -> &str {
         &self.lexeme
     }
@@ -71,6 +85,23 @@ impl Token {
     pub fn line(&self) -> &i32 {
         &self.line
     }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // Byte index of the first char after the lexeme.
+    pub fn end(&self) -> usize {
+        self.offset + self.lexeme.len()
+    }
+
+    // The same token, moved by an edit before it.
+    pub(crate) fn shifted(&self, offset: isize, lines: i32) -> Self {
+        let mut token = self.clone();
+        token.offset = token.offset.saturating_add_signed(offset);
+        token.line += lines;
+        token
+    }
 }
 
 impl Display for Token {
@@ -83,13 +114,16 @@ impl Display for Token {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// EnumString and IntoStaticStr go by the variant names, the cache writes token types that way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, IntoStaticStr)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -98,6 +132,7 @@ pub enum TokenType {
     Colon,
     Slash,
     Star,
+    Percent,
     QuestionMark,
 
     // One or two character tokens.
@@ -109,6 +144,9 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    // `...` before a rest parameter.
+    DotDotDot,
 
     // Literals.
     Identifier,
@@ -117,19 +155,32 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    Break,
+    Case,
+    Catch,
     Class,
+    Const,
+    Continue,
+    Default,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
+    Is,
+    Loop,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
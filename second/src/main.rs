@@ -1,64 +1,329 @@
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::rc::{Rc, Weak};
 
-// type NodeLink = Option<Rc<RefCell<Node>>>;
-type NodeLink = Option<Box<Node>>;
+// With Box every node can only have one owner, but in a doubly linked list both neighbours
+// point at it. So `next` owns the node after it through an Rc, and `prev` only has a Weak
+// back to the node before it. Two Rcs pointing at each other would never be freed.
+type NodeLink<T> = Option<Rc<RefCell<Node<T>>>>;
+type WeakLink<T> = Option<Weak<RefCell<Node<T>>>>;
 
-#[derive(Clone)]
-struct Node {
-    prev: NodeLink,
-    next: NodeLink,
-    value: i32,
+struct Node<T> {
+    prev: WeakLink<T>,
+    next: NodeLink<T>,
+    value: T,
 }
 
-impl Node {
-    fn new(value: i32) -> Box<Node> {
-        // Rc::new(RefCell::new(Node {
-        //     value,
-        //     prev: None,
-        //     next: None,
-        // }))
-
-        Box::new(Node {
+impl<T> Node<T> {
+    fn new(value: T) -> Rc<RefCell<Node<T>>> {
+        Rc::new(RefCell::new(Node {
             value,
             prev: None,
             next: None,
+        }))
+    }
+}
+
+struct LinkedList<T> {
+    first: NodeLink<T>,
+    last: NodeLink<T>,
+}
+
+impl<T> LinkedList<T> {
+    fn new() -> Self {
+        LinkedList {
+            first: None,
+            last: None,
+        }
+    }
+
+    fn append(&mut self, value: T) {
+        let new_last = Node::new(value);
+
+        // .take() moves the value of the Option, leaving a None in its place.
+        match self.last.take() {
+            None => self.first = Some(new_last.clone()),
+            Some(prev_last) => {
+                new_last.borrow_mut().prev = Some(Rc::downgrade(&prev_last));
+                prev_last.borrow_mut().next = Some(new_last.clone());
+            }
+        }
+
+        self.last = Some(new_last);
+    }
+
+    fn prepend(&mut self, value: T) {
+        let new_first = Node::new(value);
+
+        match self.first.take() {
+            None => self.last = Some(new_first.clone()),
+            Some(prev_first) => {
+                prev_first.borrow_mut().prev = Some(Rc::downgrade(&new_first));
+                new_first.borrow_mut().next = Some(prev_first);
+            }
+        }
+
+        self.first = Some(new_first);
+    }
+
+    fn pop_front(&mut self) -> Option<T> {
+        self.first.take().map(|old_first| {
+            match old_first.borrow_mut().next.take() {
+                Some(new_first) => {
+                    new_first.borrow_mut().prev = None;
+                    self.first = Some(new_first);
+                }
+                // It was the only node, so `last` points at it too
+                None => self.last = None,
+            }
+
+            Self::into_value(old_first)
         })
     }
 
+    fn pop_back(&mut self) -> Option<T> {
+        self.last.take().map(|old_last| {
+            let prev = old_last.borrow_mut().prev.take();
+            match prev.and_then(|prev| prev.upgrade()) {
+                Some(new_last) => {
+                    // Drops the other strong reference to the old last node
+                    new_last.borrow_mut().next = None;
+                    self.last = Some(new_last);
+                }
+                None => self.first = None,
+            }
 
+            Self::into_value(old_last)
+        })
+    }
+
+    fn iter(&self) -> Iter<'_, T>
+    where
+        T: Clone,
+    {
+        Iter {
+            next: self.first.clone(),
+            _list: PhantomData,
+        }
+    }
+
+    fn into_vec(mut self) -> Vec<T> {
+        let mut values = Vec::new();
+        while let Some(value) = self.pop_front() {
+            values.push(value);
+        }
+        values
+    }
+
+    // Once a node is unlinked the list and its neighbours have let go of it,
+    // so this is the last Rc and the value can be moved out.
+    fn into_value(node: Rc<RefCell<Node<T>>>) -> T {
+        match Rc::try_unwrap(node) {
+            Ok(node) => node.into_inner().value,
+            Err(_) => panic!("Popped node is still referenced"),
+        }
+    }
 }
 
-struct LinkedList {
-    first: NodeLink,
-    last: NodeLink,
+impl<T> From<Vec<T>> for LinkedList<T> {
+    fn from(values: Vec<T>) -> Self {
+        let mut list = LinkedList::new();
+        for value in values {
+            list.append(value);
+        }
+        list
+    }
 }
 
-impl LinkedList {
-    fn new() -> Self {
-        LinkedList {first: None, last: None}
+// Prints `1 -> 2 -> 3`, and nothing at all for an empty list.
+impl<T: Display> Display for LinkedList<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut next = self.first.clone();
+        while let Some(node) = next {
+            let node = node.borrow();
+            write!(f, "{}", node.value)?;
+            if node.next.is_some() {
+                write!(f, " -> ")?;
+            }
+            next = node.next.clone();
+        }
+        Ok(())
     }
+}
 
-   fn append(&mut self, value: i32) {
-       let mut new_last = Node::new(value);
+impl<T> Drop for LinkedList<T> {
+    // Dropping `first` would free the nodes recursively and can blow the stack on long lists.
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
 
-       // .take() moves the value of the Option, leaving a None in its place.
-       match self.last.take() {
-           None => {
-               self.last = Some(new_last.clone())
-           }
-           Some(mut prev_last) => {
-               prev_last.next = Some(new_last.clone());
-               new_last.prev = Some(prev_last);
+// Holds an Rc to the next node, since a RefCell borrow can't be handed out past the node.
+// So it yields clones of the values rather than references.
+struct Iter<'a, T> {
+    next: NodeLink<T>,
+    // The list has to stay borrowed while we hold on to its nodes, or popping them would fail.
+    _list: PhantomData<&'a LinkedList<T>>,
+}
 
-               self.last = Some(new_last)
-           }
-       }
-   }
+// Without a Drop impl the borrow of the list could end before the Rc we hold is dropped.
+impl<T> Drop for Iter<'_, T> {
+    fn drop(&mut self) {}
 }
 
+impl<T: Clone> Iterator for Iter<'_, T> {
+    type Item = T;
 
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.take().map(|node| {
+            let node = node.borrow();
+            self.next = node.next.clone();
+            node.value.clone()
+        })
+    }
+}
 
 fn main() {
-    println!("Hello, world!");
+    let mut list = LinkedList::from(vec![2, 3]);
+    list.prepend(1);
+    println!("{}", list);
+    println!("sum {}", list.iter().sum::<i32>());
+
+    if let Some(value) = list.pop_back() {
+        println!("popped {}", value);
+    }
+    println!("{:?}", list.into_vec());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_and_pop_front() {
+        let mut list = LinkedList::new();
+        assert_eq!(list.pop_front(), None);
+
+        list.append(1);
+        list.append(2);
+        list.append(3);
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_front(), Some(2));
+
+        list.append(4);
+        assert_eq!(list.pop_front(), Some(3));
+        assert_eq!(list.pop_front(), Some(4));
+        assert_eq!(list.pop_front(), None);
+
+        // Make sure `last` got cleared on exhaustion
+        list.append(5);
+        assert_eq!(list.pop_back(), Some(5));
+    }
+
+    #[test]
+    fn prepend_and_pop_back() {
+        let mut list = LinkedList::new();
+        assert_eq!(list.pop_back(), None);
+
+        list.prepend(3);
+        list.prepend(2);
+        list.prepend(1);
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_back(), Some(2));
+
+        list.prepend(0);
+        assert_eq!(list.pop_back(), Some(1));
+        assert_eq!(list.pop_back(), Some(0));
+        assert_eq!(list.pop_back(), None);
+
+        list.prepend(5);
+        assert_eq!(list.pop_front(), Some(5));
+    }
+
+    #[test]
+    fn iter() {
+        let mut list = LinkedList::new();
+        assert_eq!(list.iter().next(), None);
+
+        list.append(2);
+        list.prepend(1);
+        list.append(3);
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        list.pop_back();
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn nodes_are_freed() {
+        let mut list = LinkedList::new();
+        list.append(1);
+        list.append(2);
+
+        let first = Rc::downgrade(list.first.as_ref().unwrap());
+        let last = Rc::downgrade(list.last.as_ref().unwrap());
+
+        assert_eq!(list.pop_front(), Some(1));
+        assert!(first.upgrade().is_none());
+
+        drop(list);
+        assert!(last.upgrade().is_none());
+    }
+
+    #[test]
+    fn generic_values() {
+        let mut list = LinkedList::new();
+        list.append(String::from("b"));
+        list.prepend(String::from("a"));
+        list.append(String::from("c"));
+
+        assert_eq!(list.iter().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert_eq!(list.pop_back(), Some(String::from("c")));
+        assert_eq!(list.pop_front(), Some(String::from("a")));
+        assert_eq!(list.pop_front(), Some(String::from("b")));
+        assert_eq!(list.pop_front(), None);
+
+        // Values that aren't Clone can still go in and out, just not through iter
+        let mut list = LinkedList::new();
+        list.append(Box::new(1));
+        assert_eq!(list.pop_back(), Some(Box::new(1)));
+    }
+
+    #[test]
+    fn display() {
+        let mut list = LinkedList::new();
+        assert_eq!(list.to_string(), "");
+
+        list.append(1);
+        assert_eq!(list.to_string(), "1");
+
+        list.append(2);
+        list.append(3);
+        assert_eq!(list.to_string(), "1 -> 2 -> 3");
+    }
+
+    #[test]
+    fn vec_round_trip() {
+        let values = vec![1, 2, 3, 4];
+        let list = LinkedList::from(values.clone());
+        assert_eq!(list.iter().collect::<Vec<_>>(), values);
+        assert_eq!(list.into_vec(), values);
+
+        let empty: LinkedList<i32> = LinkedList::from(Vec::new());
+        assert_eq!(empty.into_vec(), Vec::<i32>::new());
+
+        let mut list = LinkedList::from(vec![String::from("b")]);
+        list.prepend(String::from("a"));
+        assert_eq!(list.to_string(), "a -> b");
+        assert_eq!(list.into_vec(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn long_list_drop() {
+        let mut list = LinkedList::new();
+        for value in 0..100_000 {
+            list.append(value);
+        }
+    }
 }
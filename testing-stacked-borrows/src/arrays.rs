@@ -1,4 +1,4 @@
-pub fn first() {
+pub fn first() -> [i32; 10] {
     unsafe {
         let mut data = [0; 10];
         let ref1_at_0 = &mut data[0]; // Reference to 0th element;
@@ -10,11 +10,11 @@ pub fn first() {
         *ref1_at_0 += 1;
 
         // Expecting [3, 3, 0, ...]
-        println!("{:?}", &data[..])
+        data
     }
 }
 
-pub fn second() {
+pub fn second() -> [i32; 10] {
     unsafe {
         let mut data = [0; 10];
 
@@ -37,11 +37,11 @@ pub fn second() {
         *ref4_at_0 += 4;
 
         // Should be [10, 12, 0, ...]
-        println!("{:?}", &data[..])
+        data
     }
 }
 
-pub fn third() {
+pub fn third() -> [usize; 10] {
     unsafe {
         let mut data = [0; 10];
 
@@ -68,6 +68,26 @@ pub fn third() {
             *elem_ref += idx;
         }
 
-        println!("{:?}", &data[..])
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    // ref1_at_0 only borrows the 0th element, so ptr3_at_1 points outside of what it may access.
+    #[cfg_attr(miri, ignore = "Undefined Behaviour on purpose")]
+    fn first() {
+        assert_eq!(super::first(), [3, 3, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn second() {
+        assert_eq!(super::second(), [10, 12, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn third() {
+        assert_eq!(super::third(), [8, 12, 4, 6, 8, 10, 12, 14, 16, 18]);
     }
 }
@@ -1,6 +1,6 @@
 use std::cell::{Cell, UnsafeCell};
 
-pub fn first() {
+pub fn first() -> i32 {
     unsafe {
         let mut data = Cell::new(10);
         let mref1 = &mut data;
@@ -11,7 +11,7 @@ pub fn first() {
         (*ptr2).set((*ptr2).get() + 2);
         mref1.set(mref1.get() + 1);
 
-        println!("{}", data.get())
+        data.get()
     }
 }
 
@@ -19,7 +19,7 @@ fn opaque_read(val: &i32) {
     println!("{}", val)
 }
 
-pub fn second() {
+pub fn second() -> i32 {
     unsafe {
         let mut data = UnsafeCell::new(10);
         // let mref1 = data.get_mut();
@@ -37,11 +37,11 @@ pub fn second() {
         *sref3.get() += 3;
         *mref1.get() += 1;
 
-        println!("{:?}", data.get())
+        *data.get()
     }
 }
 
-pub fn third() {
+pub fn third() -> i32 {
     unsafe {
         let mut data = UnsafeCell::new(10);
         let mref1 = &mut data;
@@ -56,6 +56,24 @@ pub fn third() {
         *sref2.get() += 2;
         *mref1.get() += 1;
 
-        println!("{}", *data.get())
+        *data.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn first() {
+        assert_eq!(super::first(), 16);
+    }
+
+    #[test]
+    fn second() {
+        assert_eq!(super::second(), 16);
+    }
+
+    #[test]
+    fn third() {
+        assert_eq!(super::third(), 16);
     }
 }
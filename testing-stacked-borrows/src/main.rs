@@ -1,9 +1,21 @@
+// The experiments are run by the tests, under miri, and some are Undefined Behaviour on
+// purpose. `main` only runs one that isn't, so outside the tests the rest is unused.
+#[cfg_attr(not(test), allow(dead_code))]
 mod arrays;
+#[cfg_attr(not(test), allow(dead_code))]
 mod interior_mut;
+#[cfg_attr(not(test), allow(dead_code))]
+mod pinning;
+#[cfg_attr(not(test), allow(dead_code))]
 mod shared_ref;
 mod testing_box;
+#[cfg_attr(not(test), allow(dead_code))]
+mod threads;
+#[cfg_attr(not(test), allow(dead_code))]
+mod uninit;
 
-fn first() {
+#[cfg_attr(not(test), allow(dead_code))]
+fn first() -> i32 {
     unsafe {
         let mut data = 10;
         let ref1 = &mut data;
@@ -15,12 +27,13 @@ fn first() {
         *ref1 += 1;
         *ptr2 += 2; // Will not exist in borrow stack at this point
 
-        println!("{}", data);
+        data
     }
 }
 
 // &mut -> *mut -> &mut -> *mut
-fn second() {
+#[cfg_attr(not(test), allow(dead_code))]
+fn second() -> i32 {
     unsafe {
         let mut data = 10;
 
@@ -44,7 +57,7 @@ fn second() {
         *ptr2 += 2;
         *ref1 += 1;
 
-        println!("{}", data);
+        data
     }
 }
 
@@ -56,5 +69,28 @@ fn main() {
     // arrays::third();
     // shared_ref::first();
     // interior_mut::second();
-    testing_box::first();
+    // pinning::unpinned_move();
+    // pinning::pinned_move();
+    // uninit::manual_alloc(5);
+    // threads::spin_lock_counter(4, 1000);
+    println!("{}", testing_box::first());
+}
+
+// Run these with `cargo miri test`. The ones that are Undefined Behaviour on purpose are
+// ignored under miri, everything else has to stay clean.
+#[cfg(test)]
+mod tests {
+    #[test]
+    // Using ref1 pops ptr2 off the borrow stack.
+    #[cfg_attr(miri, ignore = "Undefined Behaviour on purpose")]
+    fn first() {
+        assert_eq!(super::first(), 13);
+    }
+
+    #[test]
+    // Using ptr2 first pops ref3 and ptr4 off the borrow stack.
+    #[cfg_attr(miri, ignore = "Undefined Behaviour on purpose")]
+    fn second() {
+        assert_eq!(super::second(), 22);
+    }
 }
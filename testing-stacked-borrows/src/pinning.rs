@@ -0,0 +1,101 @@
+use std::{marker::PhantomPinned, pin::Pin, ptr};
+
+// A struct pointing into itself. Nothing stops it from being moved, and moving is just a memcpy,
+// so `ptr` keeps pointing at wherever `value` used to live.
+struct SelfRef {
+    value: String,
+    ptr: *const String,
+}
+
+impl SelfRef {
+    fn new(value: &str) -> Self {
+        SelfRef {
+            value: value.to_owned(),
+            ptr: ptr::null(),
+        }
+    }
+
+    // Can only be done once the struct sits where it is going to stay. Which it can't promise.
+    fn init(&mut self) {
+        self.ptr = &self.value;
+    }
+
+    fn points_at_itself(&self) -> bool {
+        ptr::eq(self.ptr, &self.value)
+    }
+}
+
+// Returns whether the pointer is still right (before, after) moving the struct.
+// Reading through `ptr` after the move would be a read of moved-from memory, so we only compare.
+pub fn unpinned_move() -> (bool, bool) {
+    let mut self_ref = SelfRef::new("hello");
+    self_ref.init();
+    let before = self_ref.points_at_itself();
+
+    // Moving it to the heap gives `value` a new address, but `ptr` still has the old one.
+    let moved = Box::new(self_ref);
+    let after = moved.points_at_itself();
+
+    (before, after)
+}
+
+// Same struct, but PhantomPinned makes it !Unpin. Once it is behind a Pin it can't be moved out
+// again with safe code, so setting up the pointer after pinning is enough to keep it valid.
+struct PinnedSelfRef {
+    value: String,
+    ptr: *const String,
+    _pin: PhantomPinned,
+}
+
+impl PinnedSelfRef {
+    fn new(value: &str) -> Pin<Box<Self>> {
+        let mut boxed = Box::pin(PinnedSelfRef {
+            value: value.to_owned(),
+            ptr: ptr::null(),
+            _pin: PhantomPinned,
+        });
+
+        // Only writes a field, nothing gets moved out of the pin.
+        let this = unsafe { boxed.as_mut().get_unchecked_mut() };
+        this.ptr = &this.value;
+
+        boxed
+    }
+
+    fn value(self: Pin<&Self>) -> &str {
+        // Pinned, so `ptr` still points at our own `value`.
+        unsafe { &*self.ptr }
+    }
+
+    fn points_at_itself(self: Pin<&Self>) -> bool {
+        ptr::eq(self.ptr, &self.value)
+    }
+}
+
+// Returns the value read back through the self pointer after moving the pinned box around.
+pub fn pinned_move() -> (bool, String) {
+    let pinned = PinnedSelfRef::new("hello");
+
+    // Moving the Pin<Box> only moves the pointer to the heap allocation, not the struct in it.
+    let moved = pinned;
+    let mut boxes = vec![moved];
+    let moved = boxes.pop().unwrap();
+
+    (
+        moved.as_ref().points_at_itself(),
+        moved.as_ref().value().to_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn unpinned_move() {
+        assert_eq!(super::unpinned_move(), (true, false));
+    }
+
+    #[test]
+    fn pinned_move() {
+        assert_eq!(super::pinned_move(), (true, String::from("hello")));
+    }
+}
@@ -1,7 +1,7 @@
 fn opaque_read(value: &i32) {
     println!("{}", value);
 }
-pub fn first() {
+pub fn first() -> i32 {
     unsafe {
         let mut data = 10;
 
@@ -18,5 +18,16 @@ pub fn first() {
         *mref1 += 1;
 
         opaque_read(&data);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    // Writing through ptr2 pops sref3 off the borrow stack before it is read.
+    #[cfg_attr(miri, ignore = "Undefined Behaviour on purpose")]
+    fn first() {
+        assert_eq!(super::first(), 13);
     }
 }
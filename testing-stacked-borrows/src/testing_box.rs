@@ -1,4 +1,4 @@
-pub fn first() {
+pub fn first() -> i32 {
     unsafe {
         let mut data = Box::new(10);
         let ptr1 = &mut *data as *mut i32;
@@ -10,6 +10,14 @@ pub fn first() {
         *ptr1 += 1;
         *data += 10;
 
-        println!("{}", data);
+        *data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn first() {
+        assert_eq!(super::first(), 21);
     }
 }
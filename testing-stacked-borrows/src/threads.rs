@@ -0,0 +1,184 @@
+// Sharing an UnsafeCell between threads.
+//
+// Everything here goes through the loom types when built with `--cfg loom`, so the same code
+// can be checked by miri (`cargo miri test`) and model checked by loom
+// (`RUSTFLAGS="--cfg loom" cargo test --release threads`).
+#[cfg(loom)]
+use loom::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+#[cfg(not(loom))]
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+// std's UnsafeCell with loom's closure based API, so the code below doesn't care which one it has.
+#[cfg(not(loom))]
+struct UnsafeCell<T>(std::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    fn new(data: T) -> Self {
+        UnsafeCell(std::cell::UnsafeCell::new(data))
+    }
+
+    fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+// UnsafeCell is !Sync, so it can't be shared between threads at all without lying about it.
+// Whether this is actually fine depends entirely on how the cell gets used.
+struct SharedCell<T>(UnsafeCell<T>);
+
+unsafe impl<T: Send> Sync for SharedCell<T> {}
+
+// Runs `threads` threads which each increment the shared value `per_thread` times,
+// and returns the final value.
+fn count_with(
+    threads: usize,
+    per_thread: usize,
+    increment: impl Fn(&SharedCell<usize>) + Send + Sync + 'static,
+) -> usize {
+    let cell = Arc::new(SharedCell(UnsafeCell::new(0)));
+    let increment = Arc::new(increment);
+
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let cell = cell.clone();
+            let increment = increment.clone();
+            thread::spawn(move || {
+                for _ in 0..per_thread {
+                    increment(&cell);
+                }
+            })
+        })
+        .collect();
+
+    // Joining synchronises with everything the threads did.
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    match Arc::try_unwrap(cell) {
+        Ok(cell) => cell.0.into_inner(),
+        Err(_) => unreachable!("All threads have been joined"),
+    }
+}
+
+// A spin lock in front of the cell. The Acquire on taking the lock pairs with the Release on
+// giving it back, so each write through the cell happens-before the next thread's read.
+pub fn spin_lock_counter(threads: usize, per_thread: usize) -> usize {
+    let locked = Arc::new(AtomicBool::new(false));
+
+    count_with(threads, per_thread, move |cell| {
+        while locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            thread::yield_now();
+        }
+
+        cell.0.with_mut(|ptr| unsafe { *ptr += 1 });
+
+        locked.store(false, Ordering::Release);
+    })
+}
+
+// For comparison: no UnsafeCell at all, the atomic does both the sharing and the synchronising.
+pub fn atomic_counter(threads: usize, per_thread: usize) -> usize {
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let counter = counter.clone();
+            thread::spawn(move || {
+                for _ in 0..per_thread {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    counter.load(Ordering::Relaxed)
+}
+
+// No synchronisation, so two threads can read-modify-write at the same time. That is a data
+// race, which is Undefined Behaviour, not just a lost update.
+// Only built with `--features ub`: miri reports the race, loom panics on the concurrent access.
+#[cfg(feature = "ub")]
+pub fn racy_counter(threads: usize, per_thread: usize) -> usize {
+    count_with(threads, per_thread, |cell| {
+        cell.0.with_mut(|ptr| unsafe { *ptr += 1 })
+    })
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    // Miri is slow, and the interesting part is that threads interleave at all.
+    const THREADS: usize = 4;
+    const PER_THREAD: usize = if cfg!(miri) { 20 } else { 10_000 };
+
+    #[test]
+    fn spin_lock_counter() {
+        assert_eq!(
+            super::spin_lock_counter(THREADS, PER_THREAD),
+            THREADS * PER_THREAD
+        );
+    }
+
+    #[test]
+    fn atomic_counter() {
+        assert_eq!(
+            super::atomic_counter(THREADS, PER_THREAD),
+            THREADS * PER_THREAD
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "ub")]
+    fn racy_counter() {
+        // Without miri this usually "works", and sometimes loses updates.
+        assert!(super::racy_counter(THREADS, PER_THREAD) <= THREADS * PER_THREAD);
+    }
+}
+
+// loom runs the closure once for every possible interleaving, so keep them tiny.
+#[cfg(all(test, loom))]
+mod tests {
+    #[test]
+    fn spin_lock_counter() {
+        loom::model(|| assert_eq!(super::spin_lock_counter(2, 2), 4));
+    }
+
+    #[test]
+    fn atomic_counter() {
+        loom::model(|| assert_eq!(super::atomic_counter(2, 2), 4));
+    }
+
+    #[test]
+    #[cfg(feature = "ub")]
+    #[should_panic]
+    fn racy_counter() {
+        loom::model(|| {
+            super::racy_counter(2, 1);
+        });
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    alloc::{alloc, dealloc, Layout},
+    mem::MaybeUninit,
+};
+
+// Build an array of non-Copy values one element at a time.
+// A plain `[String; 4]` would have to be filled with something first, so every element
+// starts out as MaybeUninit and the whole array is only assumed init once all of them are written.
+pub fn array_of_strings() -> [String; 4] {
+    let mut data: [MaybeUninit<String>; 4] = [const { MaybeUninit::uninit() }; 4];
+
+    for (idx, elem) in data.iter_mut().enumerate() {
+        elem.write(idx.to_string());
+    }
+
+    // Every element was written above. MaybeUninit<T> has the same layout as T.
+    data.map(|elem| unsafe { elem.assume_init() })
+}
+
+// Same idea, but through a raw pointer to the first element and `add` to get to the rest.
+pub fn pointer_offset_init() -> [u64; 8] {
+    let mut data = MaybeUninit::<[u64; 8]>::uninit();
+    let ptr = data.as_mut_ptr() as *mut u64;
+
+    unsafe {
+        for idx in 0..8 {
+            // `write` instead of `*ptr = ..`, since assigning would drop the (garbage) old value.
+            // Doesn't matter for u64 but it would for anything with a destructor.
+            ptr.add(idx).write(1 << idx);
+        }
+
+        data.assume_init()
+    }
+}
+
+// Allocate, initialise, read and free memory by hand, like a Vec does under the hood.
+pub fn manual_alloc(len: usize) -> Vec<u32> {
+    assert!(len > 0, "Zero sized allocations are not allowed");
+
+    let layout = Layout::array::<u32>(len).unwrap();
+
+    unsafe {
+        let ptr = alloc(layout) as *mut u32;
+        if ptr.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+
+        for idx in 0..len {
+            ptr.add(idx).write((idx * idx) as u32);
+        }
+
+        let values = std::slice::from_raw_parts(ptr, len).to_vec();
+
+        // Must be freed with the same layout it was allocated with.
+        dealloc(ptr as *mut u8, layout);
+
+        values
+    }
+}
+
+// Forgets to initialise the last element before assuming the array is init.
+// Only built with `--features ub`, so `cargo miri test --features ub` can show miri catching it.
+#[cfg(feature = "ub")]
+pub fn partially_init() -> [u32; 4] {
+    let mut data = MaybeUninit::<[u32; 4]>::uninit();
+    let ptr = data.as_mut_ptr() as *mut u32;
+
+    unsafe {
+        for idx in 0..3 {
+            ptr.add(idx).write(idx as u32);
+        }
+
+        data.assume_init()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn array_of_strings() {
+        assert_eq!(super::array_of_strings(), ["0", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn pointer_offset_init() {
+        assert_eq!(super::pointer_offset_init(), [1, 2, 4, 8, 16, 32, 64, 128]);
+    }
+
+    #[test]
+    fn manual_alloc() {
+        assert_eq!(super::manual_alloc(5), vec![0, 1, 4, 9, 16]);
+    }
+
+    #[test]
+    #[cfg(feature = "ub")]
+    fn partially_init() {
+        // Miri reports the read of the uninitialised element. Without miri this "works",
+        // and the last element is whatever was in that memory.
+        let data = super::partially_init();
+        assert_eq!(&data[..3], &[0, 1, 2]);
+    }
+}